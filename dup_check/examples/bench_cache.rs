@@ -0,0 +1,106 @@
+//! Times writing hashes into a fresh cache database: one transaction per
+//! hash with a sync on every commit, the closest to the cache before WAL
+//! mode and batching; one per hash with the default `synchronous=NORMAL`;
+//! batches; and the background writer that scans use.
+//!
+//! ```bash
+//! cargo run --release --example bench_cache -- [ENTRIES]
+//! ```
+//!
+//! ENTRIES defaults to 10000. The per-hash runs write a tenth of that,
+//! scaled up in the report, as they would otherwise take minutes on a disk.
+
+use dup_check::{
+    cache::{CacheEntry, CachePragma},
+    utils::HashAlgorithm,
+    Cache,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+fn main() -> anyhow::Result<()> {
+    let entries: usize = std::env::args()
+        .nth(1)
+        .map_or(Ok(10_000), |value| value.parse())?;
+    let dir = TempDir::new()?;
+    println!(
+        "{} entries into databases under {}",
+        entries,
+        dir.path().display()
+    );
+
+    let synced: CachePragma = "synchronous=FULL".parse()?;
+    let per_hash_synced = time_per_hash(&dir.path().join("synced.db"), &[synced], entries)?;
+    let per_hash = time_per_hash(&dir.path().join("per-hash.db"), &[], entries)?;
+
+    let cache = Cache::open(&dir.path().join("batched.db"), &[])?;
+    let batch: Vec<CacheEntry> = (0..entries).map(entry).collect();
+    let started = Instant::now();
+    for chunk in batch.chunks(256) {
+        cache.store_batch(chunk)?;
+    }
+    let batched = started.elapsed();
+
+    let cache = Arc::new(Cache::open(&dir.path().join("writer.db"), &[])?);
+    let started = Instant::now();
+    let writer = Arc::clone(&cache).spawn_writer();
+    for i in 0..entries {
+        writer.send(&path(i), 1024, None, HashAlgorithm::Sha256, &hash(i));
+    }
+    let written = writer.finish()?;
+    let background = started.elapsed();
+    anyhow::ensure!(written == entries, "The writer lost entries");
+    anyhow::ensure!(
+        cache.entries()?.len() == entries,
+        "Not every entry was stored"
+    );
+
+    for (name, time) in [
+        ("per hash, synchronous=FULL", per_hash_synced),
+        ("per hash, synchronous=NORMAL", per_hash),
+        ("batches of 256", batched),
+        ("background writer", background),
+    ] {
+        println!(
+            "{:<30} {:>10.3?}  ({:>9.0} entries/s)",
+            name,
+            time,
+            entries as f64 / time.as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
+/// Time storing a tenth of `entries` one `store_hash` call at a time, scaled
+/// up to all of them
+fn time_per_hash(db: &Path, pragmas: &[CachePragma], entries: usize) -> anyhow::Result<Duration> {
+    let cache = Cache::open(db, pragmas)?;
+    let sample = (entries / 10).max(1);
+    let started = Instant::now();
+    for i in 0..sample {
+        cache.store_hash(&path(i), 1024, None, &hash(i))?;
+    }
+    Ok(started.elapsed().mul_f64(entries as f64 / sample as f64))
+}
+
+fn entry(i: usize) -> CacheEntry {
+    CacheEntry {
+        path: path(i).to_string_lossy().into_owned(),
+        size: 1024,
+        modified: None,
+        algorithm: HashAlgorithm::Sha256,
+        hash: hash(i),
+    }
+}
+
+fn path(i: usize) -> PathBuf {
+    PathBuf::from(format!("/data/dir{}/file{}.bin", i % 100, i))
+}
+
+fn hash(i: usize) -> String {
+    format!("{:064x}", i)
+}
@@ -0,0 +1,80 @@
+//! Times confirming that two large identical files match: hashing both with
+//! the buffered reader, comparing them through buffers, and comparing their
+//! memory maps as `--mmap-threshold` does.
+//!
+//! ```bash
+//! cargo run --release --example bench_mmap -- [SIZE_MB] [RUNS]
+//! ```
+//!
+//! The pair is SIZE_MB megabytes each (default 256). The first run of each
+//! method reads from the page cache too, since the files were just written.
+
+use dup_check::utils::{self, HashAlgorithm};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+fn main() -> anyhow::Result<()> {
+    let arg = |index: usize, default: usize| -> anyhow::Result<usize> {
+        std::env::args()
+            .nth(index)
+            .map_or(Ok(default), |value| Ok(value.parse()?))
+    };
+    let size_mb = arg(1, 256)?;
+    let runs = arg(2, 3)?.max(1);
+
+    let dir = TempDir::new()?;
+    let (a, b) = (dir.path().join("a.bin"), dir.path().join("b.bin"));
+    write_file(&a, size_mb)?;
+    write_file(&b, size_mb)?;
+    println!(
+        "Two identical files of {} MB under {}",
+        size_mb,
+        dir.path().display()
+    );
+
+    let hashed = best_of(runs, || {
+        Ok(utils::calculate_hash_with(&a, HashAlgorithm::Sha256)?
+            == utils::calculate_hash_with(&b, HashAlgorithm::Sha256)?)
+    })?;
+    let buffered = best_of(runs, || utils::files_equal(&a, &b))?;
+    let mapped = best_of(runs, || utils::files_equal_mapped(&a, &b))?;
+
+    println!("hash both (SHA-256): {:>10.3?}", hashed);
+    println!("buffered compare:    {:>10.3?}", buffered);
+    println!("mmap compare:        {:>10.3?}", mapped);
+    println!(
+        "mmap speedup:        {:>10.2}x over hashing, {:.2}x over buffers",
+        hashed.as_secs_f64() / mapped.as_secs_f64(),
+        buffered.as_secs_f64() / mapped.as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Write `size_mb` megabytes of a pattern that doesn't repeat within a block
+fn write_file(path: &Path, size_mb: usize) -> anyhow::Result<()> {
+    let block: Vec<u8> = (0..1024 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let mut out = BufWriter::new(File::create(path)?);
+    for _ in 0..size_mb {
+        out.write_all(&block)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The fastest of `runs` calls to `compare`, which must find the files equal
+fn best_of(runs: usize, compare: impl Fn() -> anyhow::Result<bool>) -> anyhow::Result<Duration> {
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        let started = Instant::now();
+        anyhow::ensure!(compare()?, "Identical files compared as different");
+        best = best.min(started.elapsed());
+    }
+    Ok(best)
+}
@@ -0,0 +1,108 @@
+//! Times a scan of a deep directory tree walked on one thread against the
+//! same scan walked in parallel, and checks that both find the same groups.
+//!
+//! ```bash
+//! cargo run --release --example bench_walk -- [DEPTH] [FILES_PER_DIR] [RUNS]
+//! ```
+//!
+//! The tree is a binary tree of directories DEPTH levels deep (default 10,
+//! so 2047 directories), each holding FILES_PER_DIR files (default 4) whose
+//! contents repeat often enough to form duplicate groups.
+
+use dup_check::{FileInfo, Scanner};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+fn main() -> anyhow::Result<()> {
+    let arg = |index: usize, default: usize| -> anyhow::Result<usize> {
+        std::env::args()
+            .nth(index)
+            .map_or(Ok(default), |value| Ok(value.parse()?))
+    };
+    let depth = arg(1, 10)?;
+    let files_per_dir = arg(2, 4)?;
+    let runs = arg(3, 3)?.max(1);
+
+    let tree = TempDir::new()?;
+    let mut count = 0;
+    build_tree(tree.path(), depth, files_per_dir, &mut count)?;
+    println!(
+        "{} files in a tree {} directories deep under {}",
+        count,
+        depth,
+        tree.path().display()
+    );
+
+    let (sequential, sequential_time) = best_of(runs, tree.path(), 1)?;
+    let (parallel, parallel_time) = best_of(runs, tree.path(), 0)?;
+
+    println!("sequential walk: {:>10.3?}", sequential_time);
+    println!("parallel walk:   {:>10.3?}", parallel_time);
+    println!(
+        "speedup:         {:>10.2}x",
+        sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+    anyhow::ensure!(
+        sequential == parallel,
+        "The parallel walk found different duplicates"
+    );
+    println!("{} identical duplicate groups", parallel.len());
+    Ok(())
+}
+
+fn build_tree(dir: &Path, depth: usize, files: usize, count: &mut usize) -> anyhow::Result<()> {
+    for _ in 0..files {
+        // 97 distinct contents, so every one of them recurs in a big tree
+        let content = format!("content {}\n", *count % 97).repeat(64);
+        fs::write(dir.join(format!("file{}.txt", count)), content)?;
+        *count += 1;
+    }
+    if depth > 1 {
+        for branch in ["left", "right"] {
+            let child = dir.join(branch);
+            fs::create_dir(&child)?;
+            build_tree(&child, depth - 1, files, count)?;
+        }
+    }
+    Ok(())
+}
+
+/// The duplicate groups as sorted path lists, and the fastest of `runs`
+/// scans on `threads` threads (0: rayon's global pool)
+fn best_of(
+    runs: usize,
+    root: &Path,
+    threads: usize,
+) -> anyhow::Result<(Vec<Vec<PathBuf>>, Duration)> {
+    let mut best = Duration::MAX;
+    let mut groups = Vec::new();
+    for _ in 0..runs {
+        let scanner = Scanner::builder()
+            .threads(threads)
+            .show_progress(false)
+            .build()?;
+        let started = Instant::now();
+        let duplicates = scanner.find_duplicates(root)?;
+        best = best.min(started.elapsed());
+        groups = sorted_groups(duplicates);
+    }
+    Ok((groups, best))
+}
+
+fn sorted_groups(duplicates: HashMap<String, Vec<FileInfo>>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = duplicates
+        .into_values()
+        .map(|files| {
+            let mut paths: Vec<PathBuf> = files.into_iter().map(|file| file.path).collect();
+            paths.sort();
+            paths
+        })
+        .collect();
+    groups.sort();
+    groups
+}
@@ -0,0 +1,122 @@
+//! Heuristic grouping of burst photos.
+//!
+//! Cameras shooting in burst mode produce a run of images that look the same
+//! but differ byte for byte, so content hashing never pairs them. This module
+//! clusters images in the same directory that were taken within a few seconds
+//! of each other and have similar sizes. The result is only a suggestion for
+//! manual pruning; such groups are never deleted automatically.
+
+use crate::file_info::{FileInfo, MatchKind};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Extensions of files considered photos
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "heic", "heif", "webp", "tif", "tiff", "dng", "cr2", "nef", "arw",
+];
+
+/// How close two shots must be to belong to the same burst
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstWindow {
+    /// Largest gap in seconds between consecutive shots
+    pub seconds: u64,
+    /// Largest size difference in bytes between consecutive shots
+    pub size_delta: u64,
+}
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Cluster images per directory into bursts.
+///
+/// Shots are ordered by capture time and a burst keeps growing while each
+/// next shot is within `window` of its latest member. Non-image files are
+/// ignored, and files without a usable timestamp are left out.
+pub fn burst_groups(files: Vec<FileInfo>, window: &BurstWindow) -> HashMap<String, Vec<FileInfo>> {
+    let mut by_dir: HashMap<PathBuf, Vec<(u64, FileInfo)>> = HashMap::new();
+    for mut file in files.into_iter().filter(|file| is_image(&file.path)) {
+        let Some(taken) = capture_time(&file.path) else {
+            continue;
+        };
+        let dir = file
+            .path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        file.match_kind = MatchKind::Burst;
+        by_dir.entry(dir).or_default().push((taken, file));
+    }
+
+    let mut groups = HashMap::new();
+    for (dir, mut shots) in by_dir {
+        shots.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+
+        // A shot joins the most recent burst it fits, so an unrelated photo
+        // taken in between doesn't split a burst in two
+        let mut bursts: Vec<Vec<(u64, FileInfo)>> = Vec::new();
+        for shot in shots {
+            let fitting = bursts.iter_mut().rev().find(|burst| {
+                let (taken, file) = burst.last().unwrap();
+                shot.0 - taken <= window.seconds
+                    && shot.1.size.abs_diff(file.size) <= window.size_delta
+            });
+            match fitting {
+                Some(burst) => burst.push(shot),
+                None => bursts.push(vec![shot]),
+            }
+        }
+
+        for burst in bursts.into_iter().filter(|burst| burst.len() > 1) {
+            let key = format!("burst:{}:{}", dir.display(), burst[0].0);
+            groups.insert(key, burst.into_iter().map(|(_, file)| file).collect());
+        }
+    }
+
+    groups
+}
+
+/// Seconds since the epoch the photo was taken: the EXIF `DateTimeOriginal`
+/// when present, otherwise the file's modification time.
+///
+/// EXIF times carry no time zone, so they are read as UTC. That only shifts
+/// every shot of a camera by the same amount, which doesn't affect the gaps.
+fn capture_time(path: &Path) -> Option<u64> {
+    exif_time(path).or_else(|| {
+        let modified = path.metadata().ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+    })
+}
+
+fn exif_time(path: &Path) -> Option<u64> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let time = exif::DateTime::from_ascii(values.first()?).ok()?;
+
+    let days = days_from_civil(time.year as i64, time.month as i64, time.day as i64);
+    let seconds =
+        days * 86_400 + time.hour as i64 * 3_600 + time.minute as i64 * 60 + time.second as i64;
+    u64::try_from(seconds).ok()
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -1,22 +1,227 @@
-use anyhow::Result;
+use crate::{
+    cancel::CancellationToken,
+    error::{DupCheckError, Result},
+    file_info::FileId,
+    utils::HashAlgorithm,
+};
 use directories::ProjectDirs;
-use log::debug;
-use rusqlite::{params, Connection, OpenFlags};
-use std::{fs, path::Path, sync::Mutex};
+use log::{debug, warn};
+use rusqlite::{params, types::Value, Connection, OpenFlags, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Environment variable naming the cache database, in place of the default
+/// location
+pub const CACHE_PATH_ENV: &str = "DUPCHECK_CACHE";
+
+/// Header of exported cache files, followed by a little-endian format version
+const EXPORT_MAGIC: &[u8; 8] = b"DUPCACHE";
+/// Version 2 added the hash algorithm (version 1 files hold SHA-256 hashes),
+/// version 3 the modification time
+const EXPORT_VERSION: u32 = 3;
+
+/// SQL for the current time in seconds since the Unix epoch, the unit of
+/// the `created_at` column
+const NOW_SECONDS: &str = "CAST(strftime('%s', 'now') AS INTEGER)";
+
+/// Rows removed by maintenance commands are committed in batches of this size,
+/// so an interrupted run keeps the work done so far.
+const MAINTENANCE_BATCH_SIZE: usize = 500;
+
+/// Most entries the background writer commits in one transaction
+const WRITER_BATCH_SIZE: usize = 256;
+
+/// Pragmas that may be tuned by the user. Anything else is rejected so a
+/// configuration typo can't switch off integrity features of the database.
+const ALLOWED_PRAGMAS: &[&str] = &["cache_size", "mmap_size", "synchronous", "temp_store"];
+
+/// A `PRAGMA name = value` statement applied when the cache connection opens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachePragma {
+    pub name: String,
+    pub value: String,
+}
+
+impl CachePragma {
+    pub fn new(name: &str, value: &str) -> anyhow::Result<Self> {
+        let name = name.trim().to_lowercase();
+        let value = value.trim();
+
+        if !ALLOWED_PRAGMAS.contains(&name.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Unsupported cache pragma '{}' (allowed: {})",
+                name,
+                ALLOWED_PRAGMAS.join(", ")
+            ));
+        }
+
+        // Values are interpolated into the statement, so only plain numbers
+        // and keywords such as OFF/NORMAL/MEMORY are accepted.
+        let digits = value.strip_prefix('-').unwrap_or(value);
+        let is_number = !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+        let is_keyword = !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic());
+        if !is_number && !is_keyword {
+            return Err(anyhow::anyhow!(
+                "Invalid value '{}' for cache pragma '{}'",
+                value,
+                name
+            ));
+        }
+
+        Ok(CachePragma {
+            name,
+            value: value.to_string(),
+        })
+    }
+
+    fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(&format!("PRAGMA {} = {};", self.name, self.value))
+    }
+}
+
+impl FromStr for CachePragma {
+    type Err = anyhow::Error;
+
+    /// Parse a `name=value` pair, e.g. `synchronous=OFF`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected NAME=VALUE, got '{}'", s))?;
+        CachePragma::new(name, value)
+    }
+}
+
+/// A single cached hash as stored in the database and in export files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch, if known
+    pub modified: Option<i64>,
+    pub algorithm: HashAlgorithm,
+    pub hash: String,
+}
+
+/// Entry layout of version 1 export files
+#[derive(Deserialize)]
+struct CacheEntryV1 {
+    path: String,
+    size: u64,
+    hash: String,
+}
+
+/// Entry layout of version 2 export files
+#[derive(Deserialize)]
+struct CacheEntryV2 {
+    path: String,
+    size: u64,
+    algorithm: HashAlgorithm,
+    hash: String,
+}
+
+/// Convert a modification time to the form stored in the database
+fn modified_nanos(modified: Option<SystemTime>) -> Option<i64> {
+    let nanos = modified?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    i64::try_from(nanos).ok()
+}
+
+/// A [`SnapshotEntry`] from the `dir_entries` columns starting at `first`,
+/// from `name` to `ino`
+fn snapshot_entry(row: &Row, first: usize) -> rusqlite::Result<SnapshotEntry> {
+    let dev: Option<i64> = row.get(first + 5)?;
+    let ino: Option<i64> = row.get(first + 6)?;
+    Ok(SnapshotEntry {
+        name: row.get(first)?,
+        is_dir: row.get(first + 1)?,
+        size: row.get(first + 2)?,
+        modified: row
+            .get::<_, Option<i64>>(first + 3)?
+            .and_then(modified_time),
+        allocated: row.get(first + 4)?,
+        file_id: dev.zip(ino).map(|(dev, ino)| FileId {
+            dev: dev as u64,
+            ino: ino as u64,
+        }),
+    })
+}
+
+fn modified_time(nanos: i64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_nanos(u64::try_from(nanos).ok()?))
+}
+
+/// Outcome of [`Cache::import_from`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Entries for paths the local cache didn't know about
+    pub inserted: usize,
+    /// Entries identical to what the local cache already had
+    pub unchanged: usize,
+    /// Entries that disagreed with the local cache; the local entry is kept
+    pub conflicts: usize,
+}
+
+/// Outcome of [`Cache::verify`] and [`Cache::clean_old_entries`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifySummary {
+    /// Entries looked at before finishing or being cancelled
+    pub checked: u64,
+    /// Stale entries that were deleted
+    pub removed: u64,
+    /// Whether the run stopped early
+    pub cancelled: bool,
+}
+
+/// What a directory held when it was last listed, see [`Cache::dir_snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirSnapshot {
+    /// Modification time of the directory itself at the time
+    pub modified: SystemTime,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// A file or subdirectory recorded in a [`DirSnapshot`]. Everything but the
+/// name is only meaningful for files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Bytes occupied on disk, see [`crate::file_info::FileInfo::allocated`]
+    pub allocated: u64,
+    pub file_id: Option<FileId>,
+}
 
 pub struct Cache {
     conn: Mutex<Connection>,
+    /// Where the database is stored, unless it's in memory
+    path: Option<PathBuf>,
 }
 
 impl Cache {
     pub fn new() -> Result<Self> {
-        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
-            .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+        Self::with_pragmas(&[])
+    }
 
-        let cache_dir = project_dirs.cache_dir();
-        fs::create_dir_all(cache_dir)?;
+    /// Open the default cache database and apply the given pragmas
+    pub fn with_pragmas(pragmas: &[CachePragma]) -> Result<Self> {
+        Self::open(&Self::default_path()?, pragmas)
+    }
 
-        let db_path = cache_dir.join("cache.db");
+    /// Open (or create) a cache database at an explicit location
+    pub fn open(db_path: &Path, pragmas: &[CachePragma]) -> Result<Self> {
         debug!("Using cache database at: {}", db_path.display());
 
         let conn = Connection::open_with_flags(
@@ -24,28 +229,175 @@ impl Cache {
             OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
         )?;
 
-        conn.execute(
+        // Write-ahead logging lets lookups proceed while a batch commits, and
+        // only syncs at checkpoints rather than on every commit. Databases
+        // that can't use it, e.g. on some network filesystems, keep their
+        // journal mode. Configured pragmas still take precedence.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        let mut cache = Self::with_connection(conn, pragmas)?;
+        cache.path = Some(db_path.to_path_buf());
+        Ok(cache)
+    }
+
+    /// A cache that lives only as long as this value and never touches the
+    /// disk, for one-shot runs and tests
+    pub fn in_memory() -> Result<Self> {
+        debug!("Using an in-memory cache database");
+        Self::with_connection(Connection::open_in_memory()?, &[])
+    }
+
+    /// Apply `pragmas` and set up the schema on a freshly opened connection
+    fn with_connection(conn: Connection, pragmas: &[CachePragma]) -> Result<Self> {
+        for pragma in pragmas {
+            debug!("Applying cache pragma {}={}", pragma.name, pragma.value);
+            pragma.apply(&conn)?;
+        }
+
+        conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS file_hashes (
                 path TEXT PRIMARY KEY,
                 size INTEGER NOT NULL,
-                hash TEXT NOT NULL
-            )",
-            [],
+                hash TEXT NOT NULL,
+                algorithm TEXT NOT NULL DEFAULT 'sha256',
+                modified INTEGER,
+                created_at INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS dir_snapshots (
+                path TEXT PRIMARY KEY,
+                modified INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS dir_entries (
+                dir TEXT NOT NULL,
+                name TEXT NOT NULL,
+                is_dir INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                modified INTEGER,
+                allocated INTEGER NOT NULL,
+                dev INTEGER,
+                ino INTEGER,
+                PRIMARY KEY (dir, name)
+            );
+            CREATE TABLE IF NOT EXISTS scan_journal (
+                scan TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                members TEXT NOT NULL,
+                groups TEXT NOT NULL,
+                PRIMARY KEY (scan, size, members)
+            );",
         )?;
+        Self::migrate(&conn)?;
 
         Ok(Cache {
             conn: Mutex::new(conn),
+            path: None,
         })
     }
 
-    pub fn get_hash(&self, path: &Path, size: u64) -> Result<Option<String>> {
+    /// Where the database is stored, or `None` for [`Cache::in_memory`]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Bring databases created by older versions up to the current schema
+    fn migrate(conn: &Connection) -> Result<()> {
+        let has_column = |name: &str| {
+            conn.prepare("SELECT 1 FROM pragma_table_info('file_hashes') WHERE name = ?")?
+                .exists([name])
+        };
+
+        if !has_column("algorithm")? {
+            // Hashes cached before the column existed were all SHA-256
+            debug!("Adding algorithm column to cache database");
+            conn.execute(
+                "ALTER TABLE file_hashes ADD COLUMN algorithm TEXT NOT NULL DEFAULT 'sha256'",
+                [],
+            )?;
+        }
+        if !has_column("modified")? {
+            // Without a recorded time old entries never match, so they are re-hashed
+            debug!("Adding modified column to cache database");
+            conn.execute("ALTER TABLE file_hashes ADD COLUMN modified INTEGER", [])?;
+        }
+        if !has_column("created_at")? {
+            // Existing entries count as inserted now, so none are evicted early
+            debug!("Adding created_at column to cache database");
+            conn.execute("ALTER TABLE file_hashes ADD COLUMN created_at INTEGER", [])?;
+            conn.execute(
+                &format!("UPDATE file_hashes SET created_at = {}", NOW_SECONDS),
+                [],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Where [`Cache::new`] keeps its database: the path in
+    /// [`CACHE_PATH_ENV`] if set, otherwise `cache.db` in the platform's
+    /// cache directory
+    pub fn default_path() -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os(CACHE_PATH_ENV).filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
+            .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+
+        let cache_dir = project_dirs.cache_dir();
+        fs::create_dir_all(cache_dir)?;
+
+        Ok(cache_dir.join("cache.db"))
+    }
+
+    /// Read back the current value of a pragma on the cache connection
+    pub fn pragma_value(&self, name: &str) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        let value = conn.pragma_query_value(None, name, |row| row.get::<_, Value>(0))?;
+
+        Ok(match value {
+            Value::Integer(i) => i.to_string(),
+            Value::Real(f) => f.to_string(),
+            Value::Text(s) => s,
+            Value::Null | Value::Blob(_) => String::new(),
+        })
+    }
+
+    /// Cached SHA-256 hash of `path`. An entry only matches while the file
+    /// still has the recorded size and modification time.
+    pub fn get_hash(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+    ) -> Result<Option<String>> {
+        self.get_hash_with(path, size, modified, HashAlgorithm::Sha256)
+    }
+
+    /// Cached hash of `path`, only if it was computed with `algorithm`
+    pub fn get_hash_with(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        algorithm: HashAlgorithm,
+    ) -> Result<Option<String>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT hash FROM file_hashes WHERE path = ? AND size = ?")?;
+        let mut stmt = conn.prepare(
+            "SELECT hash FROM file_hashes
+             WHERE path = ? AND size = ? AND modified IS ? AND algorithm = ?",
+        )?;
 
         let path_str = path.to_string_lossy();
-        let result = stmt.query_row(params![path_str.as_ref(), size], |row| {
-            row.get::<_, String>(0)
-        });
+        let result = stmt.query_row(
+            params![
+                path_str.as_ref(),
+                size,
+                modified_nanos(modified),
+                algorithm.name()
+            ],
+            |row| row.get::<_, String>(0),
+        );
 
         match result {
             Ok(hash) => Ok(Some(hash)),
@@ -54,15 +406,582 @@ impl Cache {
         }
     }
 
-    pub fn store_hash(&self, path: &Path, size: u64, hash: &str) -> Result<()> {
+    /// Store a SHA-256 hash
+    pub fn store_hash(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        hash: &str,
+    ) -> Result<()> {
+        self.store_hash_with(path, size, modified, HashAlgorithm::Sha256, hash)
+    }
+
+    pub fn store_hash_with(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        algorithm: HashAlgorithm,
+        hash: &str,
+    ) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         let path_str = path.to_string_lossy();
 
         conn.execute(
-            "INSERT OR REPLACE INTO file_hashes (path, size, hash) VALUES (?, ?, ?)",
-            params![path_str.as_ref(), size, hash],
+            &format!(
+                "INSERT OR REPLACE INTO file_hashes (path, size, hash, algorithm, modified, created_at)
+                 VALUES (?, ?, ?, ?, ?, {})",
+                NOW_SECONDS
+            ),
+            params![
+                path_str.as_ref(),
+                size,
+                hash,
+                algorithm.name(),
+                modified_nanos(modified)
+            ],
         )?;
 
         Ok(())
     }
+
+    /// Store several hashes in a single transaction. If any of them can't be
+    /// stored, none are.
+    pub fn store_batch(&self, entries: &[CacheEntry]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                &format!(
+                    "INSERT OR REPLACE INTO file_hashes (path, size, hash, algorithm, modified, created_at)
+                     VALUES (?, ?, ?, ?, ?, {})",
+                    NOW_SECONDS
+                ),
+            )?;
+            for entry in entries {
+                insert.execute(params![
+                    entry.path,
+                    entry.size,
+                    entry.hash,
+                    entry.algorithm.name(),
+                    entry.modified
+                ])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Start a background thread that persists hashes sent to the returned
+    /// [`CacheWriter`], so hashing threads never wait on the database.
+    pub fn spawn_writer(self: Arc<Self>) -> CacheWriter {
+        let (sender, receiver) = mpsc::channel::<CacheEntry>();
+        let handle = thread::spawn(move || {
+            let mut written = 0;
+            while let Ok(first) = receiver.recv() {
+                let mut batch = vec![first];
+                batch.extend(receiver.try_iter().take(WRITER_BATCH_SIZE - 1));
+                self.store_batch(&batch)?;
+                written += batch.len();
+            }
+            Ok(written)
+        });
+
+        CacheWriter {
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    /// All cached entries ordered by path
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT path, size, hash, algorithm, modified FROM file_hashes ORDER BY path",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(path, size, hash, algorithm, modified)| {
+                let algorithm = HashAlgorithm::from_name(&algorithm).ok_or_else(|| {
+                    anyhow::anyhow!("Unknown hash algorithm '{}' for {}", algorithm, path)
+                })?;
+                Ok(CacheEntry {
+                    path,
+                    size,
+                    modified,
+                    algorithm,
+                    hash,
+                })
+            })
+            .collect()
+    }
+
+    /// Write every entry to `path` in a compact binary format. Returns the entry count.
+    pub fn export_to(&self, path: &Path) -> Result<usize> {
+        let entries = self.entries()?;
+        let file = File::create(path).map_err(|e| DupCheckError::io(path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(EXPORT_MAGIC)?;
+        writer.write_all(&EXPORT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &entries).map_err(anyhow::Error::from)?;
+        writer.flush()?;
+
+        Ok(entries.len())
+    }
+
+    /// Merge entries from a file written by [`Cache::export_to`].
+    ///
+    /// The whole file is validated before anything is written, and the merge
+    /// runs in a single transaction. When an imported entry disagrees with a
+    /// local one the local entry wins, since it was computed on this machine.
+    pub fn import_from(&self, path: &Path) -> Result<ImportSummary> {
+        let file = File::open(path).map_err(|e| DupCheckError::io(path, e))?;
+        let mut reader = BufReader::new(file);
+        let invalid = |reason: String| {
+            DupCheckError::InvalidCacheFile(format!("{}: {}", path.display(), reason))
+        };
+
+        let mut magic = [0u8; 8];
+        let read = reader.read_exact(&mut magic);
+        if read.is_err() || &magic != EXPORT_MAGIC {
+            return Err(invalid("not a dupcheck cache export".to_string()));
+        }
+
+        let mut version = [0u8; 4];
+        reader
+            .read_exact(&mut version)
+            .map_err(|e| invalid(e.to_string()))?;
+        let version = u32::from_le_bytes(version);
+        let entries: Vec<CacheEntry> = match version {
+            1 => {
+                let legacy: Vec<CacheEntryV1> =
+                    bincode::deserialize_from(reader).map_err(|e| invalid(e.to_string()))?;
+                legacy
+                    .into_iter()
+                    .map(|entry| CacheEntry {
+                        path: entry.path,
+                        size: entry.size,
+                        modified: None,
+                        algorithm: HashAlgorithm::Sha256,
+                        hash: entry.hash,
+                    })
+                    .collect()
+            }
+            2 => {
+                let legacy: Vec<CacheEntryV2> =
+                    bincode::deserialize_from(reader).map_err(|e| invalid(e.to_string()))?;
+                legacy
+                    .into_iter()
+                    .map(|entry| CacheEntry {
+                        path: entry.path,
+                        size: entry.size,
+                        modified: None,
+                        algorithm: entry.algorithm,
+                        hash: entry.hash,
+                    })
+                    .collect()
+            }
+            EXPORT_VERSION => {
+                bincode::deserialize_from(reader).map_err(|e| invalid(e.to_string()))?
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported version {} (expected {})",
+                    version, EXPORT_VERSION
+                )))
+            }
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut summary = ImportSummary::default();
+        {
+            let mut select = tx.prepare(
+                "SELECT size, hash, algorithm, modified FROM file_hashes WHERE path = ?",
+            )?;
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO file_hashes (path, size, hash, algorithm, modified, created_at)
+                     VALUES (?, ?, ?, ?, ?, {})",
+                NOW_SECONDS
+            ))?;
+
+            for entry in &entries {
+                let existing = select
+                    .query_row(params![entry.path], |row| {
+                        Ok((
+                            row.get::<_, u64>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, Option<i64>>(3)?,
+                        ))
+                    })
+                    .optional()?;
+
+                match existing {
+                    None => {
+                        insert.execute(params![
+                            entry.path,
+                            entry.size,
+                            entry.hash,
+                            entry.algorithm.name(),
+                            entry.modified
+                        ])?;
+                        summary.inserted += 1;
+                    }
+                    Some((size, hash, algorithm, modified))
+                        if size == entry.size
+                            && hash == entry.hash
+                            && algorithm == entry.algorithm.name()
+                            && modified == entry.modified =>
+                    {
+                        summary.unchanged += 1;
+                    }
+                    Some(_) => summary.conflicts += 1,
+                }
+            }
+        }
+        tx.commit()?;
+
+        Ok(summary)
+    }
+
+    /// Remove every cached hash, directory snapshot and scan journal,
+    /// returning how many hashes there were
+    pub fn clear_all(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(
+            "DELETE FROM dir_snapshots; DELETE FROM dir_entries; DELETE FROM scan_journal;",
+        )?;
+        Ok(conn.execute("DELETE FROM file_hashes", [])?)
+    }
+
+    /// The snapshot last stored for `dir`, whether or not it changed since
+    pub fn dir_snapshot(&self, dir: &Path) -> Result<Option<DirSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        let dir = dir.to_string_lossy();
+        let modified = conn
+            .query_row(
+                "SELECT modified FROM dir_snapshots WHERE path = ?",
+                params![dir.as_ref()],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        let Some(modified) = modified.and_then(modified_time) else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT name, is_dir, size, modified, allocated, dev, ino
+             FROM dir_entries WHERE dir = ?",
+        )?;
+        let entries = stmt
+            .query_map(params![dir.as_ref()], |row| snapshot_entry(row, 0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(DirSnapshot { modified, entries }))
+    }
+
+    /// Every snapshot stored for `root` and the directories below it, keyed
+    /// by directory. Loaded with one query per table rather than one per
+    /// directory, for walks that look up each of them.
+    pub fn dir_snapshots_under(&self, root: &Path) -> Result<HashMap<PathBuf, DirSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        let root = root.to_string_lossy();
+        let separator = std::path::MAIN_SEPARATOR;
+        let prefix = format!("{}{}", root.trim_end_matches(separator), separator);
+
+        let mut snapshots = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT path, modified FROM dir_snapshots
+             WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+        )?;
+        let mut rows = stmt.query(params![root.as_ref(), prefix])?;
+        while let Some(row) = rows.next()? {
+            if let Some(modified) = modified_time(row.get(1)?) {
+                let dir: String = row.get(0)?;
+                let entries = Vec::new();
+                snapshots.insert(PathBuf::from(dir), DirSnapshot { modified, entries });
+            }
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT dir, name, is_dir, size, modified, allocated, dev, ino FROM dir_entries
+             WHERE dir = ?1 OR substr(dir, 1, length(?2)) = ?2",
+        )?;
+        let mut rows = stmt.query(params![root.as_ref(), prefix])?;
+        while let Some(row) = rows.next()? {
+            let dir: String = row.get(0)?;
+            if let Some(snapshot) = snapshots.get_mut(Path::new(&dir)) {
+                snapshot.entries.push(snapshot_entry(row, 1)?);
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Replace the snapshots of these directories, in a single transaction
+    pub fn store_dir_snapshots(&self, snapshots: &[(PathBuf, DirSnapshot)]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut clear = tx.prepare("DELETE FROM dir_entries WHERE dir = ?")?;
+            let mut insert_dir =
+                tx.prepare("INSERT OR REPLACE INTO dir_snapshots (path, modified) VALUES (?, ?)")?;
+            let mut insert_entry = tx.prepare(
+                "INSERT INTO dir_entries (dir, name, is_dir, size, modified, allocated, dev, ino)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for (dir, snapshot) in snapshots {
+                let Some(modified) = modified_nanos(Some(snapshot.modified)) else {
+                    continue;
+                };
+                let dir = dir.to_string_lossy();
+                clear.execute(params![dir.as_ref()])?;
+                insert_dir.execute(params![dir.as_ref(), modified])?;
+                for entry in &snapshot.entries {
+                    insert_entry.execute(params![
+                        dir.as_ref(),
+                        entry.name,
+                        entry.is_dir,
+                        entry.size,
+                        modified_nanos(entry.modified),
+                        entry.allocated,
+                        entry.file_id.map(|id| id.dev as i64),
+                        entry.file_id.map(|id| id.ino as i64),
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The size groups journaled under `scan`, as (size, members, groups)
+    /// rows, see [`crate::journal`]
+    pub fn scan_journal(&self, scan: &str) -> Result<Vec<(u64, String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT size, members, groups FROM scan_journal WHERE scan = ?")?;
+        let rows = stmt
+            .query_map(params![scan], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Journal a finished size group under `scan`
+    pub fn record_scan_journal(
+        &self,
+        scan: &str,
+        size: u64,
+        members: &str,
+        groups: &str,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO scan_journal (scan, size, members, groups)
+             VALUES (?, ?, ?, ?)",
+            params![scan, size, members, groups],
+        )?;
+        Ok(())
+    }
+
+    /// Forget everything journaled under `scan`
+    pub fn clear_scan_journal(&self, scan: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM scan_journal WHERE scan = ?", params![scan])?;
+        Ok(())
+    }
+
+    /// Forget the snapshot of `dir`, so the next incremental scan lists it
+    pub fn remove_dir_snapshot(&self, dir: &Path) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let dir = dir.to_string_lossy();
+        conn.execute(
+            "DELETE FROM dir_snapshots WHERE path = ?",
+            params![dir.as_ref()],
+        )?;
+        conn.execute(
+            "DELETE FROM dir_entries WHERE dir = ?",
+            params![dir.as_ref()],
+        )?;
+        Ok(())
+    }
+
+    /// Remove entries inserted more than `max_age` ago, whether or not their
+    /// file changed since, returning how many were removed. Re-hashing a file
+    /// renews its entry.
+    pub fn evict_older_than(&self, max_age: Duration) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let cutoff = SystemTime::now()
+            .checked_sub(max_age)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_secs() as i64);
+        Ok(conn.execute(
+            "DELETE FROM file_hashes WHERE created_at < ?",
+            params![cutoff],
+        )?)
+    }
+
+    /// Remove entries whose file no longer exists. Unlike [`Cache::verify`],
+    /// files that changed are kept.
+    ///
+    /// Progress and cancellation work as for [`Cache::verify`].
+    pub fn clean_old_entries(
+        &self,
+        cancel: &CancellationToken,
+        on_progress: impl FnMut(u64, u64),
+    ) -> Result<VerifySummary> {
+        self.remove_entries_where(cancel, on_progress, |path, _, _| !Path::new(path).exists())
+    }
+
+    /// Remove entries whose file is gone or no longer has the cached size or
+    /// modification time.
+    ///
+    /// `on_progress(checked, total)` is called after every entry. Cancelling
+    /// `cancel` stops the run after the current entry; everything removed up
+    /// to that point is committed, so the database stays consistent.
+    pub fn verify(
+        &self,
+        cancel: &CancellationToken,
+        on_progress: impl FnMut(u64, u64),
+    ) -> Result<VerifySummary> {
+        self.remove_entries_where(
+            cancel,
+            on_progress,
+            |path, size, modified| match fs::metadata(path) {
+                Ok(metadata) => {
+                    !metadata.is_file()
+                        || metadata.len() != size
+                        || modified.is_some_and(|recorded| {
+                            metadata.modified().ok() != modified_time(recorded)
+                        })
+                }
+                Err(_) => true,
+            },
+        )
+    }
+
+    /// Delete the entries for which `stale(path, size, modified)` holds,
+    /// committing every [`MAINTENANCE_BATCH_SIZE`] entries
+    fn remove_entries_where(
+        &self,
+        cancel: &CancellationToken,
+        mut on_progress: impl FnMut(u64, u64),
+        stale: impl Fn(&str, u64, Option<i64>) -> bool,
+    ) -> Result<VerifySummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let rows: Vec<(String, u64, Option<i64>)> = {
+            let mut stmt =
+                conn.prepare("SELECT path, size, modified FROM file_hashes ORDER BY path")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        let total = rows.len() as u64;
+        let mut summary = VerifySummary::default();
+
+        for batch in rows.chunks(MAINTENANCE_BATCH_SIZE) {
+            let tx = conn.transaction()?;
+            {
+                let mut delete = tx.prepare("DELETE FROM file_hashes WHERE path = ?")?;
+                for (path, size, modified) in batch {
+                    if cancel.is_cancelled() {
+                        summary.cancelled = true;
+                        break;
+                    }
+
+                    if stale(path, *size, *modified) {
+                        debug!("Removing stale cache entry for {}", path);
+                        delete.execute(params![path])?;
+                        summary.removed += 1;
+                    }
+
+                    summary.checked += 1;
+                    on_progress(summary.checked, total);
+                }
+            }
+            tx.commit()?;
+
+            if summary.cancelled {
+                break;
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Handle to the background thread started by [`Cache::spawn_writer`].
+///
+/// Sending never blocks. Call [`CacheWriter::finish`] to wait until every
+/// queued entry is committed; dropping the handle does the same but discards
+/// any error.
+pub struct CacheWriter {
+    sender: Option<Sender<CacheEntry>>,
+    handle: Option<JoinHandle<Result<usize>>>,
+}
+
+impl CacheWriter {
+    /// Queue a hash to be stored
+    pub fn send(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        algorithm: HashAlgorithm,
+        hash: &str,
+    ) {
+        let entry = CacheEntry {
+            path: path.to_string_lossy().into_owned(),
+            size,
+            modified: modified_nanos(modified),
+            algorithm,
+            hash: hash.to_string(),
+        };
+        let sent = self.sender.as_ref().map(|sender| sender.send(entry));
+        if let Some(Err(_)) = sent {
+            // The writer stopped after a database error; `finish` reports it
+            debug!("Cache writer is gone, not caching {}", path.display());
+        }
+    }
+
+    /// Drain the queue, join the writer and return how many entries it stored
+    pub fn finish(mut self) -> Result<usize> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<usize> {
+        drop(self.sender.take());
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("Cache writer thread panicked"))?,
+            None => Ok(0),
+        }
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.join() {
+            warn!("Failed to flush hash cache: {}", e);
+        }
+    }
 }
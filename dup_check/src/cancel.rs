@@ -0,0 +1,90 @@
+//! Cooperative cancellation shared by long-running operations
+
+use anyhow::Result;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, OnceLock,
+};
+
+/// A cheap, cloneable flag that long-running loops poll to stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    /// Cancelling the parent cancels this token too, but not the other way round
+    parent: Option<Arc<CancellationToken>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is cancelled along with this one, and can also be
+    /// cancelled on its own without affecting this one
+    pub fn child(&self) -> Self {
+        CancellationToken {
+            cancelled: Arc::default(),
+            parent: Some(Arc::new(self.clone())),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_cancelled())
+    }
+}
+
+/// Whether the Ctrl-C handler was installed
+static HANDLER: OnceLock<()> = OnceLock::new();
+
+/// The token Ctrl-C cancels, while a [`CtrlC`] holds one
+static CURRENT: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Routes Ctrl-C to a fresh [`CancellationToken`] for as long as it lives,
+/// so one operation can stop cleanly. Outside of one, and when pressed a
+/// second time, Ctrl-C exits the process.
+pub struct CtrlC {
+    token: CancellationToken,
+}
+
+impl CtrlC {
+    /// Make a new token the one Ctrl-C cancels. The signal handler is
+    /// installed on first use.
+    pub fn catch() -> Result<Self> {
+        if HANDLER.get().is_none() {
+            ctrlc::set_handler(|| match &*CURRENT.lock().unwrap() {
+                Some(token) if !token.is_cancelled() => token.cancel(),
+                _ => std::process::exit(130),
+            })?;
+            HANDLER.get_or_init(|| ());
+        }
+
+        let token = CancellationToken::new();
+        *CURRENT.lock().unwrap() = Some(token.clone());
+        Ok(CtrlC { token })
+    }
+
+    /// The token Ctrl-C cancels, to hand to the operation
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for CtrlC {
+    fn drop(&mut self) {
+        let mut current = CURRENT.lock().unwrap();
+        if current
+            .as_ref()
+            .is_some_and(|token| Arc::ptr_eq(&token.cancelled, &self.token.cancelled))
+        {
+            *current = None;
+        }
+    }
+}
@@ -1,5 +1,15 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::{
+    cache::CachePragma,
+    config::Config,
+    duplicates::KeepStrategy,
+    perceptual,
+    report::SortKey,
+    scanner::{ByteRange, EmptyFiles, GroupBy, ScanRoot, SymlinkPolicy},
+    utils::{self, HashAlgorithm},
+};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use globset::Glob;
+use std::{path::PathBuf, time::Duration};
 
 /// DupCheck - A safe and efficient duplicate file finder
 #[derive(Parser, Debug)]
@@ -11,29 +21,548 @@ use std::path::PathBuf;
     It uses SHA-256 hashing and provides features like size filtering, caching, and safe deletion."
 )]
 pub struct Args {
-    /// Directory path to scan for duplicates (defaults to current directory if not specified)
-    #[arg(short = 'p', long, default_value = ".")]
-    pub path: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    /// Minimum file size to consider (e.g., '1K' for 1 kilobyte, '1M' for 1 megabyte)
+    /// Read option defaults from this TOML file instead of the one in the config directory.
+    /// Options given on the command line take precedence over the file. A switch the
+    /// file turns on is turned off again with --no-<switch>, or --recursive, --cache
+    /// and --color for --no-recursive, --no-cache and --no-color
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Whether a config file was loaded, so a bare run uses it instead of asking
+    #[arg(skip)]
+    pub config_loaded: bool,
+
+    /// Directory to scan for duplicates (repeatable; defaults to the current directory).
+    /// Files are compared across all paths, and a file reachable from several is counted once.
+    /// Append ':min=SIZE,max=SIZE' to use other size filters below this path, e.g. '~/Downloads:min=1M'
+    #[arg(short = 'p', long = "path", value_name = "PATH", default_value = ".", value_parser = parse_root)]
+    pub paths: Vec<ScanRoot>,
+
+    /// Minimum file size to consider (e.g., '1K' for 1 kilobyte, '1M' for 1 megabyte).
+    /// Files of exactly this size are included
     #[arg(short = 'n', long, value_parser = parse_size)]
     pub min_size: Option<u64>,
 
-    /// Maximum file size to consider (e.g., '1G' for 1 gigabyte)
+    /// Maximum file size to consider (e.g., '1G' for 1 gigabyte).
+    /// Files of exactly this size are included
     #[arg(short = 'x', long, value_parser = parse_size)]
     pub max_size: Option<u64>,
 
+    /// Only consider files of exactly this size, like '--min-size N --max-size N'
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["min_size", "max_size"])]
+    pub size_exact: Option<u64>,
+
+    /// Skip files and directories matching this glob (repeatable), e.g. '*.tmp' or 'node_modules'.
+    /// Patterns match the file name or the full path; excluded directories are not descended into
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub exclude: Vec<Glob>,
+
+    /// Only scan files with these extensions, e.g. 'jpg,png,heic' (case-insensitive).
+    /// Files without an extension are skipped
+    #[arg(long = "ext", value_name = "EXT,...", value_delimiter = ',', value_parser = parse_extension)]
+    pub extensions: Option<Vec<String>>,
+
+    /// Skip files with these extensions, e.g. 'tmp,log' (case-insensitive)
+    #[arg(long, value_name = "EXT,...", value_delimiter = ',', value_parser = parse_extension)]
+    pub exclude_ext: Vec<String>,
+
+    /// Also scan hidden files, such as dotfiles or files with the Windows hidden attribute
+    #[arg(long)]
+    pub include_hidden: bool,
+    /// Turn --include-hidden off again when the config file turns it on
+    #[arg(long, overrides_with = "include_hidden", hide = true)]
+    pub no_include_hidden: bool,
+
+    /// Descend at most N directory levels; 1 only scans files directly in each path
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Only scan the files directly in each path, without descending into
+    /// subdirectories. Same as --max-depth 1
+    #[arg(long, conflicts_with = "max_depth")]
+    pub no_recursive: bool,
+    /// Turn --no-recursive off again when the config file turns it on
+    #[arg(long, overrides_with = "no_recursive", hide = true)]
+    pub recursive: bool,
+
+    /// Only scan files modified within this age, e.g. '7d' (units: s, m, h, d, w, y)
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub newer_than: Option<Duration>,
+
+    /// Only scan files last modified longer ago than this age, e.g. '1y'
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub older_than: Option<Duration>,
+
+    /// What to do with symbolic links: skip them, follow them, or treat links to
+    /// files as files of their own
+    #[arg(long, value_enum, default_value_t = SymlinkPolicy::Skip)]
+    pub symlinks: SymlinkPolicy,
+
+    /// Windows: walk into directory junctions and other reparse points, which are
+    /// skipped by default. Symbolic links are followed too
+    #[arg(long)]
+    pub follow_junctions: bool,
+    /// Turn --follow-junctions off again when the config file turns it on
+    #[arg(long, overrides_with = "follow_junctions", hide = true)]
+    pub no_follow_junctions: bool,
+
+    /// What to do with empty files: group them like other duplicates, ignore them,
+    /// or report them separately (deleted only with --force)
+    #[arg(long = "empty", value_name = "MODE", value_enum, default_value_t = EmptyFiles::Group)]
+    pub empty_files: EmptyFiles,
+
     /// Disable caching of file hashes (caching is enabled by default)
     #[arg(short = 'c', long)]
     pub no_cache: bool,
+    /// Turn --no-cache off again when the config file turns it on
+    #[arg(long, overrides_with = "no_cache", hide = true)]
+    pub cache: bool,
+
+    /// SQLite pragma applied to the cache connection, e.g. 'synchronous=OFF' (repeatable).
+    /// Allowed: cache_size, mmap_size, synchronous, temp_store
+    #[arg(long = "cache-pragma", value_name = "NAME=VALUE", value_parser = parse_pragma)]
+    pub cache_pragmas: Vec<CachePragma>,
+
+    /// Cache database to use instead of the default one. Also settable through the
+    /// DUPCHECK_CACHE environment variable; this option takes precedence
+    #[arg(long, value_name = "PATH")]
+    pub cache_path: Option<PathBuf>,
+
+    /// Drop cached hashes older than this before scanning, e.g. '30d' (units: s, m, h, d, w, y)
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub cache_max_age: Option<Duration>,
+
+    /// Remember directory listings in the cache and only list directories again
+    /// once they changed. Files are still checked for changes one by one, so this
+    /// mostly helps where listing directories is slow, e.g. on network shares
+    #[arg(long, conflicts_with = "no_cache")]
+    pub incremental: bool,
+    /// Turn --incremental off again when the config file turns it on
+    #[arg(long, overrides_with = "incremental", hide = true)]
+    pub no_incremental: bool,
+
+    /// File that must be kept as the original of its group (repeatable).
+    /// If a group contains several, the first one listed wins
+    #[arg(long = "keep-path", value_name = "PATH")]
+    pub keep_paths: Vec<PathBuf>,
+
+    /// Which file of each group to keep as the original. --keep-path and the
+    /// --original-if-* markers take precedence
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    pub keep: Option<KeepStrategy>,
+
+    /// Keep a file under this directory as the original when a group has one,
+    /// e.g. your master copy. Falls back to --keep for groups with none
+    #[arg(long, value_name = "DIR")]
+    pub path_prefer: Option<PathBuf>,
+
+    /// Treat read-only files as originals; they are never deleted
+    #[arg(long)]
+    pub original_if_readonly: bool,
+
+    /// Treat files with this extended attribute as originals; they are never deleted
+    #[arg(long, value_name = "NAME")]
+    pub original_if_xattr: Option<String>,
+
+    /// Hash algorithm used to compare files; cached hashes are kept per algorithm
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::default())]
+    pub hash_algo: HashAlgorithm,
+
+    /// Experimental: also group small text files that differ only in letter case.
+    /// These groups are flagged and never deleted automatically
+    #[arg(long)]
+    pub text_case_insensitive: bool,
+
+    /// Scan once without prompting and exit with status 1 if any duplicate group is found (for CI)
+    #[arg(long)]
+    pub fail_on_duplicates: bool,
+
+    /// Batch mode for scheduled runs: scan once and report without asking, then exit
+    /// with status 0 if there were no duplicates, 1 if there were and 2 on errors.
+    /// Nothing is deleted unless --delete is given as well
+    #[arg(long, visible_alias = "non-interactive", conflicts_with_all = ["review", "pick"])]
+    pub yes: bool,
+
+    /// With --yes, clean up the duplicates after reporting them: delete them, or
+    /// trash or link them with --trash, --hardlink or --symlink
+    #[arg(long, requires = "yes", conflicts_with = "print0")]
+    pub delete: bool,
+
+    /// What makes files duplicates: identical content, just the same file name in
+    /// different folders, or the same size and name ('quick'). Name matches are listed
+    /// but never deleted
+    #[arg(
+        long = "by",
+        value_name = "MODE",
+        value_enum,
+        default_value_t = GroupBy::Content,
+        conflicts_with_all = ["compare_range", "burst", "images", "dedupe_names"]
+    )]
+    pub group_by: GroupBy,
+
+    /// Group files by size and file name without reading them, same as '--by quick'.
+    /// Fast, but the matches are unverified and never deleted
+    #[arg(long, conflicts_with_all = ["group_by", "compare_range", "burst", "images", "dedupe_names"])]
+    pub quick: bool,
+    /// Turn --quick off again when the config file turns it on
+    #[arg(long, overrides_with = "quick", hide = true)]
+    pub no_quick: bool,
+
+    /// With '--by name' or '--quick', match file names regardless of case, e.g.
+    /// 'Foo.txt' and 'foo.txt', as on case-insensitive filesystems
+    #[arg(long)]
+    pub ignore_case_names: bool,
+    /// Turn --ignore-case-names off again when the config file turns it on
+    #[arg(long, overrides_with = "ignore_case_names", hide = true)]
+    pub no_ignore_case_names: bool,
+
+    /// Group files by hashing only this byte range, e.g. '0:4K' for the first 4 KiB.
+    /// Matches are partial, so deleting them requires --force
+    #[arg(long, value_name = "START:LEN", value_parser = parse_range)]
+    pub compare_range: Option<ByteRange>,
+
+    /// Group photos in the same directory taken within SECONDS of each other
+    /// (EXIF time, else mtime). Burst groups are never deleted
+    #[arg(long, value_name = "SECONDS")]
+    pub burst: Option<u64>,
+
+    /// Largest size difference between shots of one burst (e.g. 512K)
+    #[arg(long, value_name = "SIZE", default_value = "1M", value_parser = parse_size, requires = "burst")]
+    pub burst_size_delta: u64,
+
+    /// Find images that look alike, such as resized or re-encoded copies, instead of
+    /// identical files. Such groups are only deleted with --force
+    #[arg(long, conflicts_with = "burst")]
+    pub images: bool,
+
+    /// With --images, how many of the 64 perceptual hash bits may differ between similar images
+    #[arg(long, value_name = "BITS", default_value_t = perceptual::DEFAULT_THRESHOLD, requires = "images")]
+    pub threshold: u32,
+
+    /// Only find copies named like 'photo (1).jpg' or 'photo copy.jpg' next to
+    /// an identical original; the un-suffixed file is kept
+    #[arg(long)]
+    pub dedupe_names: bool,
+
+    /// Allow deleting from groups that are not full-content matches (e.g. --compare-range)
+    #[arg(long)]
+    pub force: bool,
+
+    /// Only ever delete files with these extensions, e.g. 'jpg,png'; other duplicates are kept
+    #[arg(long, value_name = "EXT,...", value_delimiter = ',', value_parser = parse_extension)]
+    pub deletable_ext: Option<Vec<String>>,
+
+    /// Skip files that another process holds locked, when hashing and deleting
+    #[arg(long)]
+    pub skip_locked: bool,
+
+    /// Show which files would be deleted and the space freed, without deleting anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Number of threads used for walking directories and hashing; 0 uses all cores.
+    /// Use 1 on spinning disks, where parallel reads slow scanning down
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub threads: usize,
+
+    /// Compare hash-matched files byte by byte before reporting them as duplicates
+    #[arg(long)]
+    pub verify: bool,
+    /// Turn --verify off again when the config file turns it on
+    #[arg(long, overrides_with = "verify", hide = true)]
+    pub no_verify: bool,
+
+    /// Only report files as duplicates if their extended attributes match too.
+    /// Unix (Linux, macOS) only; Windows alternate data streams are not compared
+    #[arg(long)]
+    pub compare_xattrs: bool,
+    /// Turn --compare-xattrs off again when the config file turns it on
+    #[arg(long, overrides_with = "compare_xattrs", hide = true)]
+    pub no_compare_xattrs: bool,
+
+    /// Compare files at least this large directly through memory maps instead of hashing them,
+    /// e.g. '256M'. Faster for very large media, but their hashes aren't cached
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub mmap_threshold: Option<u64>,
+
+    /// Move duplicates to the system trash instead of deleting them permanently.
+    /// Files that can't be trashed are left in place
+    #[arg(long)]
+    pub trash: bool,
+    /// Turn --trash off again when the config file turns it on
+    #[arg(long, overrides_with = "trash", hide = true)]
+    pub no_trash: bool,
+
+    /// Replace duplicates with hard links to the original instead of deleting them,
+    /// so every path keeps working. Files on another filesystem are left in place
+    #[arg(long, conflicts_with = "trash")]
+    pub hardlink: bool,
+
+    /// Replace duplicates with symbolic links to the original instead of deleting them.
+    /// The links break if the original is moved or deleted
+    #[arg(long, conflicts_with_all = ["trash", "hardlink"])]
+    pub symlink: bool,
+
+    /// With --symlink, give each link the timestamps of the duplicate it replaces
+    #[arg(long, requires = "symlink")]
+    pub preserve_link_times: bool,
+
+    /// Restore the files of the most recent deletion from the trash, then exit.
+    /// Permanent deletions can't be undone
+    #[arg(long)]
+    pub undo: bool,
+
+    /// After deleting, confirm every deleted file is really gone
+    #[arg(long)]
+    pub verify_deletion: bool,
+
+    /// Only report files that exist in at least N copies
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub min_copies: usize,
+
+    /// Keep at most N groups, those wasting the most space, to bound memory and output
+    #[arg(long, value_name = "N")]
+    pub max_result_groups: Option<usize>,
+
+    /// Print sizes in text output as exact byte counts instead of e.g. '4.20 MB'
+    #[arg(long)]
+    pub bytes: bool,
+    /// Turn --bytes off again when the config file turns it on
+    #[arg(long, overrides_with = "bytes", hide = true)]
+    pub no_bytes: bool,
+
+    /// Only print the number of groups, duplicate files and wasted space, without
+    /// listing the groups
+    #[arg(long)]
+    pub summary_only: bool,
+    /// Turn --summary-only off again when the config file turns it on
+    #[arg(long, overrides_with = "summary_only", hide = true)]
+    pub no_summary_only: bool,
+
+    /// After the scan, count the files left out by the filters per reason, e.g.
+    /// hidden or too small. Run with RUST_LOG=debug to see every file
+    #[arg(long)]
+    pub log_skipped: bool,
+    /// Turn --log-skipped off again when the config file turns it on
+    #[arg(long, overrides_with = "log_skipped", hide = true)]
+    pub no_log_skipped: bool,
+
+    /// Plain ASCII output without colors or emoji. Also the default when NO_COLOR is
+    /// set or stdout isn't a terminal
+    #[arg(long)]
+    pub no_color: bool,
+    /// Turn --no-color off again when the config file turns it on
+    #[arg(long, overrides_with = "no_color", hide = true)]
+    pub color: bool,
+
+    /// Output format of the results. With 'json' or 'csv' only the results go to stdout,
+    /// and the run ends without prompting
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Only print the paths that would be deleted, each ended by a NUL byte, for
+    /// 'xargs -0'. The kept file of each group is left out; the run ends without prompting
+    #[arg(long, conflicts_with = "format")]
+    pub print0: bool,
+
+    /// Order of the groups in the report: most wasted space, largest files, most
+    /// copies, or alphabetically by the kept file's path
+    #[arg(long, value_name = "KEY", value_enum, default_value_t = SortKey::Wasted)]
+    pub sort: SortKey,
+
+    /// Write the report to FILE instead of stdout, in the chosen --format.
+    /// Progress and prompts still go to the terminal
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Keep a journal of the size groups this scan finishes, and continue an
+    /// interrupted scan of the same paths that kept one: the groups it finished
+    /// are taken from the journal instead of being hashed again
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Keep the scan journal in FILE, so a later '--resume --journal FILE' can
+    /// continue the scan. With --resume alone, the journal is kept in the hash
+    /// cache, or with --no-cache in a file per set of paths in the cache directory
+    #[arg(long, value_name = "FILE")]
+    pub journal: Option<PathBuf>,
+
+    /// Print each duplicate group as soon as it is confirmed, while the scan continues
+    #[arg(long)]
+    pub live: bool,
+
+    /// After the report, list the N most deeply nested duplicate files
+    #[arg(long, value_name = "N")]
+    pub deepest: Option<usize>,
+
+    /// Compare the space deleting, hard-linking or symlinking duplicates would reclaim
+    #[arg(long)]
+    pub savings: bool,
+
+    /// Break wasted space down by top-level folder under the (first) scanned path
+    #[arg(long)]
+    pub by_folder: bool,
+
+    /// Write the results to a SQLite database for querying with SQL
+    #[arg(long, value_name = "FILE")]
+    pub export_db: Option<PathBuf>,
+
+    /// Review groups one by one; decisions are saved so a later run resumes where you left off
+    #[arg(long)]
+    pub review: bool,
+
+    /// Pick the files to delete in each group from a checklist instead of
+    /// deleting every duplicate
+    #[arg(long, conflicts_with = "review")]
+    pub pick: bool,
+
+    /// File used to persist review decisions (defaults to the application data directory)
+    #[arg(long, value_name = "FILE", requires = "review")]
+    pub decisions: Option<PathBuf>,
+
+    /// File listing groups to hide from reports (defaults to the application data directory).
+    /// Groups ignored during --review are added by their paths, so a new copy shows them
+    /// again. Entries by hash hide that content everywhere, including future copies
+    #[arg(long, value_name = "FILE")]
+    pub ignore_list: Option<PathBuf>,
+}
+
+/// How scan results are printed
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable report
+    Text,
+    /// Machine-readable array of groups, see [`crate::ui::JsonGroup`]
+    Json,
+    /// One row per file for spreadsheets, see [`crate::ui::CsvRow`]
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Maintain the hash cache
+    Cache(CacheArgs),
+    /// Print the content hash of a file, or of stdin when FILE is '-'
+    Hash(HashArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HashArgs {
+    /// File to hash; '-' reads from stdin
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Hash algorithm to use
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::default())]
+    pub hash_algo: HashAlgorithm,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+pub struct CacheArgs {
+    /// Write all cached hashes to FILE in a compact binary format
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// Merge cached hashes from a file created with --export
+    #[arg(long, value_name = "FILE")]
+    pub import: Option<PathBuf>,
+
+    /// Remove entries for files that no longer exist or have changed size
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Remove entries for files that no longer exist, keeping changed ones
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Remove every cached hash
+    #[arg(long)]
+    pub clear: bool,
+}
+
+/// Parse the command line, filling options it doesn't set from the config
+/// file, see [`crate::config`]
+pub fn parse_args() -> anyhow::Result<Args> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(config) = Config::find(args.config.as_deref())? {
+        config.merge(&mut args, &matches)?;
+        args.config_loaded = true;
+    }
+    Ok(args)
+}
+
+pub(crate) fn parse_pragma(pragma: &str) -> Result<CachePragma, String> {
+    pragma.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+/// Normalize an extension to lowercase without a leading dot
+pub(crate) fn parse_extension(ext: &str) -> Result<String, String> {
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    if ext.is_empty() {
+        return Err("Extension must not be empty".to_string());
+    }
+    Ok(ext)
+}
+
+pub(crate) fn parse_glob(pattern: &str) -> Result<Glob, String> {
+    Glob::new(pattern).map_err(|e| e.to_string())
+}
+
+/// Parse a scan root with optional inline filters, e.g. `Downloads:min=1M,max=1G`.
+/// Only a trailing `:key=value` list is taken as filters, so paths that
+/// contain colons (such as `C:\Photos`) still parse as plain paths.
+pub(crate) fn parse_root(root: &str) -> Result<ScanRoot, String> {
+    let Some((path, filters)) = root.rsplit_once(':').filter(|(_, f)| f.contains('=')) else {
+        return Ok(ScanRoot::new(root));
+    };
+
+    let mut scan_root = ScanRoot::new(path);
+    for filter in filters.split(',') {
+        let (key, value) = filter
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE, got '{}'", filter))?;
+        match key.trim() {
+            "min" => scan_root.min_size = Some(parse_size(value)?),
+            "max" => scan_root.max_size = Some(parse_size(value)?),
+            other => {
+                return Err(format!(
+                    "Unknown path filter '{}' (expected min or max)",
+                    other
+                ))
+            }
+        }
+    }
+
+    Ok(scan_root)
+}
+
+/// Parse a `START:LEN` byte range; both parts accept size suffixes
+fn parse_range(range: &str) -> Result<ByteRange, String> {
+    let (start, len) = range
+        .split_once(':')
+        .ok_or_else(|| format!("Expected START:LEN, got '{}'", range))?;
+    let len = parse_size(len)?;
+    if len == 0 {
+        return Err("Range length must be greater than zero".to_string());
+    }
+
+    Ok(ByteRange {
+        offset: parse_size(start)?,
+        len,
+    })
 }
 
-pub fn parse_args() -> Args {
-    Args::parse()
+/// Parse an age such as '30d', see [`utils::parse_duration`]
+pub(crate) fn parse_age(age: &str) -> Result<Duration, String> {
+    utils::parse_duration(age).map_err(|e| e.to_string())
 }
 
-/// Parse human-readable sizes like "1K", "1M", "1G"
-fn parse_size(size_str: &str) -> Result<u64, String> {
+pub(crate) fn parse_size(size_str: &str) -> Result<u64, String> {
     let size_str = size_str.trim().to_uppercase();
     let len = size_str.len();
     if len == 0 {
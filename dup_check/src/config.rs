@@ -0,0 +1,312 @@
+//! Defaults for command-line options, read from a TOML file.
+//!
+//! The file lives at `$XDG_CONFIG_HOME/dupcheck/config.toml` (or the
+//! platform's equivalent), or wherever `--config` points. Keys are the long
+//! option names, and values are written as on the command line:
+//!
+//! ```toml
+//! paths = ["/home/me/Pictures", "/home/me/Downloads:min=1M"]
+//! min-size = "4K"
+//! exclude = ["node_modules", "*.tmp"]
+//! hash-algo = "blake3"
+//! include-hidden = true
+//! ```
+//!
+//! Precedence, from strongest to weakest: options given on the command line,
+//! then the config file, then the built-in defaults. A switch such as
+//! `include-hidden` that the file turns on is turned off on the command line
+//! with its hidden negation, e.g. `--no-include-hidden`, or `--recursive`,
+//! `--cache` and `--color` for `no-recursive`, `no-cache` and `no-color`.
+
+use crate::cli::{self, Args, OutputFormat};
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use directories::ProjectDirs;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub paths: Option<Vec<String>>,
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
+    pub size_exact: Option<String>,
+    pub exclude: Option<Vec<String>>,
+    pub include_hidden: Option<bool>,
+    pub no_recursive: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub newer_than: Option<String>,
+    pub older_than: Option<String>,
+    pub symlinks: Option<String>,
+    pub follow_junctions: Option<bool>,
+    pub empty: Option<String>,
+    pub by: Option<String>,
+    pub quick: Option<bool>,
+    pub ignore_case_names: Option<bool>,
+    pub ext: Option<Vec<String>>,
+    pub exclude_ext: Option<Vec<String>>,
+    pub no_cache: Option<bool>,
+    pub cache_path: Option<PathBuf>,
+    pub cache_pragma: Option<Vec<String>>,
+    pub incremental: Option<bool>,
+    pub hash_algo: Option<String>,
+    pub keep: Option<String>,
+    pub path_prefer: Option<PathBuf>,
+    pub threads: Option<usize>,
+    pub verify: Option<bool>,
+    pub compare_xattrs: Option<bool>,
+    pub trash: Option<bool>,
+    pub min_copies: Option<usize>,
+    pub format: Option<String>,
+    pub sort: Option<String>,
+    pub bytes: Option<bool>,
+    pub summary_only: Option<bool>,
+    pub log_skipped: Option<bool>,
+    pub no_color: Option<bool>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&data).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")?;
+        Some(project_dirs.config_dir().join("config.toml"))
+    }
+
+    /// The file `--config` names, or the one at [`Config::default_path`] if
+    /// it exists. Only an explicitly named file must exist.
+    pub fn find(explicit: Option<&Path>) -> Result<Option<Self>> {
+        match explicit {
+            Some(path) => Self::load(path).map(Some),
+            None => match Self::default_path().filter(|path| path.exists()) {
+                Some(path) => Self::load(&path).map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Layer the command line over this config: every option set here is
+    /// copied into `args` unless `matches` got it on the command line.
+    /// Values are checked the same way the command line checks them.
+    pub fn merge(&self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let from_file = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+        if let (Some(paths), true) = (&self.paths, from_file("paths")) {
+            args.paths = parse_all("paths", paths, cli::parse_root)?;
+        }
+        if let (Some(size), true) = (&self.min_size, from_file("min_size")) {
+            args.min_size = Some(parse("min-size", size, cli::parse_size)?);
+        }
+        if let (Some(size), true) = (&self.max_size, from_file("max_size")) {
+            args.max_size = Some(parse("max-size", size, cli::parse_size)?);
+        }
+        if let (Some(size), true) = (&self.size_exact, from_file("size_exact")) {
+            args.size_exact = Some(parse("size-exact", size, cli::parse_size)?);
+        }
+        if let (Some(patterns), true) = (&self.exclude, from_file("exclude")) {
+            args.exclude = parse_all("exclude", patterns, cli::parse_glob)?;
+        }
+        if let (Some(depth), true) = (self.max_depth, from_file("max_depth")) {
+            args.max_depth = Some(depth);
+        }
+        if let (Some(age), true) = (&self.newer_than, from_file("newer_than")) {
+            args.newer_than = Some(parse("newer-than", age, cli::parse_age)?);
+        }
+        if let (Some(age), true) = (&self.older_than, from_file("older_than")) {
+            args.older_than = Some(parse("older-than", age, cli::parse_age)?);
+        }
+        if let (Some(policy), true) = (&self.symlinks, from_file("symlinks")) {
+            args.symlinks = parse_enum("symlinks", policy)?;
+        }
+        if let (Some(mode), true) = (&self.by, from_file("group_by")) {
+            args.group_by = parse_enum("by", mode)?;
+        }
+        if let (Some(mode), true) = (&self.empty, from_file("empty_files")) {
+            args.empty_files = parse_enum("empty", mode)?;
+        }
+        if let (Some(extensions), true) = (&self.ext, from_file("extensions")) {
+            args.extensions = Some(parse_all("ext", extensions, cli::parse_extension)?);
+        }
+        if let (Some(extensions), true) = (&self.exclude_ext, from_file("exclude_ext")) {
+            args.exclude_ext = parse_all("exclude-ext", extensions, cli::parse_extension)?;
+        }
+        if let (Some(path), true) = (&self.cache_path, from_file("cache_path")) {
+            args.cache_path = Some(path.clone());
+        }
+        if let (Some(pragmas), true) = (&self.cache_pragma, from_file("cache_pragmas")) {
+            args.cache_pragmas = parse_all("cache-pragma", pragmas, cli::parse_pragma)?;
+        }
+        if let (Some(dir), true) = (&self.path_prefer, from_file("path_prefer")) {
+            args.path_prefer = Some(dir.clone());
+        }
+        if let (Some(algorithm), true) = (&self.hash_algo, from_file("hash_algo")) {
+            args.hash_algo = parse_enum("hash-algo", algorithm)?;
+        }
+        if let (Some(strategy), true) = (&self.keep, from_file("keep")) {
+            args.keep = Some(parse_enum("keep", strategy)?);
+        }
+        if let (Some(threads), true) = (self.threads, from_file("threads")) {
+            args.threads = threads;
+        }
+        if let (Some(copies), true) = (self.min_copies, from_file("min_copies")) {
+            args.min_copies = copies;
+        }
+        if let (Some(format), true) = (&self.format, from_file("format")) {
+            args.format = parse_enum::<OutputFormat>("format", format)?;
+        }
+        if let (Some(key), true) = (&self.sort, from_file("sort")) {
+            args.sort = parse_enum("sort", key)?;
+        }
+
+        // Switches: the command line turns them on, or off with their negation
+        let on_command_line = |ids: [&str; 2]| {
+            ids.iter()
+                .any(|&id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        };
+        let switches = [
+            (
+                &mut args.include_hidden,
+                self.include_hidden,
+                ["include_hidden", "no_include_hidden"],
+            ),
+            (
+                &mut args.no_recursive,
+                self.no_recursive,
+                ["no_recursive", "recursive"],
+            ),
+            (&mut args.quick, self.quick, ["quick", "no_quick"]),
+            (
+                &mut args.ignore_case_names,
+                self.ignore_case_names,
+                ["ignore_case_names", "no_ignore_case_names"],
+            ),
+            (
+                &mut args.follow_junctions,
+                self.follow_junctions,
+                ["follow_junctions", "no_follow_junctions"],
+            ),
+            (&mut args.no_cache, self.no_cache, ["no_cache", "cache"]),
+            (
+                &mut args.incremental,
+                self.incremental,
+                ["incremental", "no_incremental"],
+            ),
+            (&mut args.verify, self.verify, ["verify", "no_verify"]),
+            (
+                &mut args.compare_xattrs,
+                self.compare_xattrs,
+                ["compare_xattrs", "no_compare_xattrs"],
+            ),
+            (&mut args.trash, self.trash, ["trash", "no_trash"]),
+            (&mut args.bytes, self.bytes, ["bytes", "no_bytes"]),
+            (
+                &mut args.summary_only,
+                self.summary_only,
+                ["summary_only", "no_summary_only"],
+            ),
+            (
+                &mut args.log_skipped,
+                self.log_skipped,
+                ["log_skipped", "no_log_skipped"],
+            ),
+            (&mut args.no_color, self.no_color, ["no_color", "color"]),
+        ];
+        for (arg, value, ids) in switches {
+            if let (Some(value), false) = (value, on_command_line(ids)) {
+                *arg = value;
+            }
+        }
+
+        self.check_conflicts(args, matches)
+    }
+
+    /// Reject the combinations the command line rejects, now that either side
+    /// may come from the file
+    fn check_conflicts(&self, args: &Args, matches: &ArgMatches) -> Result<()> {
+        let given = |id: &str, in_file: bool| {
+            in_file || matches.value_source(id) == Some(ValueSource::CommandLine)
+        };
+        let size_exact = args.size_exact.is_some();
+        let conflicts = [
+            (
+                "size-exact",
+                size_exact,
+                "min-size",
+                args.min_size.is_some(),
+            ),
+            (
+                "size-exact",
+                size_exact,
+                "max-size",
+                args.max_size.is_some(),
+            ),
+            (
+                "no-recursive",
+                args.no_recursive,
+                "max-depth",
+                args.max_depth.is_some(),
+            ),
+            ("incremental", args.incremental, "no-cache", args.no_cache),
+            (
+                "quick",
+                args.quick,
+                "by",
+                given("group_by", self.by.is_some()),
+            ),
+            (
+                "quick",
+                args.quick,
+                "compare-range",
+                args.compare_range.is_some(),
+            ),
+            ("quick", args.quick, "burst", args.burst.is_some()),
+            ("quick", args.quick, "images", args.images),
+            ("quick", args.quick, "dedupe-names", args.dedupe_names),
+            ("trash", args.trash, "hardlink", args.hardlink),
+            ("trash", args.trash, "symlink", args.symlink),
+            (
+                "print0",
+                args.print0,
+                "format",
+                given("format", self.format.is_some()),
+            ),
+        ];
+        for (first, first_set, second, second_set) in conflicts {
+            if first_set && second_set {
+                return Err(anyhow!(
+                    "'--{}' cannot be used with '--{}' (set on the command line or in the config file)",
+                    first,
+                    second
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse<T>(key: &str, value: &str, parser: fn(&str) -> Result<T, String>) -> Result<T> {
+    parser(value).map_err(|e| anyhow!("Invalid '{}' in config file: {}", key, e))
+}
+
+fn parse_all<T>(
+    key: &str,
+    values: &[String],
+    parser: fn(&str) -> Result<T, String>,
+) -> Result<Vec<T>> {
+    values
+        .iter()
+        .map(|value| parse(key, value, parser))
+        .collect()
+}
+
+fn parse_enum<T: ValueEnum>(key: &str, value: &str) -> Result<T> {
+    parse(key, value, |value| T::from_str(value, true))
+}
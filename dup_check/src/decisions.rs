@@ -0,0 +1,148 @@
+//! Persistent per-group review decisions.
+//!
+//! Interactive reviews of large result sets rarely finish in one sitting.
+//! Decisions are keyed by the group's content hash and written to disk as
+//! soon as they are made, so a later run over the same tree only asks about
+//! groups that haven't been decided yet. Each decision also records the
+//! group's members; once they change, for example because a new copy turned
+//! up, the group is asked about again.
+
+use crate::file_info::FileInfo;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// Delete every duplicate, keeping the group's original
+    Delete,
+    /// Keep all copies
+    Keep,
+    /// Leave the group alone without judging it
+    Skip,
+}
+
+/// A decision together with the members of the group it was made for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Recorded {
+    decision: Decision,
+    paths: BTreeSet<PathBuf>,
+}
+
+/// An entry of the decisions file. Files written before members were
+/// recorded hold bare decisions, which match no group.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Recorded(Recorded),
+    Legacy(Decision),
+}
+
+pub struct DecisionStore {
+    path: PathBuf,
+    decisions: BTreeMap<String, Recorded>,
+}
+
+impl DecisionStore {
+    /// Load decisions from `path`, starting empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let decisions = if path.exists() {
+            let data = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read decisions file {}", path.display()))?;
+            let stored: BTreeMap<String, Stored> = serde_json::from_str(&data)
+                .with_context(|| format!("Invalid decisions file {}", path.display()))?;
+            stored
+                .into_iter()
+                .map(|(hash, stored)| {
+                    let recorded = match stored {
+                        Stored::Recorded(recorded) => recorded,
+                        Stored::Legacy(decision) => Recorded {
+                            decision,
+                            paths: BTreeSet::new(),
+                        },
+                    };
+                    (hash, recorded)
+                })
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(DecisionStore {
+            path: path.to_path_buf(),
+            decisions,
+        })
+    }
+
+    pub fn default_path() -> Result<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
+            .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+
+        let data_dir = project_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+
+        Ok(data_dir.join("decisions.json"))
+    }
+
+    /// The decision recorded for the group `hash`, if it was made for
+    /// exactly these `files`
+    pub fn get(&self, hash: &str, files: &[FileInfo]) -> Option<Decision> {
+        let recorded = self.decisions.get(hash)?;
+        (recorded.paths == member_paths(files)).then_some(recorded.decision)
+    }
+
+    /// Record a decision about the group `hash` of `files` and persist it
+    /// immediately
+    pub fn record(&mut self, hash: &str, files: &[FileInfo], decision: Decision) -> Result<()> {
+        let recorded = Recorded {
+            decision,
+            paths: member_paths(files),
+        };
+        self.decisions.insert(hash.to_string(), recorded);
+        self.save()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(&self.decisions)?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Failed to write decisions file {}", self.path.display()))
+    }
+
+    /// Hashes of groups that still need a decision, in a stable order.
+    /// Groups whose members changed since they were decided are included.
+    pub fn pending<'a>(&self, duplicates: &'a HashMap<String, Vec<FileInfo>>) -> Vec<&'a String> {
+        let mut pending: Vec<&String> = duplicates
+            .iter()
+            .filter(|(hash, files)| self.get(hash, files).is_none())
+            .map(|(hash, _)| hash)
+            .collect();
+        pending.sort();
+        pending
+    }
+
+    /// Groups whose recorded decision is [`Decision::Delete`] and whose
+    /// members are still the ones it was made for
+    pub fn groups_to_delete(
+        &self,
+        duplicates: &HashMap<String, Vec<FileInfo>>,
+    ) -> HashMap<String, Vec<FileInfo>> {
+        duplicates
+            .iter()
+            .filter(|(hash, files)| self.get(hash, files) == Some(Decision::Delete))
+            .map(|(hash, files)| (hash.clone(), files.clone()))
+            .collect()
+    }
+}
+
+fn member_paths(files: &[FileInfo]) -> BTreeSet<PathBuf> {
+    files.iter().map(|file| file.path.clone()).collect()
+}
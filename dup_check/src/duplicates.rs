@@ -0,0 +1,286 @@
+//! Group-level operations on scan results, such as choosing the original
+
+use crate::file_info::FileInfo;
+use clap::ValueEnum;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Order in which the files of a group are ranked; the first one is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeepStrategy {
+    /// Least recently modified file
+    Oldest,
+    /// Most recently modified file
+    Newest,
+    /// File with the shortest path
+    ShortestPath,
+    /// First path in alphabetical order
+    FirstAlphabetical,
+}
+
+impl KeepStrategy {
+    /// Sort `files` so the one to keep comes first. Ties, and files without
+    /// a known modification time, fall back to alphabetical order.
+    pub fn sort(self, files: &mut [FileInfo]) {
+        files.sort_by(|a, b| self.compare(a, b).then_with(|| a.path.cmp(&b.path)));
+    }
+
+    /// Index of the file this strategy keeps, i.e. the one [`sort`] would
+    /// put first
+    ///
+    /// [`sort`]: KeepStrategy::sort
+    pub fn survivor(self, files: &[FileInfo]) -> Option<usize> {
+        (0..files.len()).min_by(|&a, &b| {
+            self.compare(&files[a], &files[b])
+                .then_with(|| files[a].path.cmp(&files[b].path))
+        })
+    }
+
+    fn compare(self, a: &FileInfo, b: &FileInfo) -> Ordering {
+        match self {
+            KeepStrategy::Oldest | KeepStrategy::Newest => match (a.modified, b.modified) {
+                (Some(x), Some(y)) if self == KeepStrategy::Oldest => x.cmp(&y),
+                (Some(x), Some(y)) => y.cmp(&x),
+                // Files with a known time rank first
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            KeepStrategy::ShortestPath => a.path.as_os_str().len().cmp(&b.path.as_os_str().len()),
+            KeepStrategy::FirstAlphabetical => Ordering::Equal,
+        }
+    }
+}
+
+/// Rules deciding which file of a group is kept as the original.
+///
+/// The chosen file is moved to index 0 of its group, which is where the
+/// display and deletion code expect the original to be.
+#[derive(Debug, Clone, Default)]
+pub struct KeepPolicy {
+    /// Exact files that must be kept when present in a group. If a group
+    /// contains several, the one listed first wins.
+    pub keep_paths: Vec<PathBuf>,
+    /// Treat read-only files as originals
+    pub original_if_readonly: bool,
+    /// Treat files carrying this extended attribute as originals (Unix only)
+    pub original_if_xattr: Option<String>,
+    /// Rank the files of each group before the rules above are applied
+    pub strategy: Option<KeepStrategy>,
+    /// Prefer a file under this directory as the original, over the
+    /// [`KeepStrategy`] ranking but below the rules above
+    pub prefer_dir: Option<PathBuf>,
+}
+
+/// A group containing more than one `--keep-path` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepConflict {
+    pub kept: PathBuf,
+    pub also_listed: Vec<PathBuf>,
+}
+
+impl KeepPolicy {
+    /// Reorder every group so its original comes first.
+    ///
+    /// Groups are first sorted by the [`KeepStrategy`], if any, then the
+    /// first file under [`prefer_dir`](KeepPolicy::prefer_dir) moves to the
+    /// front. Files marked as originals (read-only or tagged) are pinned so they are
+    /// never deleted, and the first of them becomes the original unless a
+    /// `--keep-path` file is present, which always ranks highest.
+    pub fn apply(&self, duplicates: &mut HashMap<String, Vec<FileInfo>>) -> Vec<KeepConflict> {
+        if let Some(strategy) = self.strategy {
+            for files in duplicates.values_mut() {
+                strategy.sort(files);
+            }
+        }
+        if let Some(dir) = &self.prefer_dir {
+            let dir = canonical(dir);
+            for files in duplicates.values_mut() {
+                if let Some(index) = files
+                    .iter()
+                    .position(|file| canonical(&file.path).starts_with(&dir))
+                {
+                    files[..=index].rotate_right(1);
+                }
+            }
+        }
+
+        let has_markers = self.original_if_readonly || self.original_if_xattr.is_some();
+        if self.keep_paths.is_empty() && !has_markers {
+            return Vec::new();
+        }
+
+        let keep_paths: Vec<PathBuf> = self.keep_paths.iter().map(|p| canonical(p)).collect();
+        let mut conflicts = Vec::new();
+        for files in duplicates.values_mut() {
+            if has_markers {
+                for file in files.iter_mut() {
+                    file.pinned = self.is_marked_original(&file.path);
+                }
+                if let Some(index) = files.iter().position(|file| file.pinned) {
+                    files[..=index].rotate_right(1);
+                }
+            }
+            conflicts.extend(select_keep_path(files, &keep_paths));
+        }
+        conflicts
+    }
+
+    /// Whether the file carries one of the configured original markers
+    pub fn is_marked_original(&self, path: &Path) -> bool {
+        if self.original_if_readonly {
+            let readonly = fs::metadata(path)
+                .map(|metadata| metadata.permissions().readonly())
+                .unwrap_or(false);
+            if readonly {
+                return true;
+            }
+        }
+
+        match &self.original_if_xattr {
+            Some(name) => has_xattr(path, name),
+            None => false,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn has_xattr(path: &Path, name: &str) -> bool {
+    matches!(xattr::get(path, name), Ok(Some(_)))
+}
+
+#[cfg(not(unix))]
+fn has_xattr(_path: &Path, _name: &str) -> bool {
+    false
+}
+
+/// Move the member matching the earliest keep-path to the front
+fn select_keep_path(files: &mut [FileInfo], keep_paths: &[PathBuf]) -> Option<KeepConflict> {
+    let mut matches: Vec<(usize, usize)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(index, file)| {
+            let path = canonical(&file.path);
+            keep_paths
+                .iter()
+                .position(|keep| *keep == path)
+                .map(|rank| (rank, index))
+        })
+        .collect();
+    matches.sort();
+
+    let &(_, chosen) = matches.first()?;
+    let conflict = (matches.len() > 1).then(|| KeepConflict {
+        kept: files[chosen].path.clone(),
+        also_listed: matches[1..]
+            .iter()
+            .map(|&(_, index)| files[index].path.clone())
+            .collect(),
+    });
+
+    files[..=chosen].rotate_right(1);
+    conflict
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Bytes taken by the copies of a group beyond its original
+pub fn wasted_bytes(files: &[FileInfo]) -> u64 {
+    reclaimable_bytes(files, None)
+}
+
+/// Bytes freed by deleting every file of `group` except the one kept.
+///
+/// The survivor is the file `keep` ranks first, or index 0 (where
+/// [`KeepPolicy::apply`] puts the original) without a strategy. Sizes are
+/// summed per file, so groups whose members differ in size are counted
+/// correctly. Each file counts the bytes it occupies on disk
+/// ([`FileInfo::allocated`]), so sparse files aren't overstated.
+pub fn reclaimable_bytes(group: &[FileInfo], keep: Option<KeepStrategy>) -> u64 {
+    let survivor = match keep {
+        Some(strategy) => strategy.survivor(group),
+        None => (!group.is_empty()).then_some(0),
+    };
+    group
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| Some(index) != survivor)
+        .map(|(_, file)| file.allocated)
+        .sum()
+}
+
+/// Collects result groups, keeping at most `limit` of them.
+///
+/// When full, a new group only gets in by evicting the group wasting the
+/// least space, so the survivors are always the `limit` biggest groups seen.
+/// A min-heap keyed on wasted bytes finds the eviction candidate.
+#[derive(Debug, Default)]
+pub struct TopGroups {
+    limit: Option<usize>,
+    groups: HashMap<String, Vec<FileInfo>>,
+    smallest: BinaryHeap<Reverse<(u64, String)>>,
+    dropped: usize,
+}
+
+impl TopGroups {
+    /// `None` keeps every group
+    pub fn new(limit: Option<usize>) -> Self {
+        TopGroups {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    pub fn insert(&mut self, key: String, files: Vec<FileInfo>) {
+        let Some(limit) = self.limit else {
+            self.groups.insert(key, files);
+            return;
+        };
+
+        let wasted = wasted_bytes(&files);
+        if self.groups.len() < limit {
+            self.smallest.push(Reverse((wasted, key.clone())));
+            self.groups.insert(key, files);
+            return;
+        }
+
+        self.dropped += 1;
+        let evict =
+            matches!(self.smallest.peek(), Some(Reverse(min)) if (wasted, &key) > (min.0, &min.1));
+        if evict {
+            let Reverse((_, evicted)) = self.smallest.pop().unwrap();
+            self.groups.remove(&evicted);
+            self.smallest.push(Reverse((wasted, key.clone())));
+            self.groups.insert(key, files);
+        }
+    }
+
+    pub fn extend(&mut self, groups: impl IntoIterator<Item = (String, Vec<FileInfo>)>) {
+        for (key, files) in groups {
+            self.insert(key, files);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// How many groups were left out because of the limit
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn into_groups(self) -> HashMap<String, Vec<FileInfo>> {
+        self.groups
+    }
+}
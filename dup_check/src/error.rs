@@ -0,0 +1,61 @@
+//! Errors returned by [`Scanner`](crate::Scanner) and [`Cache`](crate::Cache).
+//!
+//! Callers can match on the variants to tell failure modes apart, while the
+//! binary simply converts them into `anyhow` errors.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DupCheckError {
+    /// A scan root, or a file an operation needs, doesn't exist
+    #[error("Path not found: {}", .0.display())]
+    PathNotFound(PathBuf),
+    /// Reading a directory or file was refused
+    #[error("Permission denied: {}", .0.display())]
+    PermissionDenied(PathBuf),
+    /// The cache database failed, e.g. because it is corrupt or locked
+    #[error("Cache error: {0}")]
+    CacheError(#[from] rusqlite::Error),
+    /// A file given to [`Cache::import_from`](crate::Cache::import_from)
+    /// isn't a readable cache export
+    #[error("Invalid cache export: {0}")]
+    InvalidCacheFile(String),
+    /// Reading or writing a file failed
+    #[error("I/O error: {0}")]
+    HashError(#[from] io::Error),
+    /// The operation was stopped through its
+    /// [`CancellationToken`](crate::cancel::CancellationToken)
+    #[error("Cancelled")]
+    Cancelled,
+    /// Any other failure
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub type Result<T, E = DupCheckError> = std::result::Result<T, E>;
+
+impl DupCheckError {
+    /// Classify an I/O error that occurred on `path`
+    pub fn io(path: &Path, error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => DupCheckError::PathNotFound(path.to_path_buf()),
+            io::ErrorKind::PermissionDenied => DupCheckError::PermissionDenied(path.to_path_buf()),
+            _ => DupCheckError::HashError(error),
+        }
+    }
+}
+
+impl From<walkdir::Error> for DupCheckError {
+    fn from(error: walkdir::Error) -> Self {
+        match error.path().map(Path::to_path_buf) {
+            Some(path) if error.io_error().is_some() => {
+                DupCheckError::io(&path, error.into_io_error().unwrap())
+            }
+            _ => DupCheckError::Other(error.into()),
+        }
+    }
+}
@@ -1,10 +1,131 @@
-use std::path::PathBuf;
+use std::{fs::Metadata, path::PathBuf, time::SystemTime};
+
+/// How the members of a duplicate group were matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchKind {
+    /// Byte-for-byte identical content
+    #[default]
+    Exact,
+    /// Text files that only match once letter case is ignored
+    CaseInsensitiveText,
+    /// Files that only share the bytes of a user-specified range
+    ByteRange,
+    /// Photos taken moments apart with similar sizes; content differs
+    Burst,
+    /// Images that look alike by their perceptual hash, such as resized or
+    /// re-encoded copies; content differs
+    SimilarImage,
+    /// Zero-byte files; identical, but often placeholders or markers
+    Empty,
+    /// Files that only share their file name; content wasn't compared
+    SameName,
+    /// Files sharing their size and file name; content wasn't compared, so
+    /// they are only probably identical
+    Unverified,
+}
+
+impl MatchKind {
+    pub fn is_exact(self) -> bool {
+        self == MatchKind::Exact
+    }
+
+    /// Whether members of such a group may be deleted. Only exact matches are
+    /// deleted by default; range matches, similar images and empty files
+    /// additionally need `--force`.
+    pub fn can_delete(self, force: bool) -> bool {
+        match self {
+            MatchKind::Exact => true,
+            MatchKind::ByteRange | MatchKind::SimilarImage | MatchKind::Empty => force,
+            MatchKind::CaseInsensitiveText
+            | MatchKind::Burst
+            | MatchKind::SameName
+            | MatchKind::Unverified => false,
+        }
+    }
+
+    /// Short stable identifier, used in exported reports
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::CaseInsensitiveText => "case-insensitive-text",
+            MatchKind::ByteRange => "byte-range",
+            MatchKind::Burst => "burst",
+            MatchKind::SimilarImage => "similar-image",
+            MatchKind::Empty => "empty",
+            MatchKind::SameName => "same-name",
+            MatchKind::Unverified => "unverified",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            MatchKind::Exact => "identical content",
+            MatchKind::CaseInsensitiveText => "same text ignoring case (not byte-identical)",
+            MatchKind::ByteRange => "same bytes in the compared range only (not a full match)",
+            MatchKind::Burst => "likely burst shots: close in time and size (content differs)",
+            MatchKind::SimilarImage => "similar images, e.g. resized or re-encoded (not identical)",
+            MatchKind::Empty => "empty files (no content to compare)",
+            MatchKind::SameName => "same file name only (content not compared)",
+            MatchKind::Unverified => "unverified: same size and file name (content not compared)",
+        }
+    }
+}
+
+/// Device and inode number of a file. Hard links to the same data share it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl FileId {
+    /// Only available on Unix; `None` elsewhere
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Some(FileId {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = metadata;
+            None
+        }
+    }
+}
+
+/// Bytes `metadata`'s file occupies on disk, capped at its length so that
+/// block rounding doesn't count. Only known on Unix; the length elsewhere.
+pub fn allocated_size(metadata: &Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.len().min(metadata.blocks().saturating_mul(512))
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.len()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct FileInfo {
     pub path: PathBuf,
     pub size: u64,
     pub hash: Option<String>,
+    pub match_kind: MatchKind,
+    /// Modification time seen during the scan; validates cached hashes
+    pub modified: Option<SystemTime>,
+    /// Designated as an original by the keep policy; never deleted
+    pub pinned: bool,
+    /// Identity of the underlying file, if known
+    pub file_id: Option<FileId>,
+    /// Bytes the file occupies on disk, at most `size`. Less than `size` for
+    /// sparse files; equal to it when unknown.
+    pub allocated: u64,
 }
 
 impl FileInfo {
@@ -13,6 +134,11 @@ impl FileInfo {
             path,
             size,
             hash: None,
+            match_kind: MatchKind::Exact,
+            modified: None,
+            pinned: false,
+            file_id: None,
+            allocated: size,
         }
     }
 
@@ -21,6 +147,11 @@ impl FileInfo {
             path,
             size,
             hash: Some(hash),
+            match_kind: MatchKind::Exact,
+            modified: None,
+            pinned: false,
+            file_id: None,
+            allocated: size,
         }
     }
 }
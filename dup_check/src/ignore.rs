@@ -0,0 +1,102 @@
+//! Groups the user chose to keep for good.
+//!
+//! Some duplicates are intentional, such as redundant copies a build or a
+//! backup scheme relies on. Entries in the ignore list hide such groups from
+//! every later report, either by content hash or by the exact set of paths.
+
+use crate::file_info::FileInfo;
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IgnoreEntry {
+    /// Any group with this content hash, wherever its files live. This also
+    /// hides copies made later, in any directory; the review only records
+    /// [`IgnoreEntry::Paths`] entries.
+    Hash { hash: String },
+    /// Groups made up only of these paths. A copy elsewhere brings the group
+    /// back.
+    Paths { paths: Vec<PathBuf> },
+}
+
+impl IgnoreEntry {
+    pub fn matches(&self, hash: &str, files: &[FileInfo]) -> bool {
+        match self {
+            IgnoreEntry::Hash { hash: ignored } => ignored == hash,
+            IgnoreEntry::Paths { paths } => files.iter().all(|file| paths.contains(&file.path)),
+        }
+    }
+}
+
+pub struct IgnoreList {
+    path: PathBuf,
+    entries: Vec<IgnoreEntry>,
+}
+
+impl IgnoreList {
+    /// Load the list from `path`, starting empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = if path.exists() {
+            let data = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read ignore list {}", path.display()))?;
+            serde_json::from_str(&data)
+                .with_context(|| format!("Invalid ignore list {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+
+        Ok(IgnoreList {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn default_path() -> Result<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
+            .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+
+        let data_dir = project_dirs.data_dir();
+        fs::create_dir_all(data_dir)?;
+
+        Ok(data_dir.join("ignored.json"))
+    }
+
+    pub fn entries(&self) -> &[IgnoreEntry] {
+        &self.entries
+    }
+
+    /// Add an entry and persist the list immediately
+    pub fn add(&mut self, entry: IgnoreEntry) -> Result<()> {
+        if !self.entries.contains(&entry) {
+            self.entries.push(entry);
+        }
+        self.save()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(&self.entries)?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Failed to write ignore list {}", self.path.display()))
+    }
+
+    pub fn is_ignored(&self, hash: &str, files: &[FileInfo]) -> bool {
+        self.entries.iter().any(|entry| entry.matches(hash, files))
+    }
+
+    /// Drop ignored groups from `duplicates`, returning how many were removed
+    pub fn filter(&self, duplicates: &mut HashMap<String, Vec<FileInfo>>) -> usize {
+        let before = duplicates.len();
+        duplicates.retain(|hash, files| !self.is_ignored(hash, files));
+        before - duplicates.len()
+    }
+}
@@ -1,10 +1,28 @@
+use crate::{
+    decisions::{Decision, DecisionStore},
+    file_info::FileInfo,
+    ignore::{IgnoreEntry, IgnoreList},
+    report::{self, SortKey},
+    scanner::ScanRoot,
+    ui::{DeleteOptions, LinkKind, Markers, SizeFormat},
+};
 use anyhow::Result;
 use console::Term;
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
-use std::path::PathBuf;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use similar::TextDiff;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files larger than this are compared by metadata instead of a text diff
+const DIFF_MAX_SIZE: u64 = 1024 * 1024;
 
 pub struct InteractiveConfig {
-    pub path: PathBuf,
+    pub roots: Vec<ScanRoot>,
     pub min_size: Option<u64>,
     pub max_size: Option<u64>,
     pub use_cache: bool,
@@ -48,16 +66,19 @@ fn parse_size(input: &str) -> Option<u64> {
     Some((value * multiplier as f64) as u64)
 }
 
-pub fn get_interactive_config() -> Result<InteractiveConfig> {
+pub fn get_interactive_config(markers: Markers, sizes: SizeFormat) -> Result<InteractiveConfig> {
     let theme = ColorfulTheme::default();
     let term = Term::stdout();
     term.clear_screen()?;
 
-    println!("🔍 DupCheck - Duplicate File Finder");
+    println!(
+        "{}DupCheck - Duplicate File Finder",
+        markers.pick("🔍 ", "")
+    );
     println!("===================================\n");
 
     // Directory selection
-    let path = select_directory(&theme)?;
+    let path = select_directory(&theme, markers)?;
 
     // Size filters
     let use_size_filters = Confirm::with_theme(&theme)
@@ -139,35 +160,396 @@ pub fn get_interactive_config() -> Result<InteractiveConfig> {
 
     // Configuration summary
     term.clear_screen()?;
-    println!("📋 Scan Configuration:");
+    println!("{}Scan Configuration:", markers.pick("📋 ", ""));
     println!("Directory: {}", path.display());
     if let Some(min) = min_size {
-        println!("Minimum size: {}", format_size(min));
+        println!("Minimum size: {}", sizes.format(min));
     }
     if let Some(max) = max_size {
-        println!("Maximum size: {}", format_size(max));
+        println!("Maximum size: {}", sizes.format(max));
     }
     println!("Cache enabled: {}", if use_cache { "Yes" } else { "No" });
     println!("\nPress Enter to start scanning...");
     term.read_line()?;
 
     Ok(InteractiveConfig {
-        path,
+        roots: vec![ScanRoot::new(path)],
         min_size,
         max_size,
         use_cache,
     })
 }
 
-fn select_directory(theme: &ColorfulTheme) -> Result<PathBuf> {
+/// Walk through undecided groups one at a time, persisting each decision.
+///
+/// Returns every group marked for deletion, including ones decided in
+/// earlier sessions. Groups the user ignores are added to `ignored` and
+/// won't be reported again.
+pub fn review_groups(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    store: &mut DecisionStore,
+    ignored: &mut IgnoreList,
+    markers: Markers,
+    sizes: SizeFormat,
+) -> Result<HashMap<String, Vec<FileInfo>>> {
+    let theme = ColorfulTheme::default();
+    let pending = store.pending(duplicates);
+    let already_decided = duplicates.len() - pending.len();
+
+    if already_decided > 0 {
+        println!(
+            "\n{}Resuming review: {} of {} groups already decided",
+            markers.pick("⏩ ", ""),
+            already_decided,
+            duplicates.len()
+        );
+    }
+
+    let choices = [
+        markers.pick(
+            "🗑️  Delete duplicates (keep original)",
+            "Delete duplicates (keep original)",
+        ),
+        markers.pick("🔒 Keep all copies", "Keep all copies"),
+        markers.pick("⏭️  Skip this group", "Skip this group"),
+        markers.pick(
+            "⏸️  Stop reviewing (resume later)",
+            "Stop reviewing (resume later)",
+        ),
+        markers.pick("🙈 Ignore this group forever", "Ignore this group forever"),
+        markers.pick("🔍 Show differences", "Show differences"),
+    ];
+    let warning = markers.pick("⚠️  ", "WARNING: ");
+
+    'groups: for (i, hash) in pending.iter().enumerate() {
+        let files = &duplicates[hash.as_str()];
+        println!(
+            "\nGroup {}/{} ({} each)",
+            already_decided + i + 1,
+            duplicates.len(),
+            sizes.format(files[0].size)
+        );
+        for (j, file) in files.iter().enumerate() {
+            let symbol = if j == 0 {
+                markers.pick("🔒", "[keep]")
+            } else {
+                markers.pick("📄", "[dup] ")
+            };
+            println!("{} {}", symbol, file.path.display());
+        }
+
+        // Near-duplicates aren't byte-identical, so offer to inspect them first
+        let choices = if files[0].match_kind.is_exact() {
+            &choices[..5]
+        } else {
+            println!("{}{}", warning, files[0].match_kind.description());
+            &choices[..]
+        };
+
+        let decision = loop {
+            let selection = Select::with_theme(&theme)
+                .with_prompt("What should happen to this group?")
+                .items(choices)
+                .default(0)
+                .interact()?;
+
+            match selection {
+                0 => break Decision::Delete,
+                1 => break Decision::Keep,
+                2 => break Decision::Skip,
+                3 => break 'groups,
+                4 => {
+                    ignored.add(IgnoreEntry::Paths {
+                        paths: files.iter().map(|file| file.path.clone()).collect(),
+                    })?;
+                    // Intentional copies are kept, so nothing left to decide
+                    break Decision::Keep;
+                }
+                _ => {
+                    for file in &files[1..] {
+                        println!(
+                            "\n{}",
+                            describe_differences(&files[0].path, &file.path, sizes)?
+                        );
+                    }
+                }
+            }
+        };
+        store.record(hash, files, decision)?;
+    }
+
+    Ok(store.groups_to_delete(duplicates))
+}
+
+/// Let the user tick which files of each group to delete. Everything but the
+/// suggested original (and files pinned as originals) starts out ticked.
+///
+/// Each returned group starts with a file that is kept, followed by the
+/// files picked for deletion, ready for [`crate::ui::delete_duplicates`].
+/// Groups with nothing picked are left out.
+pub fn select_deletions(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    markers: Markers,
+    sizes: SizeFormat,
+) -> Result<HashMap<String, Vec<FileInfo>>> {
+    let warning = markers.pick("⚠️  ", "WARNING: ");
+    let theme = ColorfulTheme::default();
+    let mut hashes: Vec<&String> = duplicates.keys().collect();
+    hashes.sort();
+
+    let mut to_delete = HashMap::new();
+    for (i, hash) in hashes.into_iter().enumerate() {
+        let files = &duplicates[hash];
+        println!(
+            "\nGroup {}/{} ({} each)",
+            i + 1,
+            duplicates.len(),
+            sizes.format(files[0].size)
+        );
+        if !files[0].match_kind.is_exact() {
+            println!("{}{}", warning, files[0].match_kind.description());
+        }
+
+        let items: Vec<String> = files
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect();
+        let defaults: Vec<bool> = files
+            .iter()
+            .enumerate()
+            .map(|(j, file)| j > 0 && !file.pinned)
+            .collect();
+
+        let group = loop {
+            let picked = MultiSelect::with_theme(&theme)
+                .with_prompt("Files to delete (space toggles, enter confirms)")
+                .items(&items)
+                .defaults(&defaults)
+                .interact()?;
+            match deletion_group(files, &picked) {
+                Some(group) => break Some(group),
+                None if picked.is_empty() => break None,
+                None => println!("{}Leave at least one file of the group unticked", warning),
+            }
+        };
+        if let Some(group) = group {
+            to_delete.insert(hash.clone(), group);
+        }
+    }
+
+    Ok(to_delete)
+}
+
+/// Ask before deleting the duplicates of each group, or with `link` before
+/// replacing them with links, one group at a time in the report's `sort`
+/// order. Groups that may not be deleted (see
+/// [`crate::file_info::MatchKind::can_delete`]) are left out without asking.
+///
+/// Returns the confirmed groups, ready for [`crate::ui::delete_duplicates`]
+/// or [`crate::ui::link_duplicates`].
+pub fn confirm_deletions(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DeleteOptions,
+    sort: SortKey,
+    link: Option<LinkKind>,
+) -> Result<HashMap<String, Vec<FileInfo>>> {
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout().lock();
+    let mut delete_remaining = false;
+    let mut to_delete = HashMap::new();
+    for (hash, files) in report::sorted_groups(duplicates, sort) {
+        if !files[0].match_kind.can_delete(options.force) {
+            continue;
+        }
+        let confirmed = confirm_group_deletion_with(
+            &mut input,
+            &mut output,
+            files,
+            &files[0],
+            link,
+            &mut delete_remaining,
+            options,
+        )?;
+        if confirmed {
+            to_delete.insert(hash.clone(), files.clone());
+        }
+    }
+
+    Ok(to_delete)
+}
+
+/// Ask on stdin whether to delete every file of `group` but `survivor`, or
+/// with `link` to replace them with links to it. The files are listed
+/// first, along with any that `options` keep anyway. Answering "a" confirms
+/// this group and sets `delete_remaining`, after which later calls confirm
+/// without asking. End of input counts as "no".
+pub fn confirm_group_deletion(
+    group: &[FileInfo],
+    survivor: &FileInfo,
+    link: Option<LinkKind>,
+    delete_remaining: &mut bool,
+    options: &DeleteOptions,
+) -> Result<bool> {
+    confirm_group_deletion_with(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout().lock(),
+        group,
+        survivor,
+        link,
+        delete_remaining,
+        options,
+    )
+}
+
+/// Like [`confirm_group_deletion`], but reads the answers from `input` and
+/// writes the questions to `output`
+pub fn confirm_group_deletion_with<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    group: &[FileInfo],
+    survivor: &FileInfo,
+    link: Option<LinkKind>,
+    delete_remaining: &mut bool,
+    options: &DeleteOptions,
+) -> Result<bool> {
+    if *delete_remaining {
+        return Ok(true);
+    }
+
+    let action = match link {
+        Some(LinkKind::Hard) => "replace with hard links",
+        Some(LinkKind::Symbolic) => "replace with symbolic links",
+        None if options.trash => "move to the trash",
+        None => "delete",
+    };
+    let others = group.iter().filter(|file| file.path != survivor.path);
+    let (kept, affected): (Vec<_>, Vec<_>) =
+        others.partition(|file| options.keep_reason(file).is_some());
+    writeln!(
+        output,
+        "\nKeep {} ({} each) and {} {} duplicate(s):",
+        survivor.path.display(),
+        options.size_format.format(survivor.size),
+        action,
+        affected.len()
+    )?;
+    for file in &affected {
+        writeln!(output, "    {}", file.path.display())?;
+    }
+    for file in &kept {
+        let reason = options.keep_reason(file).unwrap_or_default();
+        writeln!(output, "  {}: {}", reason, file.path.display())?;
+    }
+    if affected.is_empty() {
+        return Ok(false);
+    }
+
+    loop {
+        writeln!(output, "Go ahead? (y/n, a = all remaining groups)")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" | "" => return Ok(false),
+            "a" | "all" => {
+                *delete_remaining = true;
+                return Ok(true);
+            }
+            _ => writeln!(
+                output,
+                "{}Answer y (yes), n (no) or a (yes to all remaining groups)",
+                options.markers.pick("⚠️  ", "")
+            )?,
+        }
+    }
+}
+
+/// Arrange `files` for deletion of the ones at `picked`: the first file not
+/// picked leads, as the one kept. `None` when nothing or everything is picked.
+pub fn deletion_group(files: &[FileInfo], picked: &[usize]) -> Option<Vec<FileInfo>> {
+    let (deleted, kept): (Vec<_>, Vec<_>) = files
+        .iter()
+        .enumerate()
+        .partition(|(j, _)| picked.contains(j));
+    let (_, original) = kept.first()?;
+    if deleted.is_empty() {
+        return None;
+    }
+
+    let mut group = vec![(*original).clone()];
+    group.extend(deleted.into_iter().map(|(_, file)| file.clone()));
+    Some(group)
+}
+
+/// How `other` differs from `original`: a unified diff when both are small
+/// UTF-8 text files, otherwise their metadata side by side, with sizes
+/// printed as `sizes` says
+pub fn describe_differences(original: &Path, other: &Path, sizes: SizeFormat) -> Result<String> {
+    if let (Some(old), Some(new)) = (read_small_text(original), read_small_text(other)) {
+        let diff = TextDiff::from_lines(&old, &new);
+        return Ok(diff
+            .unified_diff()
+            .header(
+                &original.display().to_string(),
+                &other.display().to_string(),
+            )
+            .to_string());
+    }
+
+    let describe = |path: &Path| -> Result<(String, String)> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| SystemTime::now().duration_since(time).ok())
+            .map_or_else(|| "unknown".to_string(), |age| format_age(age.as_secs()));
+        Ok((sizes.format(metadata.len()), modified))
+    };
+    let (old_size, old_modified) = describe(original)?;
+    let (new_size, new_modified) = describe(other)?;
+
+    Ok(format!(
+        "{:<10} {:<30} {:<30}\n{:<10} {:<30} {:<30}\n{:<10} {:<30} {:<30}",
+        "",
+        original.display(),
+        other.display(),
+        "size",
+        old_size,
+        new_size,
+        "modified",
+        old_modified,
+        new_modified
+    ))
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+fn read_small_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > DIFF_MAX_SIZE {
+        return None;
+    }
+    String::from_utf8(fs::read(path).ok()?).ok()
+}
+
+fn select_directory(theme: &ColorfulTheme, markers: Markers) -> Result<PathBuf> {
     let mut current_dir = std::env::current_dir()?;
     let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
 
     loop {
         let mut entries = vec![
-            String::from("📂 Select current directory"),
-            String::from("📁 Parent directory"),
-            String::from("🏠 Go to home directory"),
+            String::from(markers.pick("📂 Select current directory", "Select current directory")),
+            String::from(markers.pick("📁 Parent directory", "Parent directory")),
+            String::from(markers.pick("🏠 Go to home directory", "Go to home directory")),
         ];
 
         // Add subdirectories
@@ -209,20 +591,3 @@ fn select_directory(theme: &ColorfulTheme) -> Result<PathBuf> {
         }
     }
 }
-
-fn format_size(size: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = size as f64;
-    let mut unit_index = 0;
-
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
-    }
-
-    if unit_index == 0 {
-        format!("{:.0} {}", size, UNITS[unit_index])
-    } else {
-        format!("{:.2} {}", size, UNITS[unit_index])
-    }
-}
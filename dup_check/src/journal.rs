@@ -0,0 +1,355 @@
+//! Append-only journal of finished size groups, for resuming crashed scans.
+//!
+//! Only scans started with `--resume` or `--journal` keep one: in the hash
+//! cache's `scan_journal` table when a cache is in use, and otherwise in a
+//! file. Journals in the cache, and default journal files, are kept per set
+//! of scanned roots, so scans of other roots don't replace them.
+//!
+//! Every same-size group the scanner finishes hashing is appended as one
+//! JSON line, together with the duplicate groups found in it. After a crash,
+//! a scan started with `--resume` takes those results from the journal and
+//! only hashes the size groups that weren't finished. A size group is only
+//! taken over while its members, and their modification times, are exactly
+//! those that were journaled.
+//!
+//! Every line is written before the scanner reports its groups, so they
+//! survive the process dying. Lines holding duplicates are also synced to
+//! disk, at most once per [`SYNC_INTERVAL`], so a power loss costs at most
+//! the groups confirmed in that window. A line torn by the crash is ignored.
+//! In the cache, each size group is committed as a row of its own.
+
+use crate::{
+    cache::Cache,
+    file_info::FileInfo,
+    utils::{self, HashAlgorithm},
+};
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+/// Bumped whenever the line format changes; other versions aren't resumed
+const JOURNAL_VERSION: u32 = 1;
+
+/// Minimum time between two syncs of the journal to disk
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// First line of a journal, identifying the scan it belongs to
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct JournalHeader {
+    version: u32,
+    roots: Vec<PathBuf>,
+    algorithm: HashAlgorithm,
+}
+
+/// A finished size group
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    size: u64,
+    /// [`fingerprint`] of all members of the size group
+    members: String,
+    groups: Vec<JournalGroup>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalGroup {
+    hash: String,
+    paths: Vec<PathBuf>,
+}
+
+pub struct ScanJournal {
+    store: JournalStore,
+    recovered: HashMap<(u64, String), Vec<JournalGroup>>,
+}
+
+/// Where a [`ScanJournal`] is kept
+enum JournalStore {
+    /// An append-only file of JSON lines
+    File {
+        path: PathBuf,
+        file: Mutex<JournalFile>,
+    },
+    /// Rows of the cache's `scan_journal` table, under a key identifying the
+    /// scan as a [`JournalHeader`] would
+    Cache { cache: Arc<Cache>, scan: String },
+}
+
+impl ScanJournal {
+    /// Where the journal of a scan of `roots` is kept by default: a file in
+    /// the cache directory named after the resolved set of roots
+    pub fn default_path(roots: &[PathBuf]) -> Result<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
+            .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+
+        let cache_dir = project_dirs.cache_dir();
+        fs::create_dir_all(cache_dir)?;
+
+        let mut data = Vec::new();
+        for root in canonical_roots(roots) {
+            data.extend_from_slice(root.as_os_str().as_encoded_bytes());
+            data.push(0);
+        }
+        let key = utils::hash_bytes(&data);
+        Ok(cache_dir.join(format!("scan-journal-{}.jsonl", &key[..16])))
+    }
+
+    /// Start a new journal at `path`, replacing any previous one
+    pub fn create(path: &Path, roots: &[PathBuf], algorithm: HashAlgorithm) -> Result<Self> {
+        let mut file = File::create(path)
+            .with_context(|| format!("Failed to create scan journal {}", path.display()))?;
+        let header = JournalHeader {
+            version: JOURNAL_VERSION,
+            roots: canonical_roots(roots),
+            algorithm,
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        file.sync_data()?;
+
+        Ok(ScanJournal {
+            store: JournalStore::File {
+                path: path.to_path_buf(),
+                file: Mutex::new(JournalFile {
+                    file,
+                    last_sync: Instant::now(),
+                }),
+            },
+            recovered: HashMap::new(),
+        })
+    }
+
+    /// Journal into `cache`, under a key for these roots and algorithm. With
+    /// `resume`, what an interrupted scan journaled there is recovered;
+    /// otherwise it is discarded.
+    pub fn in_cache(
+        cache: Arc<Cache>,
+        roots: &[PathBuf],
+        algorithm: HashAlgorithm,
+        resume: bool,
+    ) -> Result<Self> {
+        let header = JournalHeader {
+            version: JOURNAL_VERSION,
+            roots: canonical_roots(roots),
+            algorithm,
+        };
+        let scan = utils::hash_bytes(serde_json::to_string(&header)?.as_bytes());
+
+        let mut recovered = HashMap::new();
+        if resume {
+            for (size, members, groups) in cache.scan_journal(&scan)? {
+                match serde_json::from_str(&groups) {
+                    Ok(groups) => {
+                        recovered.insert((size, members), groups);
+                    }
+                    Err(e) => debug!("Ignoring unreadable scan journal row: {}", e),
+                }
+            }
+        } else {
+            cache.clear_scan_journal(&scan)?;
+        }
+
+        Ok(ScanJournal {
+            store: JournalStore::Cache { cache, scan },
+            recovered,
+        })
+    }
+
+    /// Continue the journal at `path`, recovering what it holds if it was
+    /// written for the same roots and algorithm. Otherwise, or if there is no
+    /// journal, this starts a new one.
+    pub fn resume(path: &Path, roots: &[PathBuf], algorithm: HashAlgorithm) -> Result<Self> {
+        let Ok(file) = File::open(path) else {
+            return Self::create(path, roots, algorithm);
+        };
+        let mut lines = BufReader::new(file).lines();
+
+        let expected = JournalHeader {
+            version: JOURNAL_VERSION,
+            roots: canonical_roots(roots),
+            algorithm,
+        };
+        let header = lines
+            .next()
+            .transpose()?
+            .and_then(|line| serde_json::from_str::<JournalHeader>(&line).ok());
+        if header.as_ref() != Some(&expected) {
+            debug!("Scan journal belongs to another scan, starting over");
+            return Self::create(path, roots, algorithm);
+        }
+
+        let mut recovered = HashMap::new();
+        for line in lines {
+            match serde_json::from_str::<JournalEntry>(&line?) {
+                Ok(entry) => {
+                    recovered.insert((entry.size, entry.members), entry.groups);
+                }
+                Err(e) => {
+                    debug!("Ignoring torn scan journal line: {}", e);
+                    break;
+                }
+            }
+        }
+
+        // Rewrite without a torn tail so new lines start cleanly
+        let mut journal = Self::create(path, roots, algorithm)?;
+        if let JournalStore::File { file, .. } = &mut journal.store {
+            let file = &mut file.get_mut().unwrap().file;
+            for ((size, members), groups) in &recovered {
+                let entry = JournalEntryRef {
+                    size: *size,
+                    members,
+                    groups,
+                };
+                writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+            }
+            file.sync_data()?;
+        }
+        journal.recovered = recovered;
+
+        Ok(journal)
+    }
+
+    /// Number of size groups taken over from an earlier run
+    pub fn recovered_len(&self) -> usize {
+        self.recovered.len()
+    }
+
+    /// Duplicate groups journaled for this size group, if its members are
+    /// unchanged since they were recorded
+    pub fn recovered(
+        &self,
+        size: u64,
+        members: &str,
+        files: &[FileInfo],
+    ) -> Option<Vec<(String, Vec<FileInfo>)>> {
+        let groups = self.recovered.get(&(size, members.to_string()))?;
+        let by_path: HashMap<&Path, &FileInfo> = files
+            .iter()
+            .map(|file| (file.path.as_path(), file))
+            .collect();
+
+        Some(
+            groups
+                .iter()
+                .map(|group| {
+                    let files = group
+                        .paths
+                        .iter()
+                        .filter_map(|path| by_path.get(path.as_path()).map(|&file| file.clone()))
+                        .collect();
+                    (group.hash.clone(), files)
+                })
+                .collect(),
+        )
+    }
+
+    /// Append a finished size group. In a file, lines with duplicates are
+    /// synced to disk unless the journal was synced less than
+    /// [`SYNC_INTERVAL`] ago.
+    pub fn record(
+        &self,
+        size: u64,
+        members: &str,
+        groups: &[(String, Vec<FileInfo>)],
+    ) -> Result<()> {
+        let groups: Vec<JournalGroup> = groups
+            .iter()
+            .map(|(hash, files)| JournalGroup {
+                hash: hash.clone(),
+                paths: files.iter().map(|file| file.path.clone()).collect(),
+            })
+            .collect();
+        let file = match &self.store {
+            JournalStore::File { file, .. } => file,
+            JournalStore::Cache { cache, scan } => {
+                let groups = serde_json::to_string(&groups)?;
+                return Ok(cache.record_scan_journal(scan, size, members, &groups)?);
+            }
+        };
+        let line = serde_json::to_string(&JournalEntryRef {
+            size,
+            members,
+            groups: &groups,
+        })?;
+
+        let mut journal = file.lock().unwrap();
+        writeln!(journal.file, "{}", line)?;
+        if !groups.is_empty() && journal.last_sync.elapsed() >= SYNC_INTERVAL {
+            journal.file.sync_data()?;
+            journal.last_sync = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    /// The scan completed, so the journal is no longer needed
+    pub fn finish(self) -> Result<()> {
+        match self.store {
+            JournalStore::File { path, file } => {
+                drop(file);
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                    _ => Ok(()),
+                }
+            }
+            JournalStore::Cache { cache, scan } => Ok(cache.clear_scan_journal(&scan)?),
+        }
+    }
+}
+
+/// The open journal and when it was last synced to disk
+struct JournalFile {
+    file: File,
+    last_sync: Instant,
+}
+
+/// Borrowed form of [`JournalEntry`] for writing
+#[derive(Serialize)]
+struct JournalEntryRef<'a> {
+    size: u64,
+    members: &'a str,
+    groups: &'a [JournalGroup],
+}
+
+/// The set of `roots` a journal belongs to: resolved, so the same scan
+/// started from another directory matches, and sorted
+fn canonical_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = roots
+        .iter()
+        .map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Identifies the exact member set of a size group, including each file's
+/// modification time, so changed files are never taken from the journal
+pub fn fingerprint(files: &[FileInfo]) -> String {
+    let mut members: Vec<(&Path, Option<u128>)> = files
+        .iter()
+        .map(|file| {
+            let modified = file
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|age| age.as_nanos());
+            (file.path.as_path(), modified)
+        })
+        .collect();
+    members.sort();
+
+    let mut data = Vec::new();
+    for (path, modified) in members {
+        data.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        data.push(0);
+        data.extend_from_slice(&modified.unwrap_or(0).to_le_bytes());
+    }
+    utils::hash_bytes(&data)
+}
@@ -15,18 +15,33 @@
 //!
 //! Current version: 0.1.0
 
+pub mod burst;
 pub mod cache;
+pub mod cancel;
 pub mod cli;
+pub mod config;
+pub mod decisions;
+pub mod duplicates;
+pub mod error;
 pub mod file_info;
+pub mod ignore;
 pub mod interactive;
+pub mod journal;
+pub mod names;
+pub mod perceptual;
+pub mod report;
+pub mod report_db;
 pub mod scanner;
+mod spill;
 pub mod ui;
+pub mod undo;
 pub mod utils;
 
 pub use cache::Cache;
 pub use cli::Args;
+pub use error::DupCheckError;
 pub use file_info::FileInfo;
-pub use scanner::Scanner;
+pub use scanner::{Scanner, ScannerBuilder};
 
 #[cfg(test)]
 mod tests {
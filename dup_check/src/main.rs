@@ -11,53 +11,432 @@
 //! # Usage
 //! ```bash
 //! dupcheck [OPTIONS]
+//! dupcheck cache --export <FILE> | --import <FILE> | --verify | --clean | --clear
+//! dupcheck hash [--hash-algo <ALGO>] <FILE|->
 //!
 //! Options:
-//!   -p, --path <PATH>      Directory to scan (default: current directory)
-//!   -n, --min-size <SIZE>  Minimum file size (e.g., 1K, 1M)
-//!   -x, --max-size <SIZE>  Maximum file size (e.g., 1G)
+//!       --config <FILE>   Option defaults (default: <config dir>/dupcheck/config.toml)
+//!   -p, --path <PATH>      Directory to scan, repeatable (default: current directory)
+//!                          PATH:min=SIZE,max=SIZE filters sizes below PATH only
+//!       --exclude <GLOB>  Skip matching files and directories (repeatable)
+//!       --ext <EXT,...>   Only scan files with these extensions
+//!       --exclude-ext <EXT,...>  Skip files with these extensions
+//!       --include-hidden  Also scan hidden files
+//!       --max-depth <N>   Descend at most N levels (1: only the path itself)
+//!       --no-recursive    Don't descend into subdirectories
+//!       --newer-than <AGE>  Only scan files modified within AGE (e.g. 7d)
+//!       --older-than <AGE>  Only scan files modified more than AGE ago (e.g. 1y)
+//!       --symlinks <POLICY>  skip (default), follow or treat-as-file
+//!       --follow-junctions  Walk into Windows junctions and reparse points
+//!       --empty <MODE>    Empty files: group (default), ignore or separate
+//!   -n, --min-size <SIZE>  Minimum file size, inclusive (e.g., 1K, 1M)
+//!   -x, --max-size <SIZE>  Maximum file size, inclusive (e.g., 1G)
+//!       --size-exact <SIZE>  Only files of exactly this size
 //!   -c, --no-cache        Disable hash caching
+//!       --cache-pragma <NAME=VALUE>  Tune the cache database (repeatable)
+//!       --cache-path <PATH>  Cache database to use (or set DUPCHECK_CACHE)
+//!       --cache-max-age <AGE>  Evict cached hashes older than AGE (e.g. 30d)
+//!       --incremental     Only list directories that changed since the last scan
+//!       --hash-algo <ALGO>  sha256 (default), blake3 or xxhash
+//!       --keep <STRATEGY>  oldest, newest, shortest-path or first-alphabetical
+//!       --keep-path <PATH>  Always keep this exact file (repeatable)
+//!       --path-prefer <DIR>  Keep the copy under DIR when a group has one
+//!       --original-if-readonly  Never delete read-only files
+//!       --original-if-xattr <NAME>  Never delete files with this xattr
+//!       --text-case-insensitive  Also group text files differing only in case
+//!       --fail-on-duplicates  Exit with status 1 if duplicates exist (CI gating)
+//!       --yes, --non-interactive  Report without prompting, exit 0/1/2 (for cron)
+//!       --delete          With --yes, also delete the duplicates
+//!       --by <MODE>       Group by content (default), file name, or size and name
+//!       --quick           Group by size and name without hashing (unverified)
+//!       --ignore-case-names  Match names regardless of case with --by name/--quick
+//!       --compare-range <START:LEN>  Group by a byte range only
+//!       --burst <SECONDS>  Group burst photos taken within SECONDS
+//!       --burst-size-delta <SIZE>  Size tolerance within a burst (default: 1M)
+//!       --images          Group images that look alike (resized, re-encoded)
+//!       --threshold <BITS>  Hash bits similar images may differ in (default: 10)
+//!       --dedupe-names    Only find 'name (1).ext' / 'name copy.ext' copies
+//!       --force           Allow deleting partial (range) matches
+//!       --deletable-ext <EXT,...>  Only delete files with these extensions
+//!       --skip-locked     Skip files locked by other processes
+//!       --threads <N>     Hashing threads, 0 for all cores (default: 0)
+//!       --verify          Compare matching files byte by byte
+//!       --compare-xattrs  Also require matching extended attributes (Unix)
+//!       --mmap-threshold <SIZE>  Compare files this large via mmap, unhashed
+//!       --dry-run         Show what would be deleted without deleting
+//!       --trash           Move duplicates to the trash instead of deleting
+//!       --hardlink        Replace duplicates with hard links instead
+//!       --symlink         Replace duplicates with symbolic links instead
+//!       --preserve-link-times  Keep each replaced duplicate's times on its symlink
+//!       --undo            Restore the last batch moved to the trash
+//!       --verify-deletion  Confirm deleted files are really gone
+//!       --min-copies <N>  Only report files with at least N copies
+//!       --max-result-groups <N>  Keep only the N most wasteful groups
+//!       --bytes           Print sizes as exact byte counts
+//!       --summary-only    Print only the totals, not every group
+//!       --log-skipped     Count the files the filters left out, per reason
+//!       --no-color        Plain ASCII output (default when NO_COLOR is set or piped)
+//!       --format <FORMAT>  text (default), json or csv
+//!       --print0          Print only the paths to delete, NUL-terminated
+//!       --sort <KEY>      Group order: wasted (default), size, count or path
+//!   -o, --output <FILE>   Write the report to FILE instead of stdout
+//!       --resume          Journal the scan, continuing an interrupted one
+//!       --journal <FILE>  Journal the scan to FILE
+//!       --live            Print groups as they are found
+//!       --deepest <N>     List the N most deeply nested duplicates
+//!       --savings         Compare savings of delete, hard-link and symlink
+//!       --by-folder       Show wasted space per top-level folder
+//!       --export-db <FILE>  Write results to a SQLite database
+//!       --review          Decide per group; decisions persist across runs
+//!       --pick            Choose the files to delete in each group
+//!       --decisions <FILE>  Where review decisions are stored
+//!       --ignore-list <FILE>  Groups to hide from reports
 //! ```
 
 use anyhow::{Context, Result};
+use console::Term;
 use dialoguer::{theme::ColorfulTheme, Confirm};
-use dup_check::{cli, interactive, scanner::Scanner, ui};
+use dup_check::{
+    burst::BurstWindow,
+    cache::{Cache, VerifySummary},
+    cancel::{self, CancellationToken},
+    cli,
+    decisions::DecisionStore,
+    duplicates::KeepPolicy,
+    error::DupCheckError,
+    file_info::FileInfo,
+    ignore::IgnoreList,
+    interactive,
+    journal::ScanJournal,
+    report, report_db,
+    scanner::{GroupBy, ScanOptions, Scanner},
+    ui, undo, utils,
+};
+use globset::GlobSetBuilder;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 fn main() -> Result<()> {
     env_logger::init();
+
+    let args = cli::parse_args()?;
+    if args.yes {
+        // Scripts tell errors from found duplicates by the exit status
+        if let Err(e) = run(&args) {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+    run(&args)
+}
+
+fn run(args: &cli::Args) -> Result<()> {
     let theme = ColorfulTheme::default();
 
+    let markers = ui::Markers::detect(args.no_color);
+    if markers == ui::Markers::Ascii {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    let mark = |emoji| markers.pick(emoji, "");
+
+    if let Some(command) = &args.command {
+        return run_command(command, args, markers);
+    }
+    if args.undo {
+        return undo_last_deletion(markers);
+    }
+
+    let sizes = if args.bytes {
+        ui::SizeFormat::Bytes
+    } else {
+        ui::SizeFormat::Human
+    };
+    let display_options = ui::DisplayOptions {
+        sizes,
+        markers,
+        sort: args.sort,
+        summary_only: args.summary_only,
+    };
+
     // Get configuration either from CLI args or interactive mode
-    let mut config = if std::env::args().len() > 1 {
-        // Use CLI args if provided
-        let args = cli::parse_args();
+    let delete_options = ui::DeleteOptions {
+        force: args.force,
+        deletable_extensions: args.deletable_ext.clone(),
+        verify_deletion: args.verify_deletion,
+        skip_locked: args.skip_locked,
+        trash: args.trash,
+        dry_run: args.dry_run,
+        size_format: sizes,
+        markers,
+        undo_dir: Some(undo::default_dir()?),
+    };
+    let link = if args.hardlink {
+        Some(ui::LinkKind::Hard)
+    } else if args.symlink {
+        Some(ui::LinkKind::Symbolic)
+    } else {
+        None
+    };
+
+    if args.delete && args.format != cli::OutputFormat::Text && args.output.is_none() {
+        // The cleanup log would land in the middle of the JSON or CSV document
+        anyhow::bail!("--delete with --format json or csv needs --output");
+    }
+
+    let mut config = if std::env::args().len() > 1 || args.config_loaded {
+        // Use CLI args, or the config file's, if provided
         interactive::InteractiveConfig {
-            path: args.path,
-            min_size: args.min_size,
-            max_size: args.max_size,
+            roots: args.paths.clone(),
+            min_size: args.size_exact.or(args.min_size),
+            max_size: args.size_exact.or(args.max_size),
             use_cache: !args.no_cache,
         }
     } else {
         // Use interactive mode if no args provided
-        interactive::get_interactive_config()?
+        interactive::get_interactive_config(markers, sizes)?
     };
 
     loop {
-        let scanner = Scanner::new(config.use_cache, config.min_size, config.max_size)
+        // Ctrl-C stops this scan only; at the prompts after it, it exits
+        let ctrlc = cancel::CtrlC::catch()?;
+        let scanner = build_scanner(&config, args, markers, ctrlc.token())
             .context("Failed to initialize scanner")?;
 
-        let duplicates = scanner
-            .find_duplicates(config.path.as_path())
-            .context("Failed to scan for duplicates")?;
+        let scanned = scanner.find_duplicates_in_roots(&config.roots, |_, files| {
+            // Live output would corrupt the JSON or CSV document on stdout
+            if args.live && args.format == cli::OutputFormat::Text {
+                ui::display_live_group(files, sizes, markers);
+            }
+        });
+        drop(ctrlc);
+        let mut duplicates = match scanned {
+            Err(DupCheckError::Cancelled) => {
+                eprintln!(
+                    "{}Scan cancelled; hashes so far were saved. Use --resume to continue",
+                    mark("⏹️  ")
+                );
+                std::process::exit(130);
+            }
+            scanned => scanned.context("Failed to scan for duplicates")?,
+        };
+        if scanner.dropped_groups() > 0 {
+            eprintln!(
+                "{}Results capped: {} smaller groups were left out",
+                markers.pick("⚠️  ", "Warning: "),
+                scanner.dropped_groups()
+            );
+        }
+        let stats = scanner.stats(&duplicates);
+        if stats.cache_hits + stats.cache_misses > 0 {
+            eprintln!(
+                "{}Cache: {} hits, {} misses ({:.0}% saved IO)",
+                mark("💾 "),
+                stats.cache_hits,
+                stats.cache_misses,
+                stats.cache_saved_io_percent()
+            );
+        }
+        eprintln!(
+            "{}Processed {} files / {} in {:.1}s ({}/s hashed)",
+            mark("⏱️  "),
+            stats.files_scanned,
+            sizes.format(stats.bytes_scanned),
+            stats.elapsed.as_secs_f64(),
+            sizes.format(stats.throughput() as u64)
+        );
+        if args.incremental {
+            eprintln!(
+                "{}Directories: {} listed, {} unchanged since the last scan",
+                mark("📂 "),
+                stats.dirs_listed,
+                stats.dirs_unchanged
+            );
+        }
+        if args.log_skipped {
+            let counts: Vec<String> = stats
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", count, reason.label()))
+                .collect();
+            if counts.is_empty() {
+                eprintln!("{}Skipped: none", mark("🚫 "));
+            } else {
+                eprintln!("{}Skipped: {}", mark("🚫 "), counts.join(", "));
+            }
+        }
+        for error in scanner.scan_errors() {
+            eprintln!(
+                "{}Skipped (unreadable): {}: {}",
+                markers.pick("⚠️  ", "Warning: "),
+                error.path.display(),
+                error.message
+            );
+        }
+        for path in scanner.locked_files() {
+            eprintln!(
+                "{}Skipped (in use by another process): {}",
+                mark("🔒 "),
+                path.display()
+            );
+        }
 
-        ui::display_duplicates(&duplicates);
+        let ignore_path = match &args.ignore_list {
+            Some(path) => path.clone(),
+            None => IgnoreList::default_path()?,
+        };
+        let mut ignored = IgnoreList::load(&ignore_path)?;
+        let hidden = ignored.filter(&mut duplicates);
+        if hidden > 0 {
+            eprintln!("{}{} ignored group(s) not shown", mark("🙈 "), hidden);
+        }
+
+        let keep_policy = KeepPolicy {
+            keep_paths: args.keep_paths.clone(),
+            original_if_readonly: args.original_if_readonly,
+            original_if_xattr: args.original_if_xattr.clone(),
+            strategy: args.keep,
+            prefer_dir: args.path_prefer.clone(),
+        };
+        for conflict in keep_policy.apply(&mut duplicates) {
+            eprintln!(
+                "{}Several --keep-path files share a group; keeping {}",
+                markers.pick("⚠️  ", "Warning: "),
+                conflict.kept.display()
+            );
+            for path in &conflict.also_listed {
+                eprintln!("    also listed: {}", path.display());
+            }
+        }
 
-        if !duplicates.is_empty() {
-            println!("\nWould you like to delete duplicate files? (y/n)");
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input)?;
-            if input.trim().eq_ignore_ascii_case("y") {
-                ui::delete_duplicates(&duplicates).context("Failed to delete duplicates")?;
+        if args.print0 || args.format != cli::OutputFormat::Text {
+            let mut report = open_report(args)?;
+            if args.print0 {
+                ui::write_deletable_paths0(&duplicates, args.sort, &delete_options, &mut report)
+            } else if args.format == cli::OutputFormat::Csv {
+                ui::write_duplicates_csv(&duplicates, args.sort, &mut report)
+            } else {
+                ui::write_duplicates_json(&duplicates, args.sort, &mut report)
+            }
+            .and_then(|()| Ok(report.flush()?))
+            .context("Failed to write results")?;
+            if let Some(path) = &args.export_db {
+                let count = report_db::export(&duplicates, path, &delete_options)
+                    .context("Failed to export results")?;
+                eprintln!(
+                    "{}Exported {} files to {}",
+                    mark("🗄️  "),
+                    count,
+                    path.display()
+                );
+            }
+            if (args.fail_on_duplicates || args.yes) && !duplicates.is_empty() {
+                if args.delete {
+                    clean_up(&duplicates, &delete_options, link, args.preserve_link_times)?;
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+
+        // A report file never gets emoji, whatever the terminal supports
+        let report_markers = if args.output.is_some() {
+            ui::Markers::Ascii
+        } else {
+            if !args.yes && markers == ui::Markers::Emoji {
+                let _ = Term::stdout().clear_screen();
+            }
+            markers
+        };
+        let options = ui::DisplayOptions {
+            markers: report_markers,
+            ..display_options
+        };
+        let mut report = open_report(args)?;
+        ui::display_duplicates(&duplicates, &options, &mut report)
+            .and_then(|()| {
+                if let Some(limit) = args.deepest {
+                    let nested = report::deepest_duplicates(&duplicates);
+                    ui::display_deepest(&nested, limit, report_markers, &mut report)?;
+                }
+                if args.savings && !duplicates.is_empty() {
+                    let savings = report::savings_by_action(&duplicates);
+                    ui::display_savings(&savings, sizes, report_markers, &mut report)?;
+                }
+                if args.by_folder {
+                    // Folders are reported relative to the first scanned path
+                    let waste =
+                        report::waste_by_top_level_folder(&duplicates, &config.roots[0].path);
+                    ui::display_folder_waste(&waste, sizes, report_markers, &mut report)?;
+                }
+                report.flush()
+            })
+            .context("Failed to write results")?;
+        drop(report);
+        if let Some(path) = &args.output {
+            println!("{}Report written to {}", mark("📝 "), path.display());
+        }
+        if let Some(path) = &args.export_db {
+            let count = report_db::export(&duplicates, path, &delete_options)
+                .context("Failed to export results")?;
+            println!(
+                "{}Exported {} files to {}",
+                mark("🗄️  "),
+                count,
+                path.display()
+            );
+        }
+
+        if args.fail_on_duplicates {
+            if duplicates.is_empty() {
+                return Ok(());
+            }
+            eprintln!(
+                "\n{}{} duplicate group(s) found; failing as requested",
+                mark("❌ "),
+                duplicates.len()
+            );
+            std::process::exit(1);
+        }
+
+        if args.yes {
+            if !duplicates.is_empty() {
+                if args.delete {
+                    clean_up(&duplicates, &delete_options, link, args.preserve_link_times)?;
+                }
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+
+        if !duplicates.is_empty() && args.review {
+            let decisions_path = match &args.decisions {
+                Some(path) => path.clone(),
+                None => DecisionStore::default_path()?,
+            };
+            let mut store = DecisionStore::load(&decisions_path)?;
+            let to_delete =
+                interactive::review_groups(&duplicates, &mut store, &mut ignored, markers, sizes)?;
+            if !to_delete.is_empty() {
+                clean_up(&to_delete, &delete_options, link, args.preserve_link_times)?;
+            }
+        } else if !duplicates.is_empty() && args.pick {
+            let to_delete = interactive::select_deletions(&duplicates, markers, sizes)?;
+            if !to_delete.is_empty() {
+                clean_up(&to_delete, &delete_options, link, args.preserve_link_times)?;
+            }
+        } else if !duplicates.is_empty() {
+            let to_delete =
+                interactive::confirm_deletions(&duplicates, &delete_options, args.sort, link)?;
+            if !to_delete.is_empty() {
+                clean_up(&to_delete, &delete_options, link, args.preserve_link_times)?;
             }
         } else {
             println!("\nNo duplicates found!");
@@ -74,8 +453,274 @@ fn main() -> Result<()> {
         }
 
         // Get new configuration for next scan
-        config = interactive::get_interactive_config()?;
+        config = interactive::get_interactive_config(markers, sizes)?;
     }
 
     Ok(())
 }
+
+fn run_command(command: &cli::Command, args: &cli::Args, markers: ui::Markers) -> Result<()> {
+    let mark = |emoji| markers.pick(emoji, "");
+    match command {
+        cli::Command::Cache(cache_args) => {
+            let cache = open_cache(args)?;
+            if let Some(path) = &cache_args.export {
+                let count = cache.export_to(path)?;
+                println!(
+                    "{}Exported {} cache entries to {}",
+                    mark("📦 "),
+                    count,
+                    path.display()
+                );
+            }
+            if let Some(path) = &cache_args.import {
+                let summary = cache.import_from(path)?;
+                println!(
+                    "{}Imported cache entries from {}",
+                    mark("📥 "),
+                    path.display()
+                );
+                println!("   {} new", summary.inserted);
+                println!("   {} already present", summary.unchanged);
+                println!("   {} conflicting (local entry kept)", summary.conflicts);
+            }
+            if cache_args.clear {
+                let count = cache.clear_all()?;
+                println!("{}Removed {} cache entries", mark("🧹 "), count);
+            }
+            if cache_args.verify {
+                let summary = with_maintenance_progress(|cancel, on_progress| {
+                    cache.verify(cancel, on_progress)
+                })?;
+                if summary.cancelled {
+                    println!(
+                        "{}Verification cancelled; progress so far was saved",
+                        mark("⏹️  ")
+                    );
+                }
+                println!(
+                    "{}Checked {} cache entries, removed {} stale",
+                    mark("🧹 "),
+                    summary.checked,
+                    summary.removed
+                );
+            }
+            if cache_args.clean {
+                let summary = with_maintenance_progress(|cancel, on_progress| {
+                    cache.clean_old_entries(cancel, on_progress)
+                })?;
+                if summary.cancelled {
+                    println!(
+                        "{}Cleaning cancelled; progress so far was saved",
+                        mark("⏹️  ")
+                    );
+                }
+                println!(
+                    "{}Checked {} cache entries, removed {} for missing files",
+                    mark("🧹 "),
+                    summary.checked,
+                    summary.removed
+                );
+            }
+        }
+        cli::Command::Hash(hash_args) => {
+            let hash = if hash_args.file.as_os_str() == "-" {
+                utils::hash_reader(std::io::stdin().lock(), hash_args.hash_algo)?
+            } else {
+                let file = std::fs::File::open(&hash_args.file)
+                    .with_context(|| format!("Failed to open {}", hash_args.file.display()))?;
+                utils::hash_reader(file, hash_args.hash_algo)?
+            };
+            println!("{}  {}", hash, hash_args.file.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a cache maintenance pass with a progress bar, stopping it on Ctrl-C
+fn with_maintenance_progress(
+    run: impl FnOnce(
+        &CancellationToken,
+        &mut dyn FnMut(u64, u64),
+    ) -> Result<VerifySummary, DupCheckError>,
+) -> Result<VerifySummary> {
+    let ctrlc = cancel::CtrlC::catch()?;
+    let progress = ProgressBar::new(0);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40}] {pos}/{len} entries ({eta})")
+            .unwrap(),
+    );
+    let summary = run(&ctrlc.token(), &mut |checked, total| {
+        progress.set_length(total);
+        progress.set_position(checked);
+    })?;
+    progress.finish_and_clear();
+    Ok(summary)
+}
+
+fn undo_last_deletion(markers: ui::Markers) -> Result<()> {
+    let mark = |emoji| markers.pick(emoji, "");
+    let summary = undo::undo_latest(&undo::default_dir()?)?;
+    for path in &summary.restored {
+        println!("{}Restored: {}", mark("↩️  "), path.display());
+    }
+    for path in &summary.missing {
+        println!("{}No longer in the trash: {}", mark("❓ "), path.display());
+    }
+    for (path, reason) in &summary.failed {
+        println!(
+            "{}Could not restore {} ({})",
+            mark("❌ "),
+            path.display(),
+            reason
+        );
+    }
+    println!(
+        "\n{}Restored {} files",
+        mark("♻️  "),
+        summary.restored.len()
+    );
+
+    if !summary.failed.is_empty() {
+        anyhow::bail!(
+            "{} files could not be restored; run --undo again once they're out of the way",
+            summary.failed.len()
+        );
+    }
+    Ok(())
+}
+
+/// Where the report goes: the `--output` file, or stdout
+fn open_report(args: &cli::Args) -> Result<Box<dyn Write>> {
+    Ok(match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            Box::new(BufWriter::new(file))
+        }
+        None => Box::new(std::io::stdout().lock()),
+    })
+}
+
+/// Delete the duplicates, or with `link` replace them with links of that kind
+fn clean_up(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &ui::DeleteOptions,
+    link: Option<ui::LinkKind>,
+    preserve_link_times: bool,
+) -> Result<()> {
+    match link {
+        Some(kind) => ui::link_duplicates(duplicates, options, kind, preserve_link_times)
+            .context("Failed to link duplicates"),
+        None => ui::delete_duplicates(duplicates, options).context("Failed to delete duplicates"),
+    }
+}
+
+/// The time `age` ago
+fn cutoff(age: Duration) -> SystemTime {
+    SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH)
+}
+
+/// Open the cache at `--cache-path`, or at [`Cache::default_path`]
+fn open_cache(args: &cli::Args) -> Result<Cache> {
+    let path = match &args.cache_path {
+        Some(path) => path.clone(),
+        None => Cache::default_path()?,
+    };
+    Ok(Cache::open(&path, &args.cache_pragmas)?)
+}
+
+fn build_scanner(
+    config: &interactive::InteractiveConfig,
+    args: &cli::Args,
+    markers: ui::Markers,
+    cancel: CancellationToken,
+) -> Result<Scanner> {
+    if args.ignore_case_names && !args.quick && args.group_by == GroupBy::Content {
+        // Content groups don't compare names, so the option would do nothing
+        anyhow::bail!("--ignore-case-names needs --by name or --quick");
+    }
+
+    let cache = if config.use_cache {
+        let cache = open_cache(args)?;
+        if let Some(max_age) = args.cache_max_age {
+            let evicted = cache.evict_older_than(max_age)?;
+            if evicted > 0 {
+                eprintln!(
+                    "{}Evicted {} expired cache entries",
+                    markers.pick("🧹 ", ""),
+                    evicted
+                );
+            }
+        }
+        Some(Arc::new(cache))
+    } else {
+        None
+    };
+
+    // Only scans that may have to be resumed keep a journal, in the cache
+    // unless there is none
+    let journal = match (&args.journal, args.resume) {
+        (Some(path), _) => Some(path.clone()),
+        (None, true) if cache.is_none() => {
+            let roots: Vec<_> = config.roots.iter().map(|root| root.path.clone()).collect();
+            Some(ScanJournal::default_path(&roots)?)
+        }
+        (None, _) => None,
+    };
+
+    let mut exclude = GlobSetBuilder::new();
+    for glob in &args.exclude {
+        exclude.add(glob.clone());
+    }
+
+    let options = ScanOptions {
+        min_size: config.min_size,
+        max_size: config.max_size,
+        text_case_insensitive: args.text_case_insensitive,
+        compare_range: args.compare_range,
+        dedupe_names: args.dedupe_names,
+        skip_locked: args.skip_locked,
+        hash_algorithm: args.hash_algo,
+        max_result_groups: args.max_result_groups,
+        journal,
+        resume: args.resume,
+        exclude: exclude.build()?,
+        include_hidden: args.include_hidden,
+        verify: args.verify,
+        compare_xattrs: args.compare_xattrs,
+        incremental: args.incremental,
+        cancel,
+        threads: args.threads,
+        min_copies: args.min_copies,
+        symlinks: args.symlinks,
+        follow_junctions: args.follow_junctions,
+        max_depth: if args.no_recursive {
+            Some(1)
+        } else {
+            args.max_depth
+        },
+        newer_than: args.newer_than.map(cutoff),
+        older_than: args.older_than.map(cutoff),
+        mmap_threshold: args.mmap_threshold,
+        extensions: args.extensions.clone(),
+        exclude_extensions: args.exclude_ext.clone(),
+        empty_files: args.empty_files,
+        group_by: if args.quick {
+            GroupBy::Quick
+        } else {
+            args.group_by
+        },
+        ignore_case_names: args.ignore_case_names,
+        similar_images: args.images.then_some(args.threshold),
+        burst: args.burst.map(|seconds| BurstWindow {
+            seconds,
+            size_delta: args.burst_size_delta,
+        }),
+        ..Default::default()
+    };
+
+    Ok(Scanner::with_options(cache, options))
+}
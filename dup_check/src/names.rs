@@ -0,0 +1,118 @@
+//! Recognizing the names browsers and file managers give to repeated copies,
+//! such as `photo (1).jpg`, `photo copy.jpg` or `photo - Copy (2).jpg`, and
+//! grouping files by name (and size) alone.
+
+use crate::file_info::{FileInfo, MatchKind};
+use std::{collections::HashMap, path::Path};
+
+/// The stem without a copy suffix, or `None` if it has none.
+///
+/// Understands ` (N)`, ` copy`, ` copy N`, ` - Copy` and ` - Copy (N)`,
+/// ignoring the case of "copy".
+pub fn strip_copy_suffix(stem: &str) -> Option<&str> {
+    let mut base = stem;
+
+    if let Some((before, number)) = base
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        if is_number(number) {
+            base = before;
+        }
+    } else if let Some((before, number)) = base.rsplit_once(' ') {
+        if is_number(number) && ends_with_ignore_case(before, " copy") {
+            base = before;
+        }
+    }
+
+    for suffix in [" - copy", " copy"] {
+        if ends_with_ignore_case(base, suffix) {
+            base = &base[..base.len() - suffix.len()];
+            break;
+        }
+    }
+
+    (base != stem && !base.is_empty()).then_some(base)
+}
+
+/// File name with any copy suffix removed, and whether one was present
+pub fn original_name(path: &Path) -> Option<(String, bool)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (base, suffixed) = match strip_copy_suffix(stem) {
+        Some(base) => (base, true),
+        None => (stem, false),
+    };
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}", base, ext.to_string_lossy()),
+        None => base.to_string(),
+    };
+    Some((name, suffixed))
+}
+
+/// Group files that have the same file name, in whatever directory and
+/// whatever their content. Groups are keyed `name:<file name>` and their
+/// files ordered by path. With `ignore_case`, names are compared by
+/// [`fold_name`] and the key holds the folded name.
+pub fn same_name_groups(files: Vec<FileInfo>, ignore_case: bool) -> HashMap<String, Vec<FileInfo>> {
+    group_by_name(files, MatchKind::SameName, ignore_case, |name, _| {
+        format!("name:{}", name)
+    })
+}
+
+/// Group files that have the same size and file name, without reading
+/// them. Groups are keyed `quick:<size>:<file name>`, flagged as
+/// [`MatchKind::Unverified`] and their files ordered by path. `ignore_case`
+/// works as for [`same_name_groups`].
+pub fn same_size_and_name_groups(
+    files: Vec<FileInfo>,
+    ignore_case: bool,
+) -> HashMap<String, Vec<FileInfo>> {
+    group_by_name(files, MatchKind::Unverified, ignore_case, |name, size| {
+        format!("quick:{}:{}", size, name)
+    })
+}
+
+/// `name` with case differences folded away, for comparing names the way
+/// case-insensitive filesystems do. Goes through upper case first so that
+/// full Unicode mappings apply, e.g. `Straße` and `STRASSE` fold alike.
+pub fn fold_name(name: &str) -> String {
+    name.to_uppercase().to_lowercase()
+}
+
+fn group_by_name(
+    files: Vec<FileInfo>,
+    match_kind: MatchKind,
+    ignore_case: bool,
+    key: impl Fn(&str, u64) -> String,
+) -> HashMap<String, Vec<FileInfo>> {
+    let mut groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    for mut file in files {
+        let Some(name) = file.path.file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        let key = if ignore_case {
+            key(&fold_name(&name), file.size)
+        } else {
+            key(&name, file.size)
+        };
+        file.match_kind = match_kind;
+        groups.entry(key).or_default().push(file);
+    }
+
+    groups.retain(|_, files| files.len() > 1);
+    for files in groups.values_mut() {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    groups
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn ends_with_ignore_case(s: &str, suffix: &str) -> bool {
+    s.len() >= suffix.len()
+        && s.is_char_boundary(s.len() - suffix.len())
+        && s[s.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+}
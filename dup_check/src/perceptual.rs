@@ -0,0 +1,113 @@
+//! Near-duplicate image detection through perceptual hashes.
+//!
+//! Re-encoded or resized copies of a photo share no bytes with the original,
+//! so content hashing never pairs them. Here every image is reduced to a
+//! 64-bit difference hash (dHash) that only depends on how brightness changes
+//! across the picture, and images whose hashes differ in at most `threshold`
+//! bits are grouped as similar. The groups are a suggestion: they are only
+//! deleted with `--force`.
+
+use crate::file_info::{FileInfo, MatchKind};
+use anyhow::Result;
+use image::imageops::FilterType;
+use log::debug;
+use rayon::prelude::*;
+use std::{collections::HashMap, path::Path};
+
+/// Hamming distance used when none is given
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// Extensions of the image formats that can be decoded
+const DECODABLE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp"];
+
+fn is_decodable(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| DECODABLE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Difference hash of the image at `path`: the picture is shrunk to 9x8
+/// grayscale pixels, and each bit says whether a pixel is brighter than its
+/// right neighbour
+pub fn dhash(path: &Path) -> Result<u64> {
+    let image = image::open(path)?
+        .resize_exact(9, 8, FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = image.get_pixel(x, y)[0] > image.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    Ok(hash)
+}
+
+/// Number of bits in which two perceptual hashes differ
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Group images whose perceptual hashes are within `threshold` bits of each
+/// other. Similarity is transitive here: two images land in one group when a
+/// chain of similar images links them. The largest file of each group comes
+/// first, so the best quality copy is kept by default.
+///
+/// Files in other formats, and images that fail to decode, are left out.
+pub fn similar_groups(files: Vec<FileInfo>, threshold: u32) -> HashMap<String, Vec<FileInfo>> {
+    let hashed: Vec<(u64, FileInfo)> = files
+        .into_par_iter()
+        .filter(|file| is_decodable(&file.path))
+        .filter_map(|mut file| match dhash(&file.path) {
+            Ok(hash) => {
+                file.match_kind = MatchKind::SimilarImage;
+                Some((hash, file))
+            }
+            Err(e) => {
+                debug!("Skipping {}: {}", file.path.display(), e);
+                None
+            }
+        })
+        .collect();
+
+    // Union-find over all pairs within the threshold
+    let mut parent: Vec<usize> = (0..hashed.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if distance(hashed[i].0, hashed[j].0) <= threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<(u64, FileInfo)>> = HashMap::new();
+    for (i, entry) in hashed.into_iter().enumerate() {
+        clusters
+            .entry(root(&mut parent, i))
+            .or_default()
+            .push(entry);
+    }
+
+    clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by(|a, b| {
+                b.1.size
+                    .cmp(&a.1.size)
+                    .then_with(|| a.1.path.cmp(&b.1.path))
+            });
+            let key = format!("similar:{:016x}", members[0].0);
+            (key, members.into_iter().map(|(_, file)| file).collect())
+        })
+        .collect()
+}
@@ -0,0 +1,267 @@
+//! Analytical views computed from scan results
+
+use crate::{duplicates, file_info::FileInfo};
+use clap::ValueEnum;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Order of the groups in a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SortKey {
+    /// Most reclaimable space first
+    #[default]
+    Wasted,
+    /// Largest files first
+    Size,
+    /// Most copies first
+    Count,
+    /// Alphabetically by the path of the kept file
+    Path,
+}
+
+/// Groups in the order `key` gives. Ties are broken by the kept file's path,
+/// then by hash, so reports from identical scans can be diffed.
+pub fn sorted_groups(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    key: SortKey,
+) -> Vec<(&String, &Vec<FileInfo>)> {
+    let wasted = |files: &[FileInfo]| duplicates::wasted_bytes(files);
+
+    let mut groups: Vec<_> = duplicates.iter().collect();
+    groups.sort_by(|(a_hash, a), (b_hash, b)| {
+        let order = match key {
+            SortKey::Wasted => wasted(b).cmp(&wasted(a)),
+            SortKey::Size => b[0].size.cmp(&a[0].size),
+            SortKey::Count => b.len().cmp(&a.len()),
+            SortKey::Path => std::cmp::Ordering::Equal,
+        };
+        order
+            .then_with(|| a[0].path.cmp(&b[0].path))
+            .then_with(|| a_hash.cmp(b_hash))
+    });
+    groups
+}
+
+/// A duplicate file annotated with how deeply it is nested
+#[derive(Debug, Clone)]
+pub struct NestedDuplicate<'a> {
+    pub file: &'a FileInfo,
+    /// Number of path components
+    pub depth: usize,
+    /// How many levels deeper this copy sits than the shallowest copy in its group
+    pub levels_below_shallowest: usize,
+}
+
+/// List every duplicate file ordered from most to least nested.
+///
+/// Deeply buried copies are the ones most worth consolidating upward, so they
+/// come first. Ties are broken by path to keep the output stable.
+pub fn deepest_duplicates(duplicates: &HashMap<String, Vec<FileInfo>>) -> Vec<NestedDuplicate<'_>> {
+    let mut nested: Vec<NestedDuplicate> = duplicates
+        .values()
+        .flat_map(|files| {
+            let shallowest = files
+                .iter()
+                .map(|file| file.path.components().count())
+                .min()
+                .unwrap_or(0);
+
+            files.iter().map(move |file| {
+                let depth = file.path.components().count();
+                NestedDuplicate {
+                    file,
+                    depth,
+                    levels_below_shallowest: depth - shallowest,
+                }
+            })
+        })
+        .collect();
+
+    nested.sort_by(|a, b| {
+        b.depth
+            .cmp(&a.depth)
+            .then_with(|| a.file.path.cmp(&b.file.path))
+    });
+
+    nested
+}
+
+/// Ways of reclaiming the space taken by duplicate copies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavingsAction {
+    Delete,
+    HardLink,
+    Symlink,
+}
+
+impl SavingsAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            SavingsAction::Delete => "delete",
+            SavingsAction::HardLink => "hard-link",
+            SavingsAction::Symlink => "symlink",
+        }
+    }
+
+    /// What the user gives up by choosing this action
+    pub fn caveat(self) -> &'static str {
+        match self {
+            SavingsAction::Delete => "copies are gone; paths that referred to them break",
+            SavingsAction::HardLink => {
+                "only within one filesystem; editing any copy changes all of them"
+            }
+            SavingsAction::Symlink => "links break if the original is moved or deleted",
+        }
+    }
+}
+
+/// Space one action would reclaim across all exact duplicate groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionSavings {
+    pub action: SavingsAction,
+    pub reclaimable_bytes: u64,
+    /// Copies the action could be applied to
+    pub eligible_files: usize,
+    /// Copies the action can't handle, e.g. hard links across filesystems
+    pub blocked_files: usize,
+}
+
+/// Compare how much space deleting, hard-linking or symlinking the
+/// duplicates would reclaim.
+///
+/// The first file of each group is the original that stays. A symlink still
+/// occupies its target path, so it reclaims slightly less than a deletion.
+/// Groups that aren't full-content matches are left out.
+pub fn savings_by_action(duplicates: &HashMap<String, Vec<FileInfo>>) -> Vec<ActionSavings> {
+    let mut delete = ActionSavings::empty(SavingsAction::Delete);
+    let mut hard_link = ActionSavings::empty(SavingsAction::HardLink);
+    let mut symlink = ActionSavings::empty(SavingsAction::Symlink);
+
+    for files in duplicates.values() {
+        let Some((original, copies)) = files.split_first() else {
+            continue;
+        };
+        if !original.match_kind.is_exact() {
+            continue;
+        }
+
+        let original_device = device_id(&original.path);
+        let link_size = original.path.as_os_str().len() as u64;
+
+        for copy in copies {
+            delete.add(copy.size);
+            symlink.add(copy.size.saturating_sub(link_size));
+
+            match (original_device, device_id(&copy.path)) {
+                (Some(a), Some(b)) if a != b => hard_link.blocked_files += 1,
+                _ => hard_link.add(copy.size),
+            }
+        }
+    }
+
+    vec![delete, hard_link, symlink]
+}
+
+impl ActionSavings {
+    fn empty(action: SavingsAction) -> Self {
+        ActionSavings {
+            action,
+            reclaimable_bytes: 0,
+            eligible_files: 0,
+            blocked_files: 0,
+        }
+    }
+
+    fn add(&mut self, bytes: u64) {
+        self.reclaimable_bytes += bytes;
+        self.eligible_files += 1;
+    }
+}
+
+/// Identifier of the filesystem a file lives on, where the platform exposes one
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Where the wasted space of a duplicate group is attributed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FolderBucket {
+    /// All copies live under this top-level folder of the scan root
+    Folder(PathBuf),
+    /// All copies sit directly in the scan root
+    Root,
+    /// Copies are spread over several top-level folders
+    CrossFolder,
+}
+
+/// Wasted space attributed to one [`FolderBucket`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderWaste {
+    pub bucket: FolderBucket,
+    /// Bytes taken by copies beyond the original
+    pub wasted_bytes: u64,
+    pub groups: usize,
+}
+
+/// Break wasted space down by top-level folder under `root`, largest first.
+///
+/// A group is attributed to the first path component its members share
+/// relative to `root`; groups spanning several folders go to
+/// [`FolderBucket::CrossFolder`]. Only exact duplicate groups count.
+pub fn waste_by_top_level_folder(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    root: &Path,
+) -> Vec<FolderWaste> {
+    let mut buckets: HashMap<FolderBucket, FolderWaste> = HashMap::new();
+
+    for files in duplicates.values() {
+        if files.len() < 2 || !files[0].match_kind.is_exact() {
+            continue;
+        }
+
+        let mut members = files.iter().map(|file| top_level_bucket(&file.path, root));
+        let first = members.next().unwrap();
+        let bucket = if members.all(|bucket| bucket == first) {
+            first
+        } else {
+            FolderBucket::CrossFolder
+        };
+
+        let waste = buckets
+            .entry(bucket.clone())
+            .or_insert_with(|| FolderWaste {
+                bucket,
+                wasted_bytes: 0,
+                groups: 0,
+            });
+        waste.wasted_bytes += duplicates::wasted_bytes(files);
+        waste.groups += 1;
+    }
+
+    let mut waste: Vec<FolderWaste> = buckets.into_values().collect();
+    waste.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.bucket.cmp(&b.bucket))
+    });
+    waste
+}
+
+fn top_level_bucket(path: &Path, root: &Path) -> FolderBucket {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(folder), Some(_)) => FolderBucket::Folder(PathBuf::from(folder.as_os_str())),
+        _ => FolderBucket::Root,
+    }
+}
@@ -0,0 +1,118 @@
+//! Scan results exported to a standalone SQLite database for ad-hoc SQL.
+//!
+//! The schema is independent of the hash cache:
+//!
+//! ```sql
+//! -- One row per duplicate group
+//! CREATE TABLE groups (
+//!     id                INTEGER PRIMARY KEY,
+//!     hash              TEXT NOT NULL UNIQUE, -- group key, usually the content hash
+//!     match_kind        TEXT NOT NULL,        -- exact, case-insensitive-text, byte-range, burst
+//!     file_count        INTEGER NOT NULL,
+//!     reclaimable_bytes INTEGER NOT NULL      -- bytes freed by the planned deletions
+//! );
+//!
+//! -- One row per file in a group
+//! CREATE TABLE files (
+//!     id          INTEGER PRIMARY KEY,
+//!     group_id    INTEGER NOT NULL REFERENCES groups(id),
+//!     path        TEXT NOT NULL,
+//!     size        INTEGER NOT NULL,
+//!     is_original INTEGER NOT NULL,           -- 1 for the copy that is kept as original
+//!     action      TEXT NOT NULL               -- 'keep' or 'delete'
+//! );
+//! ```
+//!
+//! The `action` column reflects what a deletion with the same options would do.
+
+use crate::{file_info::FileInfo, ui::DeleteOptions};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::{collections::HashMap, fs, path::Path};
+
+const SCHEMA: &str = "
+    CREATE TABLE groups (
+        id INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL UNIQUE,
+        match_kind TEXT NOT NULL,
+        file_count INTEGER NOT NULL,
+        reclaimable_bytes INTEGER NOT NULL
+    );
+    CREATE TABLE files (
+        id INTEGER PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES groups(id),
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        is_original INTEGER NOT NULL,
+        action TEXT NOT NULL CHECK (action IN ('keep', 'delete'))
+    );
+    CREATE INDEX files_group_id ON files (group_id);
+";
+
+/// Write `duplicates` to a new database at `path`, replacing any existing file.
+/// Returns the number of files written.
+pub fn export(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    path: &Path,
+    options: &DeleteOptions,
+) -> Result<usize> {
+    if path.exists() {
+        fs::remove_file(path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    }
+    let mut conn = Connection::open(path)
+        .with_context(|| format!("Failed to create report database {}", path.display()))?;
+    conn.execute_batch(SCHEMA)?;
+
+    let mut hashes: Vec<&String> = duplicates.keys().collect();
+    hashes.sort();
+
+    let tx = conn.transaction()?;
+    let mut written = 0;
+    {
+        let mut insert_group = tx.prepare(
+            "INSERT INTO groups (hash, match_kind, file_count, reclaimable_bytes)
+             VALUES (?, ?, ?, ?)",
+        )?;
+        let mut insert_file = tx.prepare(
+            "INSERT INTO files (group_id, path, size, is_original, action)
+             VALUES (?, ?, ?, ?, ?)",
+        )?;
+
+        for hash in hashes {
+            let files = &duplicates[hash];
+            let Some(original) = files.first() else {
+                continue;
+            };
+            let deleted = |index: usize| options.plans_to_delete(files, index);
+
+            let reclaimable: u64 = files
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| deleted(index))
+                .map(|(_, file)| file.size)
+                .sum();
+            insert_group.execute(params![
+                hash,
+                original.match_kind.name(),
+                files.len(),
+                reclaimable
+            ])?;
+            let group_id = tx.last_insert_rowid();
+
+            for (index, file) in files.iter().enumerate() {
+                let action = if deleted(index) { "delete" } else { "keep" };
+                insert_file.execute(params![
+                    group_id,
+                    file.path.to_string_lossy().as_ref(),
+                    file.size,
+                    index == 0,
+                    action
+                ])?;
+                written += 1;
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(written)
+}
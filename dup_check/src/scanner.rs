@@ -1,45 +1,1615 @@
-use crate::{cache::Cache, file_info::FileInfo, utils};
-use anyhow::Result;
+use crate::{
+    burst::{self, BurstWindow},
+    cache::{Cache, CacheWriter, DirSnapshot, SnapshotEntry},
+    cancel::CancellationToken,
+    duplicates::{self, TopGroups},
+    error::{DupCheckError, Result},
+    file_info::{self, FileId, FileInfo, MatchKind},
+    journal::{self, ScanJournal},
+    names, perceptual,
+    spill::SpillStore,
+    utils::{self, HashAlgorithm},
+};
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use log::debug;
+use log::{debug, warn};
 use rayon::prelude::*;
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
 use walkdir::WalkDir;
 
+/// Extensions treated as text by the case-insensitive comparison
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "ini", "cfg", "conf", "json", "yaml", "yml", "toml", "xml", "csv", "log",
+];
+
+/// Text files above this size are never read whole for case folding
+pub const TEXT_CASE_INSENSITIVE_MAX_SIZE: u64 = 1024 * 1024;
+
+/// Size groups with more members than this are hashed in batches and
+/// grouped on disk rather than in memory
+pub const DEFAULT_LARGE_GROUP_THRESHOLD: usize = 100_000;
+
+/// Bytes compared by the quick hash before a file is hashed in full
+pub const QUICK_HASH_LEN: u64 = 4096;
+
+/// Groups [`Scanner::find_duplicates_streaming`] buffers ahead of its consumer
+const STREAM_BUFFER: usize = 64;
+
+/// Key of the group of empty files with [`EmptyFiles::Separate`]
+pub const EMPTY_KEY: &str = "empty";
+
+/// Key prefix of groups found by comparing files above
+/// [`ScanOptions::mmap_threshold`]. They have no full hash, so they are keyed
+/// by their size and the hash of their first block.
+pub const COMPARED_PREFIX: &str = "compared:";
+
+/// Directories modified more recently than this before they are listed get
+/// no snapshot, see [`ScanOptions::incremental`]
+const SNAPSHOT_MIN_AGE: Duration = Duration::from_secs(2);
+
+/// Candidate files keyed by their size
+type SizeGroups = HashMap<u64, Vec<FileInfo>>;
+
+/// A window of `len` bytes starting at `offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// A path to scan, optionally with size filters that apply only beneath it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRoot {
+    pub path: PathBuf,
+    /// Overrides [`ScanOptions::min_size`] beneath this path
+    pub min_size: Option<u64>,
+    /// Overrides [`ScanOptions::max_size`] beneath this path
+    pub max_size: Option<u64>,
+}
+
+impl ScanRoot {
+    /// A root that uses the scanner-wide size filters
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ScanRoot {
+            path: path.into(),
+            min_size: None,
+            max_size: None,
+        }
+    }
+}
+
+/// Which tree of [`Scanner::find_duplicates_between`] a file was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TreeSide {
+    Left,
+    Right,
+}
+
+/// What makes files duplicates of each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GroupBy {
+    /// Identical content
+    #[default]
+    Content,
+    /// The same file name, in any directory. Nothing is read or hashed, and
+    /// such groups are never deleted.
+    Name,
+    /// The same size and file name, for a fast first look at huge trees.
+    /// Nothing is read or hashed, so matches are unverified and never
+    /// deleted.
+    Quick,
+}
+
+/// Why the walk left out a file, see [`ScanStats::skipped`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    /// Hidden file, without [`ScanOptions::include_hidden`]
+    Hidden,
+    /// Matched an exclude pattern
+    Excluded,
+    /// Extension not selected, or excluded
+    Extension,
+    /// Zero-byte file, with [`EmptyFiles::Ignore`]
+    Empty,
+    TooSmall,
+    TooLarge,
+    /// Modified outside the `newer_than`/`older_than` window
+    OutsideTimeWindow,
+    /// Its metadata couldn't be read
+    Unreadable,
+}
+
+impl SkipReason {
+    pub const ALL: [SkipReason; 8] = [
+        SkipReason::Hidden,
+        SkipReason::Excluded,
+        SkipReason::Extension,
+        SkipReason::Empty,
+        SkipReason::TooSmall,
+        SkipReason::TooLarge,
+        SkipReason::OutsideTimeWindow,
+        SkipReason::Unreadable,
+    ];
+
+    /// Short description, as in "90 too small"
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipReason::Hidden => "hidden",
+            SkipReason::Excluded => "excluded",
+            SkipReason::Extension => "wrong extension",
+            SkipReason::Empty => "empty",
+            SkipReason::TooSmall => "too small",
+            SkipReason::TooLarge => "too large",
+            SkipReason::OutsideTimeWindow => "outside time window",
+            SkipReason::Unreadable => "unreadable",
+        }
+    }
+}
+
+/// What a scan does with zero-byte files, which would otherwise all share
+/// one hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum EmptyFiles {
+    /// Report them as one duplicate group, like any other content
+    #[default]
+    Group,
+    /// Skip them while walking
+    Ignore,
+    /// Report them as a group of their own, flagged as empty and only
+    /// deleted with `--force`
+    Separate,
+}
+
+/// What a scan does with symbolic links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SymlinkPolicy {
+    /// Ignore links to files and directories alike
+    #[default]
+    Skip,
+    /// Walk into linked directories and scan linked files as their targets.
+    /// A target reached several ways is only counted once, and link loops
+    /// are skipped.
+    Follow,
+    /// Scan links to files as files of their own, so a link is reported as a
+    /// duplicate of its target. Linked directories are not walked.
+    TreatAsFile,
+}
+
+/// Filters and behaviour switches for a scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Smallest size scanned; files of exactly this size are included
+    pub min_size: Option<u64>,
+    /// Largest size scanned; files of exactly this size are included. When
+    /// equal to `min_size`, only files of that one size are scanned.
+    pub max_size: Option<u64>,
+    /// Additionally group small text files whose content only differs in
+    /// letter case. Such groups are flagged and never deleted automatically.
+    pub text_case_insensitive: bool,
+    /// Override for [`DEFAULT_LARGE_GROUP_THRESHOLD`]; also used as the batch size
+    pub large_group_threshold: Option<usize>,
+    /// Group files by the hash of this byte range only, regardless of size.
+    /// Resulting groups are not full-content matches.
+    pub compare_range: Option<ByteRange>,
+    /// Cluster likely burst photos instead of comparing content. Groups are
+    /// only suggestions and never deleted automatically.
+    pub burst: Option<BurstWindow>,
+    /// Group images that look alike instead of comparing content: those whose
+    /// perceptual hashes differ in at most this many bits, see [`perceptual`]
+    pub similar_images: Option<u32>,
+    /// Only compare files whose names differ by a copy suffix such as
+    /// `photo (1).jpg` in the same directory. The un-suffixed name is kept.
+    pub dedupe_names: bool,
+    /// Skip files another process holds locked instead of failing on them
+    pub skip_locked: bool,
+    /// Content hash used to compare files
+    pub hash_algorithm: HashAlgorithm,
+    /// Keep at most this many groups, preferring those wasting the most space
+    pub max_result_groups: Option<usize>,
+    /// Journal finished size groups to this file while hashing, so an
+    /// interrupted scan can be resumed. The file is removed once the scan completes.
+    pub journal: Option<PathBuf>,
+    /// Take over the size groups an earlier, interrupted scan journaled.
+    /// Without a `journal` file, the journal is kept in the cache, if any.
+    pub resume: bool,
+    /// Record each directory's entries in the cache and, on later scans,
+    /// take them from there instead of listing the directory again while
+    /// its modification time is unchanged. This only saves the listings:
+    /// every directory and every file taken from a snapshot is still
+    /// stat'ed, as rewriting a file in place leaves its directory's time
+    /// alone. A root's snapshots are loaded in one go before it is walked.
+    /// Needs a cache; directories holding links, or changed moments before
+    /// they are listed, are always listed.
+    pub incremental: bool,
+    /// Skip files and whole directories whose name or full path matches
+    pub exclude: GlobSet,
+    /// Also scan hidden files, which are skipped by default
+    pub include_hidden: bool,
+    /// Compare files byte by byte after their hashes match. Files that
+    /// differ despite the hash are split into separate groups.
+    pub verify: bool,
+    /// Only group files whose extended attributes match as well as their
+    /// content, see [`utils::xattr_digest`]. Unix only; elsewhere, including
+    /// Windows alternate data streams, attributes are not compared.
+    pub compare_xattrs: bool,
+    /// Stops the scan with [`DupCheckError::Cancelled`]. Hashes computed so far are
+    /// still saved to the cache, and the journal is kept for `--resume`.
+    pub cancel: CancellationToken,
+    /// Threads used for walking directories and hashing; 0 uses all cores.
+    /// 1 reads one directory or file at a time, which is fastest on
+    /// spinning disks.
+    pub threads: usize,
+    /// Only report groups with at least this many files; values below 2
+    /// change nothing
+    pub min_copies: usize,
+    pub symlinks: SymlinkPolicy,
+    /// Walk into directory junctions, mount points and directory symlinks on
+    /// Windows. They are skipped by default, whatever [`ScanOptions::symlinks`]
+    /// says, as they can loop or lead into system directories. Following
+    /// them also follows symbolic links, as with [`SymlinkPolicy::Follow`].
+    /// No effect elsewhere.
+    pub follow_junctions: bool,
+    /// How deep to descend below each root; 1 only scans the files directly
+    /// in it
+    pub max_depth: Option<usize>,
+    /// Only scan files modified after this time. Files with modification
+    /// times in the future pass.
+    pub newer_than: Option<SystemTime>,
+    /// Only scan files modified before this time
+    pub older_than: Option<SystemTime>,
+    /// Files at least this large are compared directly through memory maps
+    /// instead of being hashed in full, see [`COMPARED_PREFIX`]
+    pub mmap_threshold: Option<u64>,
+    /// When set, only files with one of these extensions (lowercase, without
+    /// the dot) are scanned; files without an extension are skipped
+    pub extensions: Option<Vec<String>>,
+    /// Files with these extensions (lowercase, without the dot) are skipped
+    pub exclude_extensions: Vec<String>,
+    pub empty_files: EmptyFiles,
+    /// Draw no progress bars, e.g. when the caller has a UI of its own.
+    /// Results are the same either way.
+    pub hide_progress: bool,
+    pub group_by: GroupBy,
+    /// With [`GroupBy::Name`] or [`GroupBy::Quick`], treat file names that
+    /// only differ in case as the same, as case-insensitive filesystems do
+    pub ignore_case_names: bool,
+}
+
+/// Numbers describing one scan, see [`Scanner::find_duplicates_with_stats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    /// Files that passed the filters and were considered
+    pub files_scanned: u64,
+    /// Combined size of the scanned files
+    pub bytes_scanned: u64,
+    pub duplicate_groups: usize,
+    /// Files in duplicate groups, originals included
+    pub duplicate_files: usize,
+    /// Space freed by keeping one file per group
+    pub reclaimable_bytes: u64,
+    /// Hashes taken from the cache; always zero without a cache
+    pub cache_hits: u64,
+    /// Files the cache had no valid hash for, so they were read
+    pub cache_misses: u64,
+    /// Bytes that didn't have to be read thanks to cached hashes
+    pub cache_hit_bytes: u64,
+    /// Bytes read because the cache had no valid hash
+    pub cache_miss_bytes: u64,
+    /// Bytes actually read to hash or compare contents. Files ruled out by
+    /// their size or first block, and hashes taken from the cache, add only
+    /// what was read for them.
+    pub bytes_hashed: u64,
+    /// Directories read from disk
+    pub dirs_listed: u64,
+    /// Directories taken from their snapshot instead, see
+    /// [`ScanOptions::incremental`]
+    pub dirs_unchanged: u64,
+    pub elapsed: Duration,
+    /// Files left out by the filters, per reason; reasons that never
+    /// applied are omitted
+    pub skipped: Vec<(SkipReason, u64)>,
+}
+
+impl ScanStats {
+    /// Bytes hashed per second over the whole scan
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes_hashed as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Share of the bytes to hash that the cache saved reading, 0 to 100
+    pub fn cache_saved_io_percent(&self) -> f64 {
+        let total = self.cache_hit_bytes + self.cache_miss_bytes;
+        if total == 0 {
+            0.0
+        } else {
+            self.cache_hit_bytes as f64 * 100.0 / total as f64
+        }
+    }
+}
+
+/// A file or directory the last scan couldn't read and left out, see
+/// [`Scanner::scan_errors`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Counters behind [`ScanStats`], updated while scanning
+#[derive(Default)]
+struct ScanCounters {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    cache_hit_bytes: AtomicU64,
+    cache_miss_bytes: AtomicU64,
+    bytes_hashed: AtomicU64,
+    dirs_listed: AtomicU64,
+    dirs_unchanged: AtomicU64,
+    elapsed_nanos: AtomicU64,
+    /// Indexed by [`SkipReason`]
+    skipped: [AtomicU64; SkipReason::ALL.len()],
+}
+
+/// The progress bars of a scan, or none at all with
+/// [`ScanOptions::hide_progress`]
+struct Bars(Option<MultiProgress>);
+
+impl Bars {
+    fn new(hide: bool) -> Self {
+        Bars((!hide).then(MultiProgress::new))
+    }
+
+    fn add_spinner(&self) -> Bar {
+        Bar(self
+            .0
+            .as_ref()
+            .map(|bars| bars.add(ProgressBar::new_spinner())))
+    }
+
+    /// Run `f` with the bars cleared from the terminal
+    fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.0 {
+            Some(bars) => bars.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+/// One bar of [`Bars`]; everything is a no-op when progress is hidden
+struct Bar(Option<ProgressBar>);
+
+impl Bar {
+    fn set_style(&self, style: ProgressStyle) {
+        if let Some(bar) = &self.0 {
+            bar.set_style(style);
+        }
+    }
+
+    fn set_message(&self, message: impl Into<Cow<'static, str>>) {
+        if let Some(bar) = &self.0 {
+            bar.set_message(message);
+        }
+    }
+
+    fn set_length(&self, length: u64) {
+        if let Some(bar) = &self.0 {
+            bar.set_length(length);
+        }
+    }
+
+    fn set_position(&self, position: u64) {
+        if let Some(bar) = &self.0 {
+            bar.set_position(position);
+        }
+    }
+
+    fn finish_with_message(&self, message: impl Into<Cow<'static, str>>) {
+        if let Some(bar) = &self.0 {
+            bar.finish_with_message(message);
+        }
+    }
+
+    fn abandon_with_message(&self, message: impl Into<Cow<'static, str>>) {
+        if let Some(bar) = &self.0 {
+            bar.abandon_with_message(message);
+        }
+    }
+}
+
+/// Feeds the byte-sized hashing progress bar from parallel workers
+struct HashProgress<'a> {
+    bar: &'a Bar,
+    done: AtomicU64,
+}
+
+impl<'a> HashProgress<'a> {
+    fn new(bar: &'a Bar) -> Self {
+        HashProgress {
+            bar,
+            done: AtomicU64::new(0),
+        }
+    }
+
+    /// Count `bytes` more as hashed, or as no longer needing it
+    fn add(&self, bytes: u64) {
+        let done = self.done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.bar.set_position(done);
+    }
+}
+
+/// Groups of a [`Scanner::find_duplicates_streaming`] scan, yielded while
+/// it runs
+pub struct DuplicateStream {
+    groups: Receiver<(String, Vec<FileInfo>)>,
+    scan: Option<JoinHandle<Result<()>>>,
+    cancel: CancellationToken,
+}
+
+impl Iterator for DuplicateStream {
+    type Item = Result<(String, Vec<FileInfo>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(group) = self.groups.recv() {
+            return Some(Ok(group));
+        }
+
+        // The scan is over; report how it ended, once
+        match self.scan.take()?.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(e)),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for DuplicateStream {
+    fn drop(&mut self) {
+        if let Some(scan) = self.scan.take() {
+            self.cancel.cancel();
+            // Unblock the scan if it's waiting for room in the channel
+            while self.groups.recv().is_ok() {}
+            let _ = scan.join();
+        }
+    }
+}
+
+/// Fluent configuration of a [`Scanner`], see [`Scanner::builder`].
+/// Anything without a setter can be set through [`ScannerBuilder::options`].
+#[derive(Default)]
+pub struct ScannerBuilder {
+    use_cache: bool,
+    cache: Option<Arc<Cache>>,
+    options: ScanOptions,
+    exclude: Vec<Glob>,
+}
+
+impl ScannerBuilder {
+    /// Cache hashes in the default cache database, opened by
+    /// [`ScannerBuilder::build`]
+    pub fn cache(mut self, use_cache: bool) -> Self {
+        self.use_cache = use_cache;
+        self
+    }
+
+    /// Cache hashes in an already opened cache, e.g. one shared by scanners
+    pub fn with_cache(mut self, cache: Arc<Cache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.options.min_size = Some(size);
+        self
+    }
+
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.options.max_size = Some(size);
+        self
+    }
+
+    /// Hashing threads, see [`ScanOptions::threads`]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Skip matching files and directories; may be called repeatedly
+    pub fn exclude(mut self, glob: Glob) -> Self {
+        self.exclude.push(glob);
+        self
+    }
+
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.options.include_hidden = include_hidden;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.options.symlinks = policy;
+        self
+    }
+
+    pub fn hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.options.hash_algorithm = algorithm;
+        self
+    }
+
+    /// Compare matching files byte by byte, see [`ScanOptions::verify`]
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.options.verify = verify;
+        self
+    }
+
+    pub fn min_copies(mut self, copies: usize) -> Self {
+        self.options.min_copies = copies;
+        self
+    }
+
+    pub fn empty_files(mut self, mode: EmptyFiles) -> Self {
+        self.options.empty_files = mode;
+        self
+    }
+
+    /// Draw progress bars on stderr while scanning; on by default
+    pub fn show_progress(mut self, show: bool) -> Self {
+        self.options.hide_progress = !show;
+        self
+    }
+
+    pub fn cancel(mut self, token: CancellationToken) -> Self {
+        self.options.cancel = token;
+        self
+    }
+
+    /// Replace all options set so far, except the exclusions
+    pub fn options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Open the cache if asked for and create the scanner. Globs passed to
+    /// [`ScannerBuilder::exclude`] take the place of [`ScanOptions::exclude`].
+    pub fn build(mut self) -> Result<Scanner> {
+        let cache = match self.cache {
+            Some(cache) => Some(cache),
+            None if self.use_cache => Some(Arc::new(Cache::new()?)),
+            None => None,
+        };
+
+        if !self.exclude.is_empty() {
+            let mut exclude = GlobSetBuilder::new();
+            for glob in self.exclude {
+                exclude.add(glob);
+            }
+            self.options.exclude = exclude.build().map_err(anyhow::Error::from)?;
+        }
+
+        Ok(Scanner::with_options(cache, self.options))
+    }
+}
+
+/// A file found by the walk, see [`Scanner::candidate`]
+struct Candidate {
+    /// Index of the root it was found under
+    root: usize,
+    /// Found as a link to a file rather than the file itself
+    is_link: bool,
+    file: FileInfo,
+    /// Resolved path, when several roots may reach the same file
+    canonical: Option<PathBuf>,
+}
+
+/// Where a directory visited by [`Walk`] resolves to when links are
+/// followed, to detect loops
+#[derive(Clone)]
+struct Resolved {
+    path: PathBuf,
+    /// Resolved directories this branch of the walk followed links out of.
+    /// Together with `path` they hold every ancestor's resolved path as a
+    /// prefix, so loops through several links are caught too.
+    link_parents: Arc<Vec<PathBuf>>,
+}
+
+impl Resolved {
+    /// Whether `target` is this directory or one of its ancestors
+    fn is_within(&self, target: &Path) -> bool {
+        self.path.starts_with(target) || self.link_parents.iter().any(|dir| dir.starts_with(target))
+    }
+
+    fn join(&self, name: impl AsRef<Path>) -> Resolved {
+        Resolved {
+            path: self.path.join(name),
+            link_parents: Arc::clone(&self.link_parents),
+        }
+    }
+
+    /// The resolved `target` of a link in this directory
+    fn follow(&self, target: PathBuf) -> Resolved {
+        let mut link_parents = Vec::clone(&self.link_parents);
+        link_parents.push(self.path.clone());
+        Resolved {
+            path: target,
+            link_parents: Arc::new(link_parents),
+        }
+    }
+}
+
+/// A file the walk kept
+enum Found {
+    /// Listed from its directory, with the metadata the filters read
+    Entry(walkdir::DirEntry, fs::Metadata),
+    /// Known from a directory snapshot, or stat'ed while recording one, see
+    /// [`ScanOptions::incremental`]
+    Known(FileInfo),
+}
+
+/// One root's directory walk. Every directory is listed by a rayon task of
+/// its own, so large and slow (e.g. network) trees are read concurrently.
+/// Excluded directories, junctions, `max_depth` and symlink loops are
+/// handled as [`WalkDir`] would for a single sequential walk.
+struct Walk<'a> {
+    scanner: &'a Scanner,
+    root: &'a ScanRoot,
+    follow_links: bool,
+    progress: &'a Bar,
+    /// Files that passed the filters
+    found: Mutex<Vec<Found>>,
+    count: AtomicU64,
+    /// Snapshots stored by earlier scans for the directories under the
+    /// root, loaded up front with [`ScanOptions::incremental`]
+    known: HashMap<PathBuf, DirSnapshot>,
+    /// Snapshots of the directories listed, to store once the walk is done
+    snapshots: Mutex<Vec<(PathBuf, DirSnapshot)>>,
+}
+
+impl Walk<'_> {
+    /// Walk the root and return the files to scan, in no particular order
+    fn run(self) -> Result<Vec<Found>> {
+        let scanner = self.scanner;
+        let root = WalkDir::new(&self.root.path)
+            .follow_links(self.follow_links)
+            .max_depth(0)
+            .into_iter()
+            .next();
+        match root {
+            Some(Ok(entry)) if entry.file_type().is_dir() => {
+                let resolved = self
+                    .follow_links
+                    .then(|| fs::canonicalize(entry.path()).ok())
+                    .flatten()
+                    .map(|path| Resolved {
+                        path,
+                        link_parents: Arc::default(),
+                    });
+                rayon::scope(|scope| self.visit_dir(scope, entry.into_path(), 0, resolved));
+            }
+            Some(Ok(entry)) => self.visit_file(entry, &mut self.found.lock().unwrap()),
+            Some(Err(e)) => self.record_walk_error(e),
+            None => {}
+        }
+
+        let snapshots = self.snapshots.into_inner().unwrap();
+        if let (Some(cache), false) = (&scanner.cache, snapshots.is_empty()) {
+            if let Err(e) = cache.store_dir_snapshots(&snapshots) {
+                warn!("Failed to save directory snapshots to cache: {}", e);
+            }
+        }
+        if scanner.options.cancel.is_cancelled() {
+            return Err(DupCheckError::Cancelled);
+        }
+        Ok(self.found.into_inner().unwrap())
+    }
+
+    /// List `dir`, which is `depth` levels below the root, keep its files
+    /// and spawn a task for each subdirectory. `resolved` is where `dir`
+    /// resolves to when links are followed, to detect loops.
+    ///
+    /// With [`ScanOptions::incremental`], a directory whose modification
+    /// time matches its snapshot is taken from there instead, and one that
+    /// is listed has its snapshot recorded, unless it holds links.
+    fn visit_dir<'s>(
+        &'s self,
+        scope: &rayon::Scope<'s>,
+        dir: PathBuf,
+        depth: usize,
+        resolved: Option<Resolved>,
+    ) {
+        let scanner = self.scanner;
+        if scanner.options.cancel.is_cancelled()
+            || scanner.options.max_depth.is_some_and(|max| depth >= max)
+        {
+            return;
+        }
+
+        let incremental = scanner.options.incremental && scanner.cache.is_some();
+        let modified = if incremental && dir.to_str().is_some() {
+            let modified = fs::metadata(&dir).and_then(|metadata| metadata.modified());
+            match (modified, self.known.get(&dir)) {
+                (Ok(modified), Some(snapshot)) if snapshot.modified == modified => {
+                    scanner
+                        .counters
+                        .dirs_unchanged
+                        .fetch_add(1, Ordering::Relaxed);
+                    return self.replay(scope, &dir, snapshot.clone(), depth, resolved);
+                }
+                (modified, _) => modified.ok(),
+            }
+        } else {
+            None
+        };
+        scanner.counters.dirs_listed.fetch_add(1, Ordering::Relaxed);
+        // A directory changed just before it is listed may change again
+        // without its time moving on filesystems with coarse timestamps
+        let settled = SystemTime::now() - SNAPSHOT_MIN_AGE;
+        let mut snapshot = modified
+            .filter(|&modified| modified < settled)
+            .map(|modified| DirSnapshot {
+                modified,
+                entries: Vec::new(),
+            });
+
+        let mut files = Vec::new();
+        let entries = WalkDir::new(&dir)
+            .follow_links(self.follow_links)
+            .min_depth(1)
+            .max_depth(1);
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.record_walk_error(e);
+                    snapshot = None;
+                    continue;
+                }
+            };
+            let name = entry.file_name().to_str().map(str::to_string);
+            if entry.path_is_symlink() || name.is_none() {
+                snapshot = None;
+            }
+            if !entry.file_type().is_dir() {
+                match (&mut snapshot, name) {
+                    (Some(recorded), Some(name)) if entry.file_type().is_file() => {
+                        match entry.metadata() {
+                            Ok(metadata) => {
+                                let known = snapshot_entry(name, &metadata);
+                                self.visit_known(entry.into_path(), &known, &mut files);
+                                recorded.entries.push(known);
+                            }
+                            Err(e) => {
+                                scanner.record_error(entry.path(), e);
+                                scanner.count_skipped(entry.path(), SkipReason::Unreadable);
+                                snapshot = None;
+                            }
+                        }
+                    }
+                    _ => self.visit_file(entry, &mut files),
+                }
+                continue;
+            }
+            if let (Some(recorded), Some(name)) = (&mut snapshot, name) {
+                recorded.entries.push(SnapshotEntry {
+                    name,
+                    is_dir: true,
+                    size: 0,
+                    modified: None,
+                    allocated: 0,
+                    file_id: None,
+                });
+            }
+            if scanner.is_excluded_dir(entry.path()) || scanner.is_skipped_junction(entry.path()) {
+                continue;
+            }
+            let child_resolved = match &resolved {
+                Some(parent) if entry.path_is_symlink() => match fs::canonicalize(entry.path()) {
+                    Ok(target) if parent.is_within(&target) => {
+                        warn!(
+                            "Skipping symlink loop: {} points to its ancestor {}",
+                            entry.path().display(),
+                            target.display()
+                        );
+                        continue;
+                    }
+                    Ok(target) => Some(parent.follow(target)),
+                    Err(e) => {
+                        scanner.record_error(entry.path(), e);
+                        continue;
+                    }
+                },
+                Some(parent) => Some(parent.join(entry.file_name())),
+                None => None,
+            };
+            let child = entry.into_path();
+            scope.spawn(move |scope| self.visit_dir(scope, child, depth + 1, child_resolved));
+        }
+
+        if let Some(snapshot) = snapshot {
+            self.snapshots.lock().unwrap().push((dir, snapshot));
+        }
+        self.keep(files);
+    }
+
+    /// Visit what `snapshot` recorded for `dir` as [`Walk::visit_dir`] would
+    /// after listing it. Files are stat'ed again, and the snapshot is updated
+    /// if any of them changed.
+    fn replay<'s>(
+        &'s self,
+        scope: &rayon::Scope<'s>,
+        dir: &Path,
+        snapshot: DirSnapshot,
+        depth: usize,
+        resolved: Option<Resolved>,
+    ) {
+        let scanner = self.scanner;
+        let mut files = Vec::new();
+        let mut entries = Vec::with_capacity(snapshot.entries.len());
+        let mut changed = false;
+        for entry in snapshot.entries {
+            let path = dir.join(&entry.name);
+            if !entry.is_dir {
+                match self.visit_replayed(path, &entry, &mut files) {
+                    Some(current) => {
+                        changed |= current != entry;
+                        entries.push(current);
+                    }
+                    None => entries.push(entry),
+                }
+                continue;
+            }
+            entries.push(entry.clone());
+            if scanner.is_excluded_dir(&path) || scanner.is_skipped_junction(&path) {
+                continue;
+            }
+            // Directories holding links are never recorded, so there are no
+            // links to resolve here
+            let child_resolved = resolved.as_ref().map(|parent| parent.join(&entry.name));
+            scope.spawn(move |scope| self.visit_dir(scope, path, depth + 1, child_resolved));
+        }
+        if changed {
+            let snapshot = DirSnapshot {
+                modified: snapshot.modified,
+                entries,
+            };
+            self.snapshots
+                .lock()
+                .unwrap()
+                .push((dir.to_path_buf(), snapshot));
+        }
+        self.keep(files);
+    }
+
+    /// Add the files kept from one directory to the walk's results
+    fn keep(&self, files: Vec<Found>) {
+        if files.is_empty() {
+            return;
+        }
+        let before = self.count.fetch_add(files.len() as u64, Ordering::Relaxed);
+        let count = before + files.len() as u64;
+        if before / 1000 != count / 1000 {
+            self.progress
+                .set_message(format!("Collecting files... {} found", count));
+        }
+        self.found.lock().unwrap().extend(files);
+    }
+
+    fn visit_file(&self, entry: walkdir::DirEntry, files: &mut Vec<Found>) {
+        if let Some(metadata) = self.scanner.should_process_file(&entry, self.root) {
+            files.push(Found::Entry(entry, metadata));
+        }
+    }
+
+    /// Like [`Walk::visit_file`], for the file at `path` as recorded in a
+    /// snapshot
+    fn visit_known(&self, path: PathBuf, entry: &SnapshotEntry, files: &mut Vec<Found>) {
+        if !self.scanner.should_process_known(&path, entry, self.root) {
+            return;
+        }
+        let mut file = FileInfo::new(path, entry.size);
+        file.modified = entry.modified;
+        file.allocated = entry.allocated;
+        file.file_id = entry.file_id;
+        files.push(Found::Known(file));
+    }
+
+    /// Like [`Walk::visit_known`], for the file at `path` as a replayed
+    /// snapshot recorded it. The file is stat'ed again so a rewrite is
+    /// grouped and filtered by its current size; returns how it is now, if
+    /// it was stat'ed.
+    fn visit_replayed(
+        &self,
+        path: PathBuf,
+        entry: &SnapshotEntry,
+        files: &mut Vec<Found>,
+    ) -> Option<SnapshotEntry> {
+        let scanner = self.scanner;
+        if let Err(reason) = scanner.check_path(&path) {
+            scanner.count_skipped(&path, reason);
+            return None;
+        }
+        let current = match fs::metadata(&path) {
+            Ok(metadata) => snapshot_entry(entry.name.clone(), &metadata),
+            Err(e) => {
+                scanner.record_error(&path, e);
+                scanner.count_skipped(&path, SkipReason::Unreadable);
+                return None;
+            }
+        };
+        if current != *entry {
+            debug!("{} changed since it was listed", path.display());
+        }
+        self.visit_known(path, &current, files);
+        Some(current)
+    }
+
+    fn record_walk_error(&self, e: walkdir::Error) {
+        let path = e.path().unwrap_or(&self.root.path).to_path_buf();
+        match e.io_error() {
+            Some(io_error) => self.scanner.record_error(&path, io_error),
+            None => self.scanner.record_error(&path, &e),
+        }
+    }
+}
+
+/// How a directory snapshot records the file `name` with `metadata`
+fn snapshot_entry(name: String, metadata: &fs::Metadata) -> SnapshotEntry {
+    SnapshotEntry {
+        name,
+        is_dir: false,
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        allocated: file_info::allocated_size(metadata),
+        file_id: FileId::from_metadata(metadata),
+    }
+}
+
 pub struct Scanner {
     cache: Option<Arc<Cache>>,
-    min_size: Option<u64>,
-    max_size: Option<u64>,
+    options: ScanOptions,
+    locked_files: Mutex<Vec<PathBuf>>,
+    scan_errors: Mutex<Vec<ScanError>>,
+    dropped_groups: AtomicUsize,
+    counters: ScanCounters,
 }
 
-impl Scanner {
-    pub fn new(use_cache: bool, min_size: Option<u64>, max_size: Option<u64>) -> Result<Self> {
-        let cache = if use_cache {
-            Some(Arc::new(Cache::new()?))
-        } else {
-            None
+impl Scanner {
+    /// Configure a scanner step by step; for anything beyond a few options
+    /// this reads better than [`Scanner::new`]
+    pub fn builder() -> ScannerBuilder {
+        ScannerBuilder::default()
+    }
+
+    /// A scanner with the given cache and size limits and defaults for
+    /// everything else, so hidden files are skipped. To include them, or for
+    /// any other option, use [`Scanner::builder`], e.g.
+    /// `Scanner::builder().include_hidden(true).build()`.
+    pub fn new(use_cache: bool, min_size: Option<u64>, max_size: Option<u64>) -> Result<Self> {
+        let mut builder = Self::builder().cache(use_cache);
+        if let Some(size) = min_size {
+            builder = builder.min_size(size);
+        }
+        if let Some(size) = max_size {
+            builder = builder.max_size(size);
+        }
+        builder.build()
+    }
+
+    /// Files skipped by the last scan because another process had them locked
+    pub fn locked_files(&self) -> Vec<PathBuf> {
+        let mut locked = self.locked_files.lock().unwrap().clone();
+        locked.sort();
+        locked
+    }
+
+    /// Files and directories the last scan couldn't read, e.g. for lack of
+    /// permission. The scan carries on without them; only a missing root
+    /// fails it. Sorted by path.
+    pub fn scan_errors(&self) -> Vec<ScanError> {
+        let mut errors = self.scan_errors.lock().unwrap().clone();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors
+    }
+
+    /// Leave out `path`, which couldn't be read, and remember why
+    fn record_error(&self, path: &Path, error: impl std::fmt::Display) {
+        warn!("Skipping {}: {}", path.display(), error);
+        self.scan_errors.lock().unwrap().push(ScanError {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        });
+    }
+
+    /// Groups the last scan left out because of [`ScanOptions::max_result_groups`]
+    pub fn dropped_groups(&self) -> usize {
+        self.dropped_groups.load(Ordering::Relaxed)
+    }
+
+    /// Create a scanner around an already opened cache (or none)
+    pub fn with_options(cache: Option<Arc<Cache>>, options: ScanOptions) -> Self {
+        Scanner {
+            cache,
+            options,
+            locked_files: Mutex::new(Vec::new()),
+            scan_errors: Mutex::new(Vec::new()),
+            dropped_groups: AtomicUsize::new(0),
+            counters: ScanCounters::default(),
+        }
+    }
+
+    /// Like [`Scanner::find_duplicates`], but also returns statistics
+    /// about the scan
+    pub fn find_duplicates_with_stats(
+        &self,
+        path: &Path,
+    ) -> Result<(HashMap<String, Vec<FileInfo>>, ScanStats)> {
+        let duplicates = self.find_duplicates(path)?;
+        let stats = self.stats(&duplicates);
+        Ok((duplicates, stats))
+    }
+
+    /// Statistics about the last scan, which found `duplicates`
+    pub fn stats(&self, duplicates: &HashMap<String, Vec<FileInfo>>) -> ScanStats {
+        ScanStats {
+            files_scanned: self.counters.files.load(Ordering::Relaxed),
+            bytes_scanned: self.counters.bytes.load(Ordering::Relaxed),
+            duplicate_groups: duplicates.len(),
+            duplicate_files: duplicates.values().map(|files| files.len()).sum(),
+            reclaimable_bytes: duplicates
+                .values()
+                .map(|files| duplicates::wasted_bytes(files))
+                .sum(),
+            cache_hits: self.counters.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.counters.cache_misses.load(Ordering::Relaxed),
+            cache_hit_bytes: self.counters.cache_hit_bytes.load(Ordering::Relaxed),
+            cache_miss_bytes: self.counters.cache_miss_bytes.load(Ordering::Relaxed),
+            bytes_hashed: self.counters.bytes_hashed.load(Ordering::Relaxed),
+            dirs_listed: self.counters.dirs_listed.load(Ordering::Relaxed),
+            dirs_unchanged: self.counters.dirs_unchanged.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(self.counters.elapsed_nanos.load(Ordering::Relaxed)),
+            skipped: SkipReason::ALL
+                .into_iter()
+                .map(|reason| {
+                    let count = self.counters.skipped[reason as usize].load(Ordering::Relaxed);
+                    (reason, count)
+                })
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+        }
+    }
+
+    pub fn find_duplicates(&self, path: &Path) -> Result<HashMap<String, Vec<FileInfo>>> {
+        self.find_duplicates_with(path, |_, _| {})
+    }
+
+    /// Like [`Scanner::find_duplicates`], but calls `on_group` with each
+    /// duplicate group as soon as it is confirmed, while the scan continues.
+    ///
+    /// The callback runs with the progress bars suspended, so it may print
+    /// to the terminal freely. It can be called from several threads.
+    pub fn find_duplicates_with<F>(
+        &self,
+        path: &Path,
+        on_group: F,
+    ) -> Result<HashMap<String, Vec<FileInfo>>>
+    where
+        F: Fn(&str, &[FileInfo]) + Sync,
+    {
+        self.find_duplicates_in(&[path], on_group)
+    }
+
+    /// Like [`Scanner::find_duplicates_in_roots`], for roots without their
+    /// own filters
+    pub fn find_duplicates_in<P, F>(
+        &self,
+        roots: &[P],
+        on_group: F,
+    ) -> Result<HashMap<String, Vec<FileInfo>>>
+    where
+        P: AsRef<Path>,
+        F: Fn(&str, &[FileInfo]) + Sync,
+    {
+        let roots: Vec<ScanRoot> = roots
+            .iter()
+            .map(|root| ScanRoot::new(root.as_ref()))
+            .collect();
+        self.find_duplicates_in_roots(&roots, on_group)
+    }
+
+    /// Scan several roots (directories or single files) as one set.
+    ///
+    /// Roots may overlap or name the same file through different spellings,
+    /// e.g. `./a.txt` and `/home/me/a.txt`. Every file is identified by its
+    /// canonical path and only collected once, so it can never be grouped
+    /// with itself. The first spelling encountered is the one reported.
+    ///
+    /// Size filters set on a root replace those of [`ScanOptions`] for the
+    /// files found under it.
+    pub fn find_duplicates_in_roots<F>(
+        &self,
+        roots: &[ScanRoot],
+        on_group: F,
+    ) -> Result<HashMap<String, Vec<FileInfo>>>
+    where
+        F: Fn(&str, &[FileInfo]) + Sync,
+    {
+        let started = Instant::now();
+        let duplicates = self.in_pool(|| self.scan_roots(roots, &on_group));
+        let elapsed = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.counters
+            .elapsed_nanos
+            .store(elapsed, Ordering::Relaxed);
+        duplicates
+    }
+
+    /// Find files that exist in both `left` and `right`. Only groups with at
+    /// least one file from each tree are returned, so copies that stay within
+    /// one tree are ignored. Each file is tagged with its tree, and the files
+    /// from `left` come first.
+    ///
+    /// When one tree contains the other, files count towards the innermost.
+    pub fn find_duplicates_between(
+        &self,
+        left: &Path,
+        right: &Path,
+    ) -> Result<HashMap<String, Vec<(TreeSide, FileInfo)>>> {
+        let duplicates = self.find_duplicates_in(&[left, right], |_, _| {})?;
+
+        // Longer root first, so the innermost tree wins for nested trees
+        let mut roots = [(left, TreeSide::Left), (right, TreeSide::Right)];
+        roots.sort_by_key(|(root, _)| std::cmp::Reverse(root.components().count()));
+        let side_of = |path: &Path| {
+            roots
+                .iter()
+                .find(|(root, _)| path.starts_with(root))
+                .map(|&(_, side)| side)
+        };
+
+        Ok(duplicates
+            .into_iter()
+            .filter_map(|(hash, files)| {
+                let mut tagged: Vec<(TreeSide, FileInfo)> = files
+                    .into_iter()
+                    .filter_map(|file| side_of(&file.path).map(|side| (side, file)))
+                    .collect();
+                let crosses = tagged.iter().any(|(side, _)| *side == TreeSide::Left)
+                    && tagged.iter().any(|(side, _)| *side == TreeSide::Right);
+                if !crosses {
+                    return None;
+                }
+                tagged.sort_by_key(|(side, _)| *side == TreeSide::Right);
+                Some((hash, tagged))
+            })
+            .collect())
+    }
+
+    /// Scan in the background and yield each duplicate group as soon as it
+    /// is confirmed. Groups aren't kept once yielded, so memory doesn't grow
+    /// with the number of results; [`ScanOptions::max_result_groups`] is
+    /// ignored, as ranking needs every group.
+    ///
+    /// A failed or cancelled scan ends the stream with its error. Dropping
+    /// the stream early cancels the scan, through a child of
+    /// [`ScanOptions::cancel`] so the caller's token is left alone.
+    pub fn find_duplicates_streaming(mut self, roots: Vec<ScanRoot>) -> DuplicateStream {
+        // Groups are handed out as found; keep none of them here
+        self.options.max_result_groups = Some(0);
+        self.options.cancel = self.options.cancel.child();
+        let cancel = self.options.cancel.clone();
+
+        // Bounded, so a slow consumer holds the scan back instead of letting
+        // groups pile up in the channel
+        let (sender, groups) = mpsc::sync_channel(STREAM_BUFFER);
+        let scan = thread::spawn(move || {
+            self.find_duplicates_in_roots(&roots, |hash, files| {
+                if sender.send((hash.to_string(), files.to_vec())).is_err() {
+                    self.options.cancel.cancel();
+                }
+            })
+            .map(drop)
+        });
+
+        DuplicateStream {
+            groups,
+            scan: Some(scan),
+            cancel,
+        }
+    }
+
+    /// Run `op` on a thread pool of [`ScanOptions::threads`] threads, or on
+    /// rayon's global pool when no thread count is set
+    fn in_pool<R, OP>(&self, op: OP) -> Result<R>
+    where
+        R: Send,
+        OP: FnOnce() -> Result<R> + Send,
+    {
+        match self.options.threads {
+            0 => op(),
+            threads => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(anyhow::Error::from)?
+                .install(op),
+        }
+    }
+
+    fn scan_roots<F>(
+        &self,
+        roots: &[ScanRoot],
+        on_group: F,
+    ) -> Result<HashMap<String, Vec<FileInfo>>>
+    where
+        F: Fn(&str, &[FileInfo]) + Sync,
+    {
+        let multi_progress = Bars::new(self.options.hide_progress);
+        let found = AtomicUsize::new(0);
+        let report_group = |hash: &str, files: &[FileInfo]| {
+            found.fetch_add(1, Ordering::Relaxed);
+            multi_progress.suspend(|| on_group(hash, files))
+        };
+
+        let (mut size_groups, text_files) = self.collect_files(roots, &multi_progress)?;
+
+        // Hash calculation progress
+        let hash_progress = multi_progress.add_spinner();
+        hash_progress.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] {msg}")
+                .unwrap(),
+        );
+        hash_progress.set_message("Analyzing potential duplicates...");
+
+        if matches!(self.options.group_by, GroupBy::Name | GroupBy::Quick) {
+            hash_progress.set_message("Grouping files by name...");
+            let files: Vec<FileInfo> = size_groups.into_values().flatten().collect();
+            let ignore_case = self.options.ignore_case_names;
+            let mut groups = if self.options.group_by == GroupBy::Name {
+                names::same_name_groups(files, ignore_case)
+            } else {
+                names::same_size_and_name_groups(files, ignore_case)
+            };
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (key, files) in &groups {
+                report_group(key, files);
+            }
+            hash_progress.finish_with_message(format!("Found {} shared names", groups.len()));
+            return Ok(self.cap_results(groups));
+        }
+
+        if let Some(range) = self.options.compare_range {
+            hash_progress.set_message(format!(
+                "Comparing {} bytes at offset {}...",
+                range.len, range.offset
+            ));
+            let files: Vec<FileInfo> = size_groups.into_values().flatten().collect();
+            let mut groups = self.range_groups(files, range);
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (hash, files) in &groups {
+                report_group(hash, files);
+            }
+            hash_progress
+                .finish_with_message(format!("Found {} range-matched groups", groups.len()));
+            return Ok(self.cap_results(groups));
+        }
+
+        if let Some(window) = self.options.burst {
+            hash_progress.set_message("Looking for burst photos...");
+            let files: Vec<FileInfo> = size_groups.into_values().flatten().collect();
+            let mut groups = burst::burst_groups(files, &window);
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (key, files) in &groups {
+                report_group(key, files);
+            }
+            hash_progress.finish_with_message(format!("Found {} likely bursts", groups.len()));
+            return Ok(self.cap_results(groups));
+        }
+
+        if let Some(threshold) = self.options.similar_images {
+            hash_progress.set_message("Comparing images by appearance...");
+            let files: Vec<FileInfo> = size_groups.into_values().flatten().collect();
+            let mut groups = perceptual::similar_groups(files, threshold);
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (key, files) in &groups {
+                report_group(key, files);
+            }
+            hash_progress
+                .finish_with_message(format!("Found {} similar image groups", groups.len()));
+            return Ok(self.cap_results(groups));
+        }
+
+        if self.options.dedupe_names {
+            hash_progress.set_message("Comparing copies with download-style names...");
+            let mut groups = self.name_groups(size_groups);
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (key, files) in &groups {
+                report_group(key, files);
+            }
+            hash_progress.finish_with_message(format!("Found {} renamed copies", groups.len()));
+            return Ok(self.cap_results(groups));
+        }
+
+        // Nothing to hash: empty files are all the same
+        let empty_files = match self.options.empty_files {
+            EmptyFiles::Separate => size_groups.remove(&0),
+            _ => None,
+        };
+
+        // Count potential duplicates for progress
+        let potential_duplicates: usize = size_groups
+            .values()
+            .filter(|files| files.len() > 1)
+            .map(|files| files.len())
+            .sum();
+
+        hash_progress.set_message(format!(
+            "Found {} files in duplicate size groups",
+            potential_duplicates
+        ));
+
+        // Hashes are persisted by a background thread so workers never wait on SQLite
+        let writer = self.cache.clone().map(Cache::spawn_writer);
+
+        let roots: Vec<PathBuf> = roots.iter().map(|root| root.path.clone()).collect();
+        let algorithm = self.options.hash_algorithm;
+        let journal = match (&self.options.journal, &self.cache) {
+            (Some(path), _) if self.options.resume => {
+                Some(ScanJournal::resume(path, &roots, algorithm)?)
+            }
+            (Some(path), _) => Some(ScanJournal::create(path, &roots, algorithm)?),
+            (None, Some(cache)) if self.options.resume => Some(ScanJournal::in_cache(
+                Arc::clone(cache),
+                &roots,
+                algorithm,
+                true,
+            )?),
+            _ => None,
         };
+        if let Some(journal) = journal
+            .as_ref()
+            .filter(|journal| journal.recovered_len() > 0)
+        {
+            hash_progress.set_message(format!(
+                "Resuming: {} size groups already done",
+                journal.recovered_len()
+            ));
+        }
 
-        Ok(Scanner {
-            cache,
-            min_size,
-            max_size,
-        })
-    }
+        // Oversized groups are handled one at a time with bounded memory
+        let threshold = self
+            .options
+            .large_group_threshold
+            .unwrap_or(DEFAULT_LARGE_GROUP_THRESHOLD)
+            .max(1);
+        let (large_groups, size_groups): (
+            HashMap<u64, Vec<FileInfo>>,
+            HashMap<u64, Vec<FileInfo>>,
+        ) = size_groups
+            .into_iter()
+            .partition(|(_, files)| files.len() > threshold);
 
-    pub fn find_duplicates(&self, path: &Path) -> Result<HashMap<String, Vec<FileInfo>>> {
-        // Check if the directory exists first
-        if !path.exists() {
-            return Err(anyhow::anyhow!(
-                "Directory does not exist: {}",
-                path.display()
+        // Large groups are hashed whatever their size, the rest only when
+        // they can reach --min-copies
+        let total_bytes: u64 = large_groups
+            .iter()
+            .chain(
+                size_groups
+                    .iter()
+                    .filter(|(_, files)| self.has_min_copies(files)),
+            )
+            .map(|(size, files)| size * files.len() as u64)
+            .sum();
+        hash_progress.set_length(total_bytes);
+        hash_progress.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40}] {bytes}/{total_bytes} ({eta}) {msg}")
+                .unwrap(),
+        );
+        let progress = HashProgress::new(&hash_progress);
+
+        // Groups are capped as they arrive, so memory stays bounded
+        let results = Mutex::new(TopGroups::new(self.options.max_result_groups));
+
+        if let Some(mut files) = empty_files.filter(|files| self.has_min_copies(files)) {
+            for file in &mut files {
+                file.match_kind = MatchKind::Empty;
+            }
+            report_group(EMPTY_KEY, &files);
+            results
+                .lock()
+                .unwrap()
+                .extend([(EMPTY_KEY.to_string(), files)]);
+        }
+
+        for (size, files) in large_groups {
+            hash_progress.set_message(format!(
+                "Hashing {} files of {} bytes in batches...",
+                files.len(),
+                size
             ));
+            let groups = self.journaled(journal.as_ref(), &progress, size, files, |files| {
+                let groups =
+                    self.hash_large_group(size, files, threshold, writer.as_ref(), &progress)?;
+                Ok(self.verify_groups(groups))
+            });
+            let mut groups = match groups {
+                Err(DupCheckError::Cancelled) => break,
+                groups => groups?,
+            };
+            groups.retain(|(_, files)| self.has_min_copies(files));
+            for (hash, files) in &groups {
+                report_group(hash, files);
+            }
+            results.lock().unwrap().extend(groups);
+        }
+
+        // Process files in parallel and collect duplicates
+        size_groups
+            .into_par_iter()
+            .filter(|(_, files)| self.has_min_copies(files) && !self.options.cancel.is_cancelled())
+            .map(|(size, files)| {
+                self.journaled(journal.as_ref(), &progress, size, files, |files| {
+                    let groups = self.hash_size_group(files, writer.as_ref(), &progress);
+                    Ok(self.verify_groups(groups))
+                })
+            })
+            .try_for_each(|groups| -> Result<()> {
+                let mut groups = groups?;
+                groups.retain(|(_, files)| self.has_min_copies(files));
+                for (hash, files) in &groups {
+                    report_group(hash, files);
+                }
+                if !groups.is_empty() {
+                    results.lock().unwrap().extend(groups);
+                    hash_progress.set_message(format!(
+                        "Found {} duplicate groups",
+                        found.load(Ordering::Relaxed)
+                    ));
+                }
+                Ok(())
+            })?;
+        let mut results = results.into_inner().unwrap();
+        let cancelled = self.options.cancel.is_cancelled();
+
+        // A cancelled scan keeps its journal so it can be resumed
+        if let Some(journal) = journal.filter(|_| !cancelled) {
+            if let Err(e) = journal.finish() {
+                warn!("Failed to remove scan journal: {}", e);
+            }
+        }
+
+        if let Some(writer) = writer {
+            hash_progress.set_message("Saving hashes to cache...");
+            if let Err(e) = writer.finish() {
+                warn!("Failed to save hashes to cache: {}", e);
+            }
+        }
+
+        if cancelled {
+            hash_progress.abandon_with_message("Scan cancelled");
+            return Err(DupCheckError::Cancelled);
+        }
+
+        if !text_files.is_empty() {
+            hash_progress.set_message("Comparing text files ignoring case...");
+            let mut groups = Self::case_insensitive_groups(text_files);
+            groups.retain(|_, files| self.has_min_copies(files));
+            for (hash, files) in &groups {
+                report_group(hash, files);
+            }
+            results.extend(groups);
+        }
+
+        hash_progress.finish_with_message(format!(
+            "Found {} duplicate groups",
+            found.load(Ordering::Relaxed)
+        ));
+        self.dropped_groups
+            .store(results.dropped(), Ordering::Relaxed);
+
+        Ok(results.into_groups())
+    }
+
+    /// Files whose content appears exactly once under `path`.
+    ///
+    /// Uses the same size grouping as [`Scanner::find_duplicates`]; files
+    /// with a unique size are known to be unique without being hashed.
+    /// Files that can't be read are left out. Sorted by path.
+    pub fn find_unique(&self, path: &Path) -> Result<Vec<FileInfo>> {
+        self.in_pool(|| self.unique_files(path))
+    }
+
+    fn unique_files(&self, path: &Path) -> Result<Vec<FileInfo>> {
+        let multi_progress = Bars::new(self.options.hide_progress);
+        let (size_groups, _) = self.collect_files(&[ScanRoot::new(path)], &multi_progress)?;
+
+        let writer = self.cache.clone().map(Cache::spawn_writer);
+        let mut unique: Vec<FileInfo> = size_groups
+            .into_par_iter()
+            .flat_map_iter(|(_, files)| {
+                if files.len() == 1 {
+                    return files;
+                }
+                let mut hash_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+                for file in files {
+                    if let Ok(hash) = self.calculate_hash_cached(&file, writer.as_ref()) {
+                        hash_groups.entry(hash).or_default().push(file);
+                    }
+                }
+                hash_groups
+                    .into_values()
+                    .filter(|group| group.len() == 1)
+                    .flatten()
+                    .collect()
+            })
+            .collect();
+
+        if let Some(writer) = writer {
+            if let Err(e) = writer.finish() {
+                warn!("Failed to save hashes to cache: {}", e);
+            }
         }
 
-        let multi_progress = MultiProgress::new();
+        unique.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(unique)
+    }
+
+    /// Walk `roots` and collect candidate files into size groups, plus the
+    /// text files eligible for case-insensitive comparison
+    fn collect_files(
+        &self,
+        roots: &[ScanRoot],
+        multi_progress: &Bars,
+    ) -> Result<(SizeGroups, Vec<FileInfo>)> {
+        // Check if the roots exist first
+        for root in roots {
+            if !root.path.exists() {
+                return Err(DupCheckError::PathNotFound(root.path.clone()));
+            }
+        }
+        self.locked_files.lock().unwrap().clear();
+        self.scan_errors.lock().unwrap().clear();
+        self.dropped_groups.store(0, Ordering::Relaxed);
+        for counter in [
+            &self.counters.files,
+            &self.counters.bytes,
+            &self.counters.cache_hits,
+            &self.counters.cache_misses,
+            &self.counters.cache_hit_bytes,
+            &self.counters.cache_miss_bytes,
+            &self.counters.bytes_hashed,
+            &self.counters.dirs_listed,
+            &self.counters.dirs_unchanged,
+        ]
+        .into_iter()
+        .chain(&self.counters.skipped)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
 
         // File scanning progress
-        let scan_progress = multi_progress.add(ProgressBar::new_spinner());
+        let scan_progress = multi_progress.add_spinner();
         scan_progress.set_style(
             ProgressStyle::default_spinner()
                 .template("{spinner:.green} [{elapsed_precise}] {msg}")
@@ -47,137 +1617,792 @@ impl Scanner {
         );
         scan_progress.set_message("Collecting files...");
 
-        // First pass: count total files for progress bar
-        let total_files: u64 = WalkDir::new(path)
-            .into_iter()
-            .try_fold(0, |acc, entry| {
-                let entry = entry?;
-                Ok::<_, anyhow::Error>(if self.should_process_file(&entry) {
-                    acc + 1
-                } else {
-                    acc
-                })
-            })?;
+        // Walk and read metadata in parallel, then put the files in path
+        // order so the deduplication below doesn't depend on thread timing.
+        // Files found through a link come after the file itself, so the file
+        // is the one that stands for both.
+        let follow_links = self.options.symlinks == SymlinkPolicy::Follow
+            || (cfg!(windows) && self.options.follow_junctions);
+        let mut found = Vec::new();
+        for (index, root) in roots.iter().enumerate() {
+            let known = match &self.cache {
+                Some(cache) if self.options.incremental => {
+                    cache.dir_snapshots_under(&root.path).unwrap_or_else(|e| {
+                        warn!("Failed to read directory snapshots: {}", e);
+                        HashMap::new()
+                    })
+                }
+                _ => HashMap::new(),
+            };
+            let walk = Walk {
+                scanner: self,
+                root,
+                follow_links,
+                progress: &scan_progress,
+                found: Mutex::new(Vec::new()),
+                count: AtomicU64::new(0),
+                known,
+                snapshots: Mutex::new(Vec::new()),
+            };
+            found.extend(walk.run()?.into_iter().map(|found| (index, found)));
+        }
+        // A single walk never yields the same file twice
+        let check_seen = roots.len() > 1;
+        let mut candidates: Vec<Candidate> = found
+            .into_par_iter()
+            .filter_map(|(root, found)| match found {
+                Found::Entry(entry, metadata) => self.candidate(root, &entry, metadata, check_seen),
+                Found::Known(file) => self.known_candidate(root, file, check_seen),
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            (a.root, a.is_link, &a.file.path).cmp(&(b.root, b.is_link, &b.file.path))
+        });
 
-        scan_progress.set_message(format!("Found {} files to process", total_files));
+        scan_progress.set_message(format!("Found {} files to process", candidates.len()));
 
-        // Second pass: collect files into size groups with progress
+        // Count each physical file once and group by size
+        let mut seen = HashSet::new();
+        let mut links: HashMap<FileId, PathBuf> = HashMap::new();
         let mut size_groups: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+        let mut text_files: Vec<FileInfo> = Vec::new();
         let mut processed = 0;
-        for entry_result in WalkDir::new(path).into_iter() {
-            let entry = entry_result?;
-            if !self.should_process_file(&entry) {
-                continue;
+        for Candidate {
+            file, canonical, ..
+        } in candidates
+        {
+            if let Some(canonical) = canonical {
+                if !seen.insert(canonical) {
+                    debug!("Skipping {} (already collected)", file.path.display());
+                    continue;
+                }
+            }
+            // Hard links share their data, so only the first one stands for
+            // it. A link treated as a file is identified by the link itself.
+            if let Some(id) = file.file_id {
+                if let Some(first) = links.get(&id) {
+                    debug!(
+                        "Skipping {} (hard link to {})",
+                        file.path.display(),
+                        first.display()
+                    );
+                    continue;
+                }
+                links.insert(id, file.path.clone());
+            }
+            if self.options.text_case_insensitive && is_case_foldable(&file) {
+                text_files.push(file.clone());
             }
+            self.counters.bytes.fetch_add(file.size, Ordering::Relaxed);
+            size_groups.entry(file.size).or_default().push(file);
+            processed += 1;
+        }
 
-            let metadata = entry.metadata()?;
-            let size = metadata.len();
-            size_groups
-                .entry(size)
-                .or_default()
-                .push(FileInfo::new(entry.path().to_path_buf(), size));
+        scan_progress.finish_with_message(format!("Processed {} files", processed));
+        self.counters.files.store(processed, Ordering::Relaxed);
 
-            processed += 1;
-            if processed % 100 == 0 || processed == total_files {
-                scan_progress.set_message(format!("Processed {}/{} files", processed, total_files));
+        Ok((size_groups, text_files))
+    }
+
+    /// What grouping needs to know about a file the walk accepted with
+    /// `metadata`. With `check_seen`, the file's canonical path is resolved
+    /// as well.
+    fn candidate(
+        &self,
+        root: usize,
+        entry: &walkdir::DirEntry,
+        metadata: fs::Metadata,
+        check_seen: bool,
+    ) -> Option<Candidate> {
+        let read = || -> Result<Candidate> {
+            let canonical = if check_seen {
+                Some(self.canonical_path(entry)?)
+            } else {
+                None
+            };
+            let mut file = FileInfo::new(entry.path().to_path_buf(), metadata.len());
+            file.modified = metadata.modified().ok();
+            file.allocated = file_info::allocated_size(&metadata);
+            file.file_id = if self.is_link_as_file(entry) {
+                FileId::from_metadata(&entry.metadata()?)
+            } else {
+                FileId::from_metadata(&metadata)
+            };
+            Ok(Candidate {
+                root,
+                is_link: entry.path_is_symlink(),
+                file,
+                canonical,
+            })
+        };
+        // The file may have been removed since it was found
+        read()
+            .inspect_err(|e| self.record_error(entry.path(), e))
+            .ok()
+    }
+
+    /// Like [`Scanner::candidate`], for a file the walk knew without an
+    /// entry. Such files are never links.
+    fn known_candidate(&self, root: usize, file: FileInfo, check_seen: bool) -> Option<Candidate> {
+        let canonical = if check_seen {
+            match fs::canonicalize(&file.path) {
+                Ok(canonical) => Some(canonical),
+                Err(e) => {
+                    self.record_error(&file.path, e);
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+        Some(Candidate {
+            root,
+            is_link: false,
+            file,
+            canonical,
+        })
+    }
+
+    /// Whether a group is large enough to report, see [`ScanOptions::min_copies`]
+    fn has_min_copies(&self, files: &[FileInfo]) -> bool {
+        files.len() >= self.options.min_copies.max(2)
+    }
+
+    /// Apply [`ScanOptions::max_result_groups`] to a finished set of groups
+    fn cap_results(
+        &self,
+        groups: HashMap<String, Vec<FileInfo>>,
+    ) -> HashMap<String, Vec<FileInfo>> {
+        let mut results = TopGroups::new(self.options.max_result_groups);
+        results.extend(groups);
+        self.dropped_groups
+            .store(results.dropped(), Ordering::Relaxed);
+        results.into_groups()
+    }
+
+    /// Split one same-size group into groups of identical files.
+    ///
+    /// Files larger than [`QUICK_HASH_LEN`] are first compared by a hash of
+    /// their first bytes, and only files whose quick hashes collide are read
+    /// in full. Same-size files usually differ early, so most are never
+    /// read past their first block. Files from [`ScanOptions::mmap_threshold`]
+    /// up are then compared with each other instead of hashed.
+    fn hash_size_group(
+        &self,
+        files: Vec<FileInfo>,
+        writer: Option<&CacheWriter>,
+        progress: &HashProgress,
+    ) -> Vec<(String, Vec<FileInfo>)> {
+        let size = files[0].size;
+        let candidates = if size > QUICK_HASH_LEN {
+            let total = files.len();
+            let mut quick_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+            for file in files {
+                if let Ok(hash) = utils::calculate_partial_hash(&file.path, 0, QUICK_HASH_LEN) {
+                    self.count_hashed(QUICK_HASH_LEN);
+                    quick_groups.entry(hash).or_default().push(file);
+                }
+            }
+            quick_groups.retain(|_, group| group.len() > 1);
+            // Files ruled out by their first block are done with
+            let remaining: usize = quick_groups.values().map(Vec::len).sum();
+            progress.add(size * (total - remaining) as u64);
+
+            if self.compares_directly(size) {
+                return quick_groups
+                    .into_iter()
+                    .flat_map(|(hash, files)| {
+                        let bytes = size * files.len() as u64;
+                        let key = format!("{}{}:{}", COMPARED_PREFIX, size, hash);
+                        let groups = self.split_identical(key, files);
+                        progress.add(bytes);
+                        groups
+                    })
+                    .collect();
+            }
+            quick_groups.into_values().collect()
+        } else {
+            vec![files]
+        };
+
+        let mut hash_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+        for file in candidates.into_iter().flatten() {
+            let hash = self.calculate_hash_cached(&file, writer);
+            progress.add(size);
+            if let Ok(hash) = hash {
+                hash_groups.entry(hash).or_default().push(file);
             }
         }
+        hash_groups
+            .into_iter()
+            .filter(|(_, group)| group.len() > 1)
+            .collect()
+    }
 
-        scan_progress.finish_with_message(format!("Processed {} files", total_files));
+    /// Apply [`ScanOptions::verify`] to freshly hashed groups
+    fn verify_groups<I>(&self, groups: I) -> Vec<(String, Vec<FileInfo>)>
+    where
+        I: IntoIterator<Item = (String, Vec<FileInfo>)>,
+    {
+        if !self.options.verify {
+            return groups.into_iter().collect();
+        }
+        groups
+            .into_iter()
+            .flat_map(|(hash, files)| {
+                if hash.starts_with(COMPARED_PREFIX) {
+                    // Already compared byte by byte
+                    vec![(hash, files)]
+                } else {
+                    self.split_identical(hash, files)
+                }
+            })
+            .collect()
+    }
 
-        // Hash calculation progress
-        let hash_progress = multi_progress.add(ProgressBar::new_spinner());
-        hash_progress.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-        hash_progress.set_message("Analyzing potential duplicates...");
+    /// Whether files of this size are compared instead of hashed
+    fn compares_directly(&self, size: u64) -> bool {
+        self.options
+            .mmap_threshold
+            .is_some_and(|threshold| size > QUICK_HASH_LEN && size >= threshold)
+    }
 
-        // Count potential duplicates for progress
-        let potential_duplicates: usize = size_groups
-            .values()
+    /// Compare two files byte by byte, through memory maps from
+    /// [`ScanOptions::mmap_threshold`] up
+    fn files_equal(&self, a: &Path, b: &Path, size: u64) -> anyhow::Result<bool> {
+        if self
+            .options
+            .mmap_threshold
+            .is_some_and(|threshold| size >= threshold)
+        {
+            utils::files_equal_mapped(a, b)
+        } else {
+            utils::files_equal(a, b)
+        }
+        .inspect(|_| self.count_hashed(2 * size))
+        .and_then(|equal| {
+            if equal && self.options.compare_xattrs {
+                Ok(utils::xattr_digest(a)? == utils::xattr_digest(b)?)
+            } else {
+                Ok(equal)
+            }
+        })
+    }
+
+    fn count_hashed(&self, bytes: u64) {
+        self.counters
+            .bytes_hashed
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Split a group into sets of byte-identical files. Sets after the
+    /// first get a suffixed key; files that can't be read are dropped.
+    fn split_identical(&self, hash: String, files: Vec<FileInfo>) -> Vec<(String, Vec<FileInfo>)> {
+        let mut identical: Vec<Vec<FileInfo>> = Vec::new();
+        let mut remaining = files;
+        while remaining.len() > 1 {
+            let reference = remaining.remove(0);
+            let equal: Vec<anyhow::Result<bool>> = remaining
+                .par_iter()
+                .map(|file| self.files_equal(&reference.path, &file.path, file.size))
+                .collect();
+
+            let mut same = vec![reference];
+            let mut rest = Vec::new();
+            for (file, equal) in remaining.into_iter().zip(equal) {
+                match equal {
+                    Ok(true) => same.push(file),
+                    Ok(false) => {
+                        // Differing after the first block is expected when comparing
+                        if !hash.starts_with(COMPARED_PREFIX) {
+                            warn!(
+                                "{} has the same hash as {} but different content",
+                                file.path.display(),
+                                same[0].path.display()
+                            );
+                        }
+                        rest.push(file);
+                    }
+                    Err(e) => warn!("Could not verify {}: {}", file.path.display(), e),
+                }
+            }
+            identical.push(same);
+            remaining = rest;
+        }
+
+        identical
+            .into_iter()
             .filter(|files| files.len() > 1)
-            .map(|files| files.len())
-            .sum();
+            .enumerate()
+            .map(|(i, files)| {
+                let key = if i == 0 {
+                    hash.clone()
+                } else {
+                    format!("{}-{}", hash, i)
+                };
+                (key, files)
+            })
+            .collect()
+    }
 
-        hash_progress.set_message(format!(
-            "Found {} files in duplicate size groups",
-            potential_duplicates
-        ));
+    /// Run `hash` on a size group, unless `journal` already holds its
+    /// result from an interrupted scan, and journal what it finds
+    fn journaled<H>(
+        &self,
+        journal: Option<&ScanJournal>,
+        progress: &HashProgress,
+        size: u64,
+        files: Vec<FileInfo>,
+        hash: H,
+    ) -> Result<Vec<(String, Vec<FileInfo>)>>
+    where
+        H: FnOnce(Vec<FileInfo>) -> Result<Vec<(String, Vec<FileInfo>)>>,
+    {
+        let Some(journal) = journal else {
+            return hash(files);
+        };
 
-        // Process files in parallel and collect duplicates
-        let duplicates: HashMap<String, Vec<FileInfo>> = size_groups
+        let members = journal::fingerprint(&files);
+        if let Some(groups) = journal.recovered(size, &members, &files) {
+            debug!("Size group of {} bytes recovered from journal", size);
+            progress.add(size * files.len() as u64);
+            return Ok(groups);
+        }
+        let groups = hash(files)?;
+        // Journaling failures only cost the ability to resume
+        if let Err(e) = journal.record(size, &members, &groups) {
+            warn!("Scan journal: {}", e);
+        }
+        Ok(groups)
+    }
+
+    /// Hash one huge same-size group in parallel batches of `batch_size`,
+    /// moving each batch of hashed files into a [`SpillStore`] so that only
+    /// one batch, and then one duplicate group, is held in memory at a time.
+    /// Files keep the metadata they were collected with. The store is kept
+    /// in the cache database when there is one on disk.
+    fn hash_large_group(
+        &self,
+        size: u64,
+        files: Vec<FileInfo>,
+        batch_size: usize,
+        writer: Option<&CacheWriter>,
+        progress: &HashProgress,
+    ) -> Result<HashMap<String, Vec<FileInfo>>> {
+        debug!(
+            "Spilling size group of {} files ({} bytes each) to disk",
+            files.len(),
+            size
+        );
+        let mut spill = match self.cache.as_ref().and_then(|cache| cache.path()) {
+            Some(path) => SpillStore::in_database(path)?,
+            None => SpillStore::new()?,
+        };
+
+        let mut members = files.into_iter();
+        loop {
+            let batch: Vec<FileInfo> = members.by_ref().take(batch_size).collect();
+            if batch.is_empty() {
+                break;
+            }
+            if self.options.cancel.is_cancelled() {
+                return Err(DupCheckError::Cancelled);
+            }
+            let hashed: Vec<(String, FileInfo)> = batch
+                .into_par_iter()
+                .filter_map(|file| {
+                    let hash = self.calculate_hash_cached(&file, writer).ok();
+                    progress.add(size);
+                    hash.map(|hash| (hash, file))
+                })
+                .collect();
+            spill.insert(hashed)?;
+        }
+
+        let mut groups = HashMap::new();
+        spill.for_each_duplicate_group(size, |hash, group| {
+            groups.insert(hash, group);
+        })?;
+
+        Ok(groups)
+    }
+
+    /// Group same-size files in one directory whose names only differ by a
+    /// copy suffix, keeping only byte-identical copies. The un-suffixed file
+    /// comes first so it is kept as the original.
+    fn name_groups(
+        &self,
+        size_groups: HashMap<u64, Vec<FileInfo>>,
+    ) -> HashMap<String, Vec<FileInfo>> {
+        let mut candidates: HashMap<(u64, PathBuf, String), Vec<(bool, FileInfo)>> = HashMap::new();
+        for (size, files) in size_groups.into_iter().filter(|(_, files)| files.len() > 1) {
+            for file in files {
+                let Some((name, suffixed)) = names::original_name(&file.path) else {
+                    continue;
+                };
+                let dir = file
+                    .path
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default();
+                candidates
+                    .entry((size, dir, name))
+                    .or_default()
+                    .push((suffixed, file));
+            }
+        }
+
+        let writer = self.cache.clone().map(Cache::spawn_writer);
+        let groups = candidates
             .into_par_iter()
-            .filter(|(_, files)| files.len() > 1)
-            .map(|(_, files)| {
-                let mut hash_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
-                for file in files {
-                    if let Ok(hash) =
-                        Self::calculate_hash_cached(&file.path, file.size, self.cache.as_ref())
-                    {
-                        hash_groups.entry(hash).or_default().push(file);
+            .filter(|(_, files)| files.len() > 1 && files.iter().any(|(suffixed, _)| *suffixed))
+            .flat_map_iter(|((_, dir, name), files)| {
+                let mut by_hash: HashMap<String, Vec<(bool, FileInfo)>> = HashMap::new();
+                for (suffixed, file) in files {
+                    if let Ok(hash) = self.calculate_hash_cached(&file, writer.as_ref()) {
+                        by_hash.entry(hash).or_default().push((suffixed, file));
                     }
                 }
-                hash_groups
+                let original = dir.join(name);
+                by_hash
                     .into_iter()
-                    .filter(|(_, group)| group.len() > 1)
-                    .collect::<Vec<_>>()
+                    .filter(|(_, files)| files.len() > 1)
+                    .map(move |(hash, mut files)| {
+                        files.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+                        let key = format!("names:{}:{}", hash, original.display());
+                        (key, files.into_iter().map(|(_, file)| file).collect())
+                    })
             })
-            .inspect(|groups| {
-                if !groups.is_empty() {
-                    hash_progress.set_message(format!("Found {} duplicate groups", groups.len()));
-                }
+            .collect();
+
+        if let Some(writer) = writer {
+            if let Err(e) = writer.finish() {
+                warn!("Failed to save hashes to cache: {}", e);
+            }
+        }
+        groups
+    }
+
+    /// Group files by the hash of a byte range, ignoring everything else
+    fn range_groups(
+        &self,
+        files: Vec<FileInfo>,
+        range: ByteRange,
+    ) -> HashMap<String, Vec<FileInfo>> {
+        let hashed: Vec<(String, FileInfo)> = files
+            .into_par_iter()
+            .filter_map(|mut file| {
+                let hash =
+                    utils::calculate_partial_hash(&file.path, range.offset, range.len).ok()?;
+                self.count_hashed(file.size.saturating_sub(range.offset).min(range.len));
+                file.match_kind = MatchKind::ByteRange;
+                Some((hash, file))
+            })
+            .collect();
+
+        let mut groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+        for (hash, file) in hashed {
+            groups.entry(hash).or_default().push(file);
+        }
+        groups.retain(|_, files| files.len() > 1);
+        groups
+    }
+
+    /// Group text files by their case-folded content.
+    ///
+    /// Groups whose members are all byte-identical are already reported by the
+    /// regular pass, so only true case variants are returned.
+    fn case_insensitive_groups(files: Vec<FileInfo>) -> HashMap<String, Vec<FileInfo>> {
+        let hashed: Vec<(String, String, FileInfo)> = files
+            .into_par_iter()
+            .filter_map(|file| {
+                let data = fs::read(&file.path).ok()?;
+                let exact = utils::hash_bytes(&data);
+                let folded = utils::hash_bytes(&utils::fold_case(&data));
+                Some((folded, exact, file))
             })
-            .flatten()
             .collect();
 
-        hash_progress.finish_with_message(format!("Found {} duplicate groups", duplicates.len()));
+        let mut folded_groups: HashMap<String, Vec<(String, FileInfo)>> = HashMap::new();
+        for (folded, exact, file) in hashed {
+            folded_groups.entry(folded).or_default().push((exact, file));
+        }
+
+        folded_groups
+            .into_iter()
+            .filter(|(_, members)| {
+                members.len() > 1 && members.iter().any(|(exact, _)| *exact != members[0].0)
+            })
+            .map(|(folded, members)| {
+                let files = members
+                    .into_iter()
+                    .map(|(_, mut file)| {
+                        file.match_kind = MatchKind::CaseInsensitiveText;
+                        file
+                    })
+                    .collect();
+                (format!("text-ci:{}", folded), files)
+            })
+            .collect()
+    }
 
-        Ok(duplicates)
+    /// Whether `path` matches an `--exclude` pattern, by file name or full path
+    fn is_excluded(&self, path: &Path) -> bool {
+        let exclude = &self.options.exclude;
+        !exclude.is_empty()
+            && (path.file_name().is_some_and(|name| exclude.is_match(name))
+                || exclude.is_match(path))
     }
 
-    fn should_process_file(&self, entry: &walkdir::DirEntry) -> bool {
-        if !entry.file_type().is_file() {
+    /// Excluded directories below the root are pruned from the walk
+    /// entirely. Roots are always walked.
+    fn is_excluded_dir(&self, dir: &Path) -> bool {
+        if !self.is_excluded(dir) {
             return false;
         }
+        debug!("Skipping directory {} (excluded)", dir.display());
+        true
+    }
 
-        if utils::is_hidden(entry.path()) {
+    /// Whether the directory `dir` below the root is a reparse point that
+    /// isn't walked, see [`ScanOptions::follow_junctions`]
+    fn is_skipped_junction(&self, dir: &Path) -> bool {
+        if self.options.follow_junctions || !utils::is_directory_reparse_point(dir) {
             return false;
         }
+        debug!("Skipping {} (junction or reparse point)", dir.display());
+        true
+    }
 
-        if let Ok(metadata) = entry.metadata() {
-            let size = metadata.len();
-            if let Some(min_size) = self.min_size {
-                if size < min_size {
-                    return false;
-                }
-            }
-            if let Some(max_size) = self.max_size {
-                if size > max_size {
-                    return false;
-                }
+    /// Whether the file was modified between [`ScanOptions::newer_than`] and
+    /// [`ScanOptions::older_than`]. Files without a modification time only
+    /// pass when neither is set.
+    fn in_time_window(&self, modified: Option<SystemTime>) -> bool {
+        if self.options.newer_than.is_none() && self.options.older_than.is_none() {
+            return true;
+        }
+        let Some(modified) = modified else {
+            return false;
+        };
+        self.options
+            .newer_than
+            .is_none_or(|cutoff| modified > cutoff)
+            && self
+                .options
+                .older_than
+                .is_none_or(|cutoff| modified < cutoff)
+    }
+
+    /// Whether `entry` is a link scanned as a file of its own
+    fn is_link_as_file(&self, entry: &walkdir::DirEntry) -> bool {
+        self.options.symlinks == SymlinkPolicy::TreatAsFile && entry.path_is_symlink()
+    }
+
+    /// Metadata of the file `entry` is scanned as. Links treated as files
+    /// take the size and times of their target.
+    fn metadata(&self, entry: &walkdir::DirEntry) -> Result<fs::Metadata> {
+        if self.is_link_as_file(entry) {
+            Ok(fs::metadata(entry.path())?)
+        } else {
+            Ok(entry.metadata()?)
+        }
+    }
+
+    /// Path identifying `entry` across roots. Links treated as files are
+    /// files of their own, so only their directory is resolved.
+    fn canonical_path(&self, entry: &walkdir::DirEntry) -> Result<PathBuf> {
+        match (entry.path().parent(), self.is_link_as_file(entry)) {
+            (Some(parent), true) => Ok(fs::canonicalize(parent)?.join(entry.file_name())),
+            _ => Ok(fs::canonicalize(entry.path())?),
+        }
+    }
+
+    /// Whether [`ScanOptions::extensions`] and
+    /// [`ScanOptions::exclude_extensions`] let `path` through
+    fn has_wanted_extension(&self, path: &Path) -> bool {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        if let Some(wanted) = &self.options.extensions {
+            if !ext.as_ref().is_some_and(|ext| wanted.contains(ext)) {
+                return false;
             }
-            true
+        }
+        !ext.is_some_and(|ext| self.options.exclude_extensions.contains(&ext))
+    }
+
+    /// The metadata of the file at `entry` if it is a file that passes the
+    /// filters. It is read only once per file, here, and reused to group it.
+    fn should_process_file(
+        &self,
+        entry: &walkdir::DirEntry,
+        root: &ScanRoot,
+    ) -> Option<fs::Metadata> {
+        let is_file = if self.is_link_as_file(entry) {
+            fs::metadata(entry.path()).is_ok_and(|metadata| metadata.is_file())
         } else {
-            false
+            entry.file_type().is_file()
+        };
+        if !is_file {
+            return None;
         }
+
+        match self.check_filters(entry, root) {
+            Ok(metadata) => Some(metadata),
+            Err(reason) => {
+                self.count_skipped(entry.path(), reason);
+                None
+            }
+        }
+    }
+
+    /// Like [`Scanner::should_process_file`], for the file at `path` as
+    /// recorded in a directory snapshot
+    fn should_process_known(&self, path: &Path, entry: &SnapshotEntry, root: &ScanRoot) -> bool {
+        let checked = self
+            .check_path(path)
+            .and_then(|()| self.check_size_and_time(entry.size, entry.modified, root));
+        match checked {
+            Ok(()) => true,
+            Err(reason) => {
+                self.count_skipped(path, reason);
+                false
+            }
+        }
+    }
+
+    fn count_skipped(&self, path: &Path, reason: SkipReason) {
+        // Unreadable files were logged along with their error
+        if reason != SkipReason::Unreadable {
+            debug!("Skipping {} ({})", path.display(), reason.label());
+        }
+        self.counters.skipped[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The file's metadata, or the first filter that leaves out the file at
+    /// `entry`. Filters that only need the path run before it is stat'ed.
+    fn check_filters(
+        &self,
+        entry: &walkdir::DirEntry,
+        root: &ScanRoot,
+    ) -> Result<fs::Metadata, SkipReason> {
+        self.check_path(entry.path())?;
+        let metadata = match self.metadata(entry) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.record_error(entry.path(), e);
+                return Err(SkipReason::Unreadable);
+            }
+        };
+        self.check_size_and_time(metadata.len(), metadata.modified().ok(), root)?;
+        Ok(metadata)
+    }
+
+    /// The first filter that leaves out `path` by its name alone
+    fn check_path(&self, path: &Path) -> Result<(), SkipReason> {
+        if !self.options.include_hidden && utils::is_hidden(path) {
+            return Err(SkipReason::Hidden);
+        }
+        if self.is_excluded(path) {
+            return Err(SkipReason::Excluded);
+        }
+        if !self.has_wanted_extension(path) {
+            return Err(SkipReason::Extension);
+        }
+        Ok(())
+    }
+
+    /// The first filter that leaves out a file of `size` bytes, last
+    /// modified at `modified`, found under `root`
+    fn check_size_and_time(
+        &self,
+        size: u64,
+        modified: Option<SystemTime>,
+        root: &ScanRoot,
+    ) -> Result<(), SkipReason> {
+        if size == 0 && self.options.empty_files == EmptyFiles::Ignore {
+            return Err(SkipReason::Empty);
+        }
+        if root
+            .min_size
+            .or(self.options.min_size)
+            .is_some_and(|min_size| size < min_size)
+        {
+            return Err(SkipReason::TooSmall);
+        }
+        if root
+            .max_size
+            .or(self.options.max_size)
+            .is_some_and(|max_size| size > max_size)
+        {
+            return Err(SkipReason::TooLarge);
+        }
+        if !self.in_time_window(modified) {
+            return Err(SkipReason::OutsideTimeWindow);
+        }
+        Ok(())
+    }
+
+    /// Hash `file` for grouping: its content hash, followed by the digest of
+    /// its extended attributes with [`ScanOptions::compare_xattrs`]
+    fn calculate_hash_cached(
+        &self,
+        file: &FileInfo,
+        writer: Option<&CacheWriter>,
+    ) -> Result<String> {
+        let hash = self.content_hash_cached(file, writer)?;
+        if !self.options.compare_xattrs {
+            return Ok(hash);
+        }
+        // Attributes can change without touching the modification time, so
+        // they are read every time instead of being cached
+        let xattrs = utils::xattr_digest(&file.path)?;
+        Ok(format!("{}:xattrs:{}", hash, xattrs))
     }
 
-    fn calculate_hash_cached(path: &Path, size: u64, cache: Option<&Arc<Cache>>) -> Result<String> {
-        if let Some(cache) = cache {
-            if let Some(hash) = cache.get_hash(path, size)? {
+    /// Hash the content of `file`, reusing the cached hash while its size and
+    /// modification time are unchanged
+    fn content_hash_cached(&self, file: &FileInfo, writer: Option<&CacheWriter>) -> Result<String> {
+        let path = file.path.as_path();
+        let algorithm = self.options.hash_algorithm;
+        if let Some(cache) = &self.cache {
+            if let Some(hash) = cache.get_hash_with(path, file.size, file.modified, algorithm)? {
                 debug!("Cache hit for {}", path.display());
+                self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+                self.counters
+                    .cache_hit_bytes
+                    .fetch_add(file.size, Ordering::Relaxed);
                 return Ok(hash);
             }
+            self.counters.cache_misses.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .cache_miss_bytes
+                .fetch_add(file.size, Ordering::Relaxed);
         }
 
-        let hash = utils::calculate_hash(path)?;
+        if self.options.skip_locked && utils::is_locked(path) {
+            debug!("Skipping locked file {}", path.display());
+            self.locked_files.lock().unwrap().push(path.to_path_buf());
+            return Err(anyhow::anyhow!("File is locked: {}", path.display()).into());
+        }
+
+        let hash = utils::calculate_hash_with(path, algorithm)?;
+        self.count_hashed(file.size);
 
-        if let Some(cache) = cache {
-            cache.store_hash(path, size, &hash)?;
+        if let Some(writer) = writer {
+            writer.send(path, file.size, file.modified, algorithm, &hash);
         }
 
         Ok(hash)
     }
 }
+
+fn is_case_foldable(file: &FileInfo) -> bool {
+    file.size <= TEXT_CASE_INSENSITIVE_MAX_SIZE
+        && file
+            .path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false)
+}
@@ -0,0 +1,165 @@
+//! Disk-backed hash grouping for pathologically large size groups
+
+use crate::file_info::{FileId, FileInfo};
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, UNIX_EPOCH},
+};
+use tempfile::NamedTempFile;
+
+/// Tells apart the tables of the spill stores open at once in one database
+static NEXT_TABLE: AtomicU64 = AtomicU64::new(0);
+
+/// How long a spill into the cache database waits for the cache's own writes
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SQLite table of hashed files of one size group, each with the metadata
+/// it was collected with.
+///
+/// Used instead of an in-memory map when a single size group holds so many
+/// files that grouping them by hash in memory would be too expensive: files
+/// are moved into the store batch by batch as they are hashed, and only the
+/// members of duplicate groups are read back. The table lives in the cache
+/// database if there is one on disk, and in a temporary database otherwise;
+/// either way it is removed when the store is dropped.
+pub(crate) struct SpillStore {
+    conn: Connection,
+    table: String,
+    /// The temporary database, when not spilling into the cache's
+    file: Option<NamedTempFile>,
+}
+
+impl SpillStore {
+    /// Spill into a fresh temporary database
+    pub(crate) fn new() -> Result<Self> {
+        let file = NamedTempFile::new()?;
+        let conn = Connection::open(file.path())?;
+        conn.execute_batch(
+            "PRAGMA synchronous = OFF;
+             PRAGMA journal_mode = OFF;",
+        )?;
+        Self::with_connection(conn, Some(file))
+    }
+
+    /// Spill into a table of its own in the database at `path`, e.g. the
+    /// cache's
+    pub(crate) fn in_database(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Self::with_connection(conn, None)
+    }
+
+    fn with_connection(conn: Connection, file: Option<NamedTempFile>) -> Result<Self> {
+        let table = format!(
+            "spill_{}_{}",
+            std::process::id(),
+            NEXT_TABLE.fetch_add(1, Ordering::Relaxed)
+        );
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (
+                hash TEXT NOT NULL,
+                path BLOB NOT NULL,
+                modified INTEGER,
+                allocated INTEGER NOT NULL,
+                dev INTEGER,
+                ino INTEGER
+            );",
+            table
+        ))?;
+
+        Ok(SpillStore { conn, table, file })
+    }
+
+    /// Move one batch of hashed files into the store, in a single transaction
+    pub(crate) fn insert(&mut self, batch: Vec<(String, FileInfo)>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT INTO {} (hash, path, modified, allocated, dev, ino)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                self.table
+            ))?;
+            for (hash, file) in batch {
+                let modified = file
+                    .modified
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|age| age.as_nanos() as i64);
+                stmt.execute(params![
+                    hash,
+                    file.path.as_os_str().as_encoded_bytes(),
+                    modified,
+                    file.allocated as i64,
+                    file.file_id.map(|id| id.dev as i64),
+                    file.file_id.map(|id| id.ino as i64),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Call `on_group` for every hash shared by more than one file, with the
+    /// files of `size` bytes sharing it, ordered by path
+    pub(crate) fn for_each_duplicate_group(
+        &self,
+        size: u64,
+        mut on_group: impl FnMut(String, Vec<FileInfo>),
+    ) -> Result<()> {
+        self.conn
+            .execute_batch(&format!("CREATE INDEX {0}_hash ON {0} (hash)", self.table))?;
+
+        let mut hashes = self.conn.prepare(&format!(
+            "SELECT hash FROM {} GROUP BY hash HAVING COUNT(*) > 1",
+            self.table
+        ))?;
+        let mut members = self.conn.prepare(&format!(
+            "SELECT path, modified, allocated, dev, ino FROM {} WHERE hash = ? ORDER BY path",
+            self.table
+        ))?;
+
+        let duplicate_hashes = hashes
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for hash in duplicate_hashes {
+            let group = members
+                .query_map(params![hash], |row| {
+                    let path: Vec<u8> = row.get(0)?;
+                    // SAFETY: written by `insert` from `as_encoded_bytes`,
+                    // in this same process
+                    let path = unsafe { OsString::from_encoded_bytes_unchecked(path) };
+                    let mut file = FileInfo::new(PathBuf::from(path), size);
+                    file.modified = row
+                        .get::<_, Option<i64>>(1)?
+                        .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos as u64));
+                    file.allocated = row.get::<_, i64>(2)? as u64;
+                    let dev: Option<i64> = row.get(3)?;
+                    let ino: Option<i64> = row.get(4)?;
+                    file.file_id = dev.zip(ino).map(|(dev, ino)| FileId {
+                        dev: dev as u64,
+                        ino: ino as u64,
+                    });
+                    Ok(file)
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            on_group(hash, group);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        // A temporary database goes away with its file
+        if self.file.is_none() {
+            let _ = self
+                .conn
+                .execute_batch(&format!("DROP TABLE IF EXISTS {}", self.table));
+        }
+    }
+}
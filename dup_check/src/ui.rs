@@ -1,7 +1,20 @@
-use crate::file_info::FileInfo;
-use anyhow::Result;
-use console::Term;
-use std::collections::HashMap;
+use crate::{
+    duplicates,
+    file_info::FileInfo,
+    report::{self, ActionSavings, FolderBucket, FolderWaste, NestedDuplicate, SortKey},
+    undo::UndoLog,
+    utils,
+};
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 fn format_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -20,66 +33,866 @@ fn format_size(size: u64) -> String {
     }
 }
 
-pub fn display_duplicates(duplicates: &HashMap<String, Vec<FileInfo>>) {
-    if duplicates.is_empty() {
-        println!("\n✨ No duplicates found!");
-        return;
+/// How sizes are printed in text output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeFormat {
+    /// Scaled with units, e.g. `4.20 MB`
+    #[default]
+    Human,
+    /// Exact byte counts as plain integers
+    Bytes,
+}
+
+impl SizeFormat {
+    pub fn format(self, size: u64) -> String {
+        match self {
+            SizeFormat::Human => format_size(size),
+            SizeFormat::Bytes => size.to_string(),
+        }
+    }
+}
+
+/// How the text report marks kept and deletable files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Markers {
+    /// Emoji, for terminals
+    #[default]
+    Emoji,
+    /// Plain ASCII, for logs and dumb terminals
+    Ascii,
+}
+
+impl Markers {
+    /// ASCII when asked for with `--no-color`, through a non-empty `NO_COLOR`
+    /// variable, or when stdout isn't a terminal
+    pub fn detect(no_color: bool) -> Self {
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        if no_color || no_color_env || !std::io::stdout().is_terminal() {
+            Markers::Ascii
+        } else {
+            Markers::Emoji
+        }
     }
 
-    let term = Term::stdout();
-    let _ = term.clear_screen();
+    /// `emoji` or its `ascii` stand-in
+    pub fn pick(self, emoji: &'static str, ascii: &'static str) -> &'static str {
+        match self {
+            Markers::Emoji => emoji,
+            Markers::Ascii => ascii,
+        }
+    }
+}
+
+/// Settings for [`display_duplicates`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayOptions {
+    pub sizes: SizeFormat,
+    pub markers: Markers,
+    /// Order in which groups are listed and numbered
+    pub sort: SortKey,
+    /// Only print the totals, not the groups
+    pub summary_only: bool,
+}
+
+/// Write the human-readable report, as printed to the terminal
+pub fn display_duplicates<W: Write>(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DisplayOptions,
+    mut out: W,
+) -> std::io::Result<()> {
+    let DisplayOptions {
+        sizes,
+        markers,
+        sort,
+        summary_only,
+    } = *options;
+    if duplicates.is_empty() {
+        writeln!(out, "\n{}No duplicates found!", markers.pick("✨ ", ""))?;
+        return Ok(());
+    }
 
     let total_groups = duplicates.len();
     let total_files: usize = duplicates.values().map(|files| files.len()).sum();
     let total_wasted: u64 = duplicates
         .values()
-        .map(|files| files[0].size * (files.len() as u64 - 1))
+        .map(|files| duplicates::wasted_bytes(files))
         .sum();
 
-    println!("\n📊 Duplicate Files Summary");
-    println!("========================");
-    println!("🔍 Found {} duplicate groups", total_groups);
-    println!("📁 Total duplicate files: {}", total_files);
-    println!("💾 Wasted space: {}\n", format_size(total_wasted));
+    let bullet = |emoji| markers.pick(emoji, "");
+    writeln!(out, "\n{}Duplicate Files Summary", bullet("📊 "))?;
+    writeln!(out, "========================")?;
+    writeln!(
+        out,
+        "{}Found {} duplicate groups",
+        bullet("🔍 "),
+        total_groups
+    )?;
+    writeln!(
+        out,
+        "{}Total duplicate files: {}",
+        bullet("📁 "),
+        total_files
+    )?;
+    writeln!(
+        out,
+        "{}Wasted space: {}\n",
+        bullet("💾 "),
+        sizes.format(total_wasted)
+    )?;
+    if summary_only {
+        return Ok(());
+    }
+
+    let original = markers.pick("🔒", "[keep]");
+    let duplicate = markers.pick("📄", "[dup] ");
+    for (i, (_, files)) in report::sorted_groups(duplicates, sort)
+        .into_iter()
+        .enumerate()
+    {
+        let size = sizes.format(files[0].size);
+        if files[0].match_kind.is_exact() {
+            writeln!(out, "Group {} (Size: {})", i + 1, size)?;
+        } else {
+            writeln!(
+                out,
+                "Group {} (Size: {}) {} {}",
+                i + 1,
+                size,
+                markers.pick("⚠️ ", "WARNING:"),
+                files[0].match_kind.description()
+            )?;
+        }
+        writeln!(out, "-------------------")?;
 
-    for (i, (_, files)) in duplicates.iter().enumerate() {
-        let size = format_size(files[0].size);
-        println!("Group {} (Size: {})", i + 1, size);
-        println!("-------------------");
+        // Only worth pointing out when the copies' times actually differ
+        let newest = files.iter().filter_map(|file| file.modified).max();
+        let mixed = files.iter().any(|file| file.modified != newest);
 
         for (j, file) in files.iter().enumerate() {
-            let symbol = if j == 0 { "🔒" } else { "📄" };
-            println!("{} {}", symbol, file.path.display());
+            let symbol = if j == 0 { original } else { duplicate };
+            match file.modified {
+                Some(modified) => writeln!(
+                    out,
+                    "{} {}  (modified {} UTC{})",
+                    symbol,
+                    file.path.display(),
+                    utils::format_time(modified),
+                    if mixed && file.modified == newest {
+                        ", newest"
+                    } else {
+                        ""
+                    }
+                )?,
+                None => writeln!(out, "{} {}", symbol, file.path.display())?,
+            }
         }
-        println!();
+        writeln!(out)?;
     }
 
-    println!("Legend:");
-    println!("🔒 Original file (will be kept)");
-    println!("📄 Duplicate file (can be deleted)");
+    writeln!(out, "Legend:")?;
+    writeln!(out, "{} Original file (will be kept)", original)?;
+    writeln!(out, "{} Duplicate file (can be deleted)", duplicate)?;
+    Ok(())
+}
+
+/// One duplicate group as written by [`write_duplicates_json`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonGroup {
+    pub hash: String,
+    /// Size of each file in bytes
+    pub size: u64,
+    /// Bytes that deleting every file but the original would reclaim
+    pub wasted_bytes: u64,
+    /// Absolute paths, the original first
+    pub paths: Vec<PathBuf>,
+}
+
+/// Write the groups as a JSON array, most wasteful group first
+pub fn write_duplicates_json<W: Write>(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    sort: SortKey,
+    mut writer: W,
+) -> Result<()> {
+    let groups = report_groups(duplicates, sort)?;
+    serde_json::to_writer_pretty(&mut writer, &groups)?;
+    writeln!(writer)?;
+
+    Ok(())
 }
 
-pub fn delete_duplicates(duplicates: &HashMap<String, Vec<FileInfo>>) -> Result<()> {
+/// One file as written by [`write_duplicates_csv`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvRow {
+    /// Position of the group in the report, starting at 1
+    pub group_id: usize,
+    pub hash: String,
+    pub size_bytes: u64,
+    /// Absolute path
+    pub path: String,
+    /// Whether this file is the one kept when the group is cleaned up
+    pub is_original: bool,
+}
+
+/// Write one CSV row per file, with a header. Groups come in the same order
+/// as in [`write_duplicates_json`].
+pub fn write_duplicates_csv<W: Write>(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    sort: SortKey,
+    writer: W,
+) -> Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    for (index, group) in report_groups(duplicates, sort)?.into_iter().enumerate() {
+        for (position, path) in group.paths.iter().enumerate() {
+            csv.serialize(CsvRow {
+                group_id: index + 1,
+                hash: group.hash.clone(),
+                size_bytes: group.size,
+                path: path.to_string_lossy().into_owned(),
+                is_original: position == 0,
+            })?;
+        }
+    }
+    csv.flush()?;
+
+    Ok(())
+}
+
+/// Write the absolute path of every file [`delete_duplicates`] would delete
+/// with `options`, each followed by a NUL byte and nothing else, for
+/// `xargs -0`. Groups come in `sort` order.
+pub fn write_deletable_paths0<W: Write>(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    sort: SortKey,
+    options: &DeleteOptions,
+    mut writer: W,
+) -> Result<()> {
+    for (_, files) in report::sorted_groups(duplicates, sort) {
+        for index in (0..files.len()).filter(|&index| options.plans_to_delete(files, index)) {
+            let path = std::path::absolute(&files[index].path)?;
+            #[cfg(unix)]
+            writer.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+            #[cfg(not(unix))]
+            writer.write_all(path.to_string_lossy().as_bytes())?;
+            writer.write_all(b"\0")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups with absolute paths, in `sort` order
+fn report_groups(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    sort: SortKey,
+) -> Result<Vec<JsonGroup>> {
+    report::sorted_groups(duplicates, sort)
+        .into_iter()
+        .map(|(hash, files)| {
+            let paths = files
+                .iter()
+                .map(|file| std::path::absolute(&file.path))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Ok(JsonGroup {
+                hash: hash.clone(),
+                size: files[0].size,
+                wasted_bytes: duplicates::wasted_bytes(files),
+                paths,
+            })
+        })
+        .collect()
+}
+
+/// Print a single group the moment the scanner confirms it
+pub fn display_live_group(files: &[FileInfo], sizes: SizeFormat, markers: Markers) {
+    println!(
+        "{}Duplicate group found ({} files, {} each)",
+        markers.pick("🔔 ", ""),
+        files.len(),
+        sizes.format(files[0].size)
+    );
+    for file in files {
+        println!("   {}", file.path.display());
+    }
+}
+
+pub fn display_deepest<W: Write>(
+    nested: &[NestedDuplicate],
+    limit: usize,
+    markers: Markers,
+    mut out: W,
+) -> std::io::Result<()> {
+    if nested.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "\n{}Most Deeply Nested Duplicates",
+        markers.pick("🪆 ", "")
+    )?;
+    writeln!(out, "==============================")?;
+    for entry in nested.iter().take(limit) {
+        writeln!(
+            out,
+            "depth {:>3} (+{}) {}",
+            entry.depth,
+            entry.levels_below_shallowest,
+            entry.file.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn display_savings<W: Write>(
+    savings: &[ActionSavings],
+    sizes: SizeFormat,
+    markers: Markers,
+    mut out: W,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "\n{}Potential Savings by Action",
+        markers.pick("💰 ", "")
+    )?;
+    writeln!(out, "=============================")?;
+    for entry in savings {
+        writeln!(
+            out,
+            "{:<10} {:>12}  ({} files)",
+            entry.action.label(),
+            sizes.format(entry.reclaimable_bytes),
+            entry.eligible_files
+        )?;
+        if entry.blocked_files > 0 {
+            writeln!(
+                out,
+                "           {} files on another filesystem can't be linked",
+                entry.blocked_files
+            )?;
+        }
+        writeln!(
+            out,
+            "           {}{}",
+            markers.pick("⚠️  ", "Note: "),
+            entry.action.caveat()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn display_folder_waste<W: Write>(
+    waste: &[FolderWaste],
+    sizes: SizeFormat,
+    markers: Markers,
+    mut out: W,
+) -> std::io::Result<()> {
+    if waste.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "\n{}Wasted Space by Top-Level Folder",
+        markers.pick("📁 ", "")
+    )?;
+    writeln!(out, "==================================")?;
+    for entry in waste {
+        let name = match &entry.bucket {
+            FolderBucket::Folder(folder) => folder.display().to_string(),
+            FolderBucket::Root => "(scan root)".to_string(),
+            FolderBucket::CrossFolder => "(cross-folder)".to_string(),
+        };
+        writeln!(
+            out,
+            "{:<30} {:>12}  ({} groups)",
+            name,
+            sizes.format(entry.wasted_bytes),
+            entry.groups
+        )?;
+    }
+    Ok(())
+}
+
+/// Settings for [`delete_duplicates`]
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Also delete from groups that aren't full-content matches but allow it
+    /// when explicitly forced (see [`MatchKind::can_delete`](crate::file_info::MatchKind::can_delete))
+    pub force: bool,
+    /// When set, only files with one of these extensions (lowercase, without
+    /// the dot) are ever deleted; every other duplicate is kept
+    pub deletable_extensions: Option<Vec<String>>,
+    /// Re-check every deleted path afterwards and fail if any still exists
+    pub verify_deletion: bool,
+    /// Leave files that another process holds locked in place
+    pub skip_locked: bool,
+    /// Move files to the system trash instead of removing them permanently
+    pub trash: bool,
+    /// Only report what would be deleted; the filesystem is left untouched
+    pub dry_run: bool,
+    /// Log each deletion batch here so it can be reversed, see [`crate::undo`]
+    pub undo_dir: Option<PathBuf>,
+    /// How the freed space is reported
+    pub size_format: SizeFormat,
+    /// How progress and summary lines are marked
+    pub markers: Markers,
+}
+
+impl DeleteOptions {
+    /// Whether the extension allowlist permits deleting `path`
+    pub fn allows_extension(&self, path: &Path) -> bool {
+        let Some(allowed) = &self.deletable_extensions else {
+            return true;
+        };
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| allowed.contains(&ext))
+    }
+
+    /// Whether cleaning up `group` is planned to delete its file at `index`.
+    /// The original at index 0 is always kept, as are files that can't be
+    /// deleted for a reason known before the cleanup, e.g. being pinned.
+    pub fn plans_to_delete(&self, group: &[FileInfo], index: usize) -> bool {
+        let file = &group[index];
+        index > 0
+            && group[0].match_kind.can_delete(self.force)
+            && !file.pinned
+            && self.allows_extension(&file.path)
+    }
+
+    /// Why `file` must stay as it is, if it must
+    pub fn keep_reason(&self, file: &FileInfo) -> Option<&'static str> {
+        let pick = |emoji, ascii| Some(self.markers.pick(emoji, ascii));
+        if file.pinned {
+            pick("🔒 Kept (marked as original)", "Kept (marked as original)")
+        } else if self.skip_locked && utils::is_locked(&file.path) {
+            pick(
+                "🔒 Skipped (in use by another process)",
+                "Skipped (in use by another process)",
+            )
+        } else if !self.allows_extension(&file.path) {
+            pick(
+                "🛡️  Kept (extension not deletable)",
+                "Kept (extension not deletable)",
+            )
+        } else {
+            None
+        }
+    }
+}
+
+pub fn delete_duplicates(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DeleteOptions,
+) -> Result<()> {
+    let mut removed = Vec::new();
+    let result = delete_duplicates_with(duplicates, options, |path| {
+        if options.trash {
+            // Never fall back to remove_file: a failed move leaves the file alone
+            trash::delete(path).map_err(std::io::Error::other)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+        removed.push(path.to_path_buf());
+        Ok(())
+    });
+
+    if let Some(dir) = &options.undo_dir {
+        if !removed.is_empty() {
+            // The files are gone either way; a missing log mustn't fail the run
+            match UndoLog::new(options.trash, &removed).write(dir) {
+                Ok(_) if options.trash => println!(
+                    "{}Run with --undo to restore them",
+                    options.markers.pick("↩️  ", "")
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!(
+                    "{}Could not write undo log: {:#}",
+                    options.markers.pick("⚠️  ", "Warning: "),
+                    e
+                ),
+            }
+        }
+    }
+    result
+}
+
+/// Like [`delete_duplicates`], but removes each file with `remove`.
+/// With [`DeleteOptions::trash`] set, `remove` is expected to move the file
+/// to the trash and the output is worded accordingly. `remove` is never
+/// called in a [`DeleteOptions::dry_run`].
+pub fn delete_duplicates_with<R>(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DeleteOptions,
+    mut remove: R,
+) -> Result<()>
+where
+    R: FnMut(&Path) -> std::io::Result<()>,
+{
+    let markers = options.markers;
+    let mark = |emoji| markers.pick(emoji, "");
+    let mut deleted_paths = Vec::new();
     let mut total_deleted = 0;
     let mut space_freed = 0u64;
+    let (done, failed) = if options.trash {
+        (
+            markers.pick("🗑️  Moved to trash", "Moved to trash"),
+            markers.pick("❌ Failed to move to trash", "Failed to move to trash"),
+        )
+    } else {
+        (
+            markers.pick("✅ Deleted", "Deleted"),
+            markers.pick("❌ Failed to delete", "Failed to delete"),
+        )
+    };
 
     for files in duplicates.values() {
+        if !files[0].match_kind.can_delete(options.force) {
+            println!(
+                "{}Skipping group of {} files: {}",
+                mark("⏭️  "),
+                files.len(),
+                files[0].match_kind.description()
+            );
+            continue;
+        }
+
         // Skip the first file (original)
         for file in files.iter().skip(1) {
-            if std::fs::remove_file(&file.path).is_ok() {
+            if let Some(reason) = options.keep_reason(file) {
+                println!("{}: {}", reason, file.path.display());
+                continue;
+            }
+            if options.dry_run {
                 total_deleted += 1;
-                space_freed += file.size;
-                println!("✅ Deleted: {}", file.path.display());
-            } else {
-                println!("❌ Failed to delete: {}", file.path.display());
+                space_freed += file.allocated;
+                println!("{}Would delete: {}", mark("📝 "), file.path.display());
+                continue;
+            }
+            match remove(&file.path) {
+                Ok(()) => {
+                    deleted_paths.push(&file.path);
+                    total_deleted += 1;
+                    space_freed += file.allocated;
+                    println!("{}: {}", done, file.path.display());
+                }
+                Err(e) => println!("{}: {} ({})", failed, file.path.display(), e),
+            }
+        }
+    }
+
+    println!("\n{}Cleanup Summary", mark("🧹 "));
+    println!("================");
+    if options.dry_run {
+        println!(
+            "{}",
+            markers.pick(
+                "🧪 DRY RUN — no files deleted",
+                "DRY RUN - no files deleted"
+            )
+        );
+        println!(
+            "{}Would delete {} duplicate files",
+            mark("📝 "),
+            total_deleted
+        );
+        println!(
+            "{}Would free up {}",
+            mark("💾 "),
+            options.size_format.format(space_freed)
+        );
+        return Ok(());
+    }
+    if options.trash {
+        println!(
+            "{}Moved {} duplicate files to the trash",
+            mark("🗑️  "),
+            total_deleted
+        );
+        println!("{}Permanently deleted 0 files", mark("✨ "));
+    } else {
+        println!("{}Deleted {} duplicate files", mark("✨ "), total_deleted);
+    }
+    println!(
+        "{}Freed up {}",
+        mark("💾 "),
+        options.size_format.format(space_freed)
+    );
+
+    if options.verify_deletion {
+        // symlink_metadata so a dangling link left in place still counts
+        let survivors: Vec<_> = deleted_paths
+            .into_iter()
+            .filter(|path| path.symlink_metadata().is_ok())
+            .collect();
+        if !survivors.is_empty() {
+            for path in &survivors {
+                println!(
+                    "{}Still exists after deletion: {}",
+                    mark("❌ "),
+                    path.display()
+                );
+            }
+            return Err(anyhow::anyhow!(
+                "{} deleted file(s) still exist",
+                survivors.len()
+            ));
+        }
+        println!("{}Verified all deleted files are gone", mark("🔍 "));
+    }
+
+    Ok(())
+}
+
+/// Replace every duplicate with a hard link to its group's original, so all
+/// paths stay in place while sharing one copy of the data.
+///
+/// Duplicates are skipped the same way [`delete_duplicates`] skips them.
+/// Duplicates on another filesystem than their original, or on a filesystem
+/// without hard link support, are left untouched and reported.
+pub fn hardlink_duplicates(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DeleteOptions,
+) -> Result<()> {
+    link_duplicates(duplicates, options, LinkKind::Hard, false)
+}
+
+/// Like [`hardlink_duplicates`], with links of the given `kind`. See
+/// [`replace_with_link`] for `preserve_link_times`. When a replaced
+/// duplicate's modification time differed from its original's and the link
+/// doesn't keep it, the lost time is printed.
+pub fn link_duplicates(
+    duplicates: &HashMap<String, Vec<FileInfo>>,
+    options: &DeleteOptions,
+    kind: LinkKind,
+    preserve_link_times: bool,
+) -> Result<()> {
+    let markers = options.markers;
+    let mark = |emoji| markers.pick(emoji, "");
+    let links = match kind {
+        LinkKind::Hard => "hard links",
+        LinkKind::Symbolic => "symbolic links",
+    };
+    let keeps_times = kind == LinkKind::Symbolic && preserve_link_times;
+    let mut links_created = 0;
+    let mut space_freed = 0u64;
+    let mut unlinkable = 0;
+
+    for files in duplicates.values() {
+        if !files[0].match_kind.can_delete(options.force) {
+            println!(
+                "{}Skipping group of {} files: {}",
+                mark("⏭️  "),
+                files.len(),
+                files[0].match_kind.description()
+            );
+            continue;
+        }
+
+        let original = &files[0].path;
+        for file in files.iter().skip(1) {
+            if let Some(reason) = options.keep_reason(file) {
+                println!("{}: {}", reason, file.path.display());
+                continue;
+            }
+            if options.dry_run {
+                links_created += 1;
+                space_freed += file.allocated;
+                println!("{}Would link: {}", mark("📝 "), file.path.display());
+                continue;
+            }
+            match replace_with_link(original, &file.path, kind, preserve_link_times) {
+                Ok(outcome) => {
+                    links_created += 1;
+                    space_freed += file.allocated;
+                    println!(
+                        "{}Linked: {} -> {}",
+                        mark("🔗 "),
+                        file.path.display(),
+                        original.display()
+                    );
+                    if let Some(replaced) = outcome.replaced_mtime.filter(|_| !keeps_times) {
+                        println!(
+                            "   replaced copy was modified {} UTC",
+                            utils::format_time(replaced)
+                        );
+                    }
+                }
+                Err(e) => match unlinkable_reason(&e, kind) {
+                    Some(reason) => {
+                        unlinkable += 1;
+                        println!(
+                            "{}Skipped ({}): {}",
+                            mark("⏭️  "),
+                            reason,
+                            file.path.display()
+                        );
+                    }
+                    None => println!(
+                        "{}Failed to link: {} ({:#})",
+                        mark("❌ "),
+                        file.path.display(),
+                        e
+                    ),
+                },
             }
         }
     }
 
-    println!("\n🧹 Cleanup Summary");
+    println!("\n{}Cleanup Summary", mark("🧹 "));
     println!("================");
-    println!("✨ Deleted {} duplicate files", total_deleted);
-    println!("💾 Freed up {}", format_size(space_freed));
+    if options.dry_run {
+        println!(
+            "{}",
+            markers.pick("🧪 DRY RUN — no files linked", "DRY RUN - no files linked")
+        );
+        println!("{}Would create {} {}", mark("📝 "), links_created, links);
+        println!(
+            "{}Would free up {}",
+            mark("💾 "),
+            options.size_format.format(space_freed)
+        );
+        return Ok(());
+    }
+    println!("{}Created {} {}", mark("🔗 "), links_created, links);
+    println!(
+        "{}Freed up {}",
+        mark("💾 "),
+        options.size_format.format(space_freed)
+    );
+    if unlinkable > 0 {
+        println!(
+            "{}{} duplicates could not be replaced with {} and were left in place",
+            mark("⏭️  "),
+            unlinkable,
+            links
+        );
+    }
 
     Ok(())
 }
+
+/// Why a link of this `kind` can't be created at all, as opposed to failing
+/// for this file only
+fn unlinkable_reason(error: &anyhow::Error, kind: LinkKind) -> Option<&'static str> {
+    // ERROR_NOT_SAME_DEVICE, which std doesn't map to CrossesDevices
+    #[cfg(windows)]
+    const NOT_SAME_DEVICE: i32 = 17;
+
+    let error = error.downcast_ref::<std::io::Error>()?;
+    match (error.kind(), kind) {
+        (std::io::ErrorKind::CrossesDevices, _) => Some("different filesystem"),
+        (std::io::ErrorKind::Unsupported, LinkKind::Hard) => Some("hard links not supported"),
+        (std::io::ErrorKind::Unsupported, LinkKind::Symbolic) => {
+            Some("symbolic links not supported")
+        }
+        #[cfg(windows)]
+        _ if error.raw_os_error() == Some(NOT_SAME_DEVICE) => Some("different filesystem"),
+        _ => None,
+    }
+}
+
+/// Modification times closer than this are treated as the same, since some
+/// filesystems store them with coarse resolution
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// How a duplicate is replaced by a link to its original
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Hard,
+    Symbolic,
+}
+
+/// Outcome of [`replace_with_link`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkOutcome {
+    /// Modification time of the replaced duplicate, if it differed from the
+    /// original's. Linking discards it, so callers may want to record it.
+    pub replaced_mtime: Option<SystemTime>,
+}
+
+/// Replace `duplicate` with a link to `original`.
+///
+/// The original's access and modification times are left as they were: they
+/// are checked after linking and restored if the platform touched them. With
+/// `preserve_link_times`, a symlink additionally takes over the duplicate's
+/// former timestamps (set on the link itself, not its target). If creating the
+/// link fails, the duplicate is put back in place.
+pub fn replace_with_link(
+    original: &Path,
+    duplicate: &Path,
+    kind: LinkKind,
+    preserve_link_times: bool,
+) -> Result<LinkOutcome> {
+    let original_meta =
+        fs::metadata(original).with_context(|| format!("Failed to read {}", original.display()))?;
+    let duplicate_meta = fs::symlink_metadata(duplicate)
+        .with_context(|| format!("Failed to read {}", duplicate.display()))?;
+    let original_atime = FileTime::from_last_access_time(&original_meta);
+    let original_mtime = FileTime::from_last_modification_time(&original_meta);
+
+    // Move the duplicate aside first so a failed link never loses data
+    let mut backup = duplicate.as_os_str().to_owned();
+    backup.push(".dupcheck-tmp");
+    let backup = PathBuf::from(backup);
+    fs::rename(duplicate, &backup)
+        .with_context(|| format!("Failed to move {} aside", duplicate.display()))?;
+
+    let linked = match kind {
+        LinkKind::Hard => fs::hard_link(original, duplicate),
+        LinkKind::Symbolic => {
+            std::path::absolute(original).and_then(|target| symlink(&target, duplicate))
+        }
+    };
+    if let Err(e) = linked {
+        fs::rename(&backup, duplicate)
+            .with_context(|| format!("Failed to restore {}", duplicate.display()))?;
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to link {} to {}",
+                duplicate.display(),
+                original.display()
+            )
+        });
+    }
+    fs::remove_file(&backup).with_context(|| format!("Failed to remove {}", backup.display()))?;
+
+    let after = fs::metadata(original)?;
+    if FileTime::from_last_modification_time(&after) != original_mtime
+        || FileTime::from_last_access_time(&after) != original_atime
+    {
+        filetime::set_file_times(original, original_atime, original_mtime)
+            .with_context(|| format!("Failed to restore times of {}", original.display()))?;
+    }
+
+    if preserve_link_times && kind == LinkKind::Symbolic {
+        filetime::set_symlink_file_times(
+            duplicate,
+            FileTime::from_last_access_time(&duplicate_meta),
+            FileTime::from_last_modification_time(&duplicate_meta),
+        )
+        .with_context(|| format!("Failed to set times of link {}", duplicate.display()))?;
+    }
+
+    let replaced_mtime = duplicate_meta.modified().ok().filter(|replaced| {
+        original_meta.modified().ok().is_some_and(|kept| {
+            let delta = replaced
+                .duration_since(kept)
+                .or_else(|_| kept.duration_since(*replaced))
+                .unwrap_or_default();
+            delta > MTIME_TOLERANCE
+        })
+    });
+
+    Ok(LinkOutcome { replaced_mtime })
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
@@ -0,0 +1,258 @@
+//! Undo logs for deletion batches.
+//!
+//! Every batch of deletions writes a timestamped log of what it removed, so
+//! the most recent batch can be reversed with `--undo`. Files moved to the
+//! trash are restored from it; a batch deleted permanently is still logged,
+//! so `--undo` can say that it can't be reversed instead of silently
+//! reaching for an older batch.
+
+use anyhow::{bail, Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOG_PREFIX: &str = "deletions-";
+
+/// One deleted file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    /// Where the file was before it was deleted
+    pub path: PathBuf,
+    /// Identifies the file in the trash: the `.trashinfo` path on Linux, the
+    /// shell item name on Windows. Unknown for permanent deletions and where
+    /// the trash can't be listed.
+    pub trash_id: Option<String>,
+}
+
+/// A deletion batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoLog {
+    /// Whether the files were moved to the trash rather than deleted
+    pub trash: bool,
+    /// Seconds since the Unix epoch
+    pub deleted_at: u64,
+    pub files: Vec<UndoEntry>,
+}
+
+/// What [`undo_latest`] did
+#[derive(Debug, Default)]
+pub struct UndoSummary {
+    pub restored: Vec<PathBuf>,
+    /// Files that are no longer in the trash
+    pub missing: Vec<PathBuf>,
+    /// Files that couldn't be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl UndoLog {
+    /// Log a batch deleted just now. Relative paths are made absolute.
+    pub fn new(trash: bool, paths: &[PathBuf]) -> Self {
+        let files: Vec<UndoEntry> = paths
+            .iter()
+            .map(|path| UndoEntry {
+                path: std::path::absolute(path).unwrap_or_else(|_| path.clone()),
+                trash_id: None,
+            })
+            .collect();
+        let mut log = UndoLog {
+            trash,
+            deleted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|age| age.as_secs())
+                .unwrap_or(0),
+            files,
+        };
+        if trash {
+            log.find_trash_ids();
+        }
+        log
+    }
+
+    /// Look up where each file ended up in the trash. Files deleted more than
+    /// once map to the most recently trashed item.
+    #[cfg(any(
+        windows,
+        all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        )
+    ))]
+    fn find_trash_ids(&mut self) {
+        let items = match trash::os_limited::list() {
+            Ok(items) => items,
+            Err(e) => {
+                log::warn!("Could not list the trash: {}", e);
+                return;
+            }
+        };
+        for entry in &mut self.files {
+            entry.trash_id = items
+                .iter()
+                .filter(|item| item.original_path() == entry.path)
+                .max_by_key(|item| item.time_deleted)
+                .map(|item| item.id.to_string_lossy().into_owned());
+        }
+    }
+
+    #[cfg(not(any(
+        windows,
+        all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        )
+    )))]
+    fn find_trash_ids(&mut self) {}
+
+    /// Write the log to a new timestamped file in `dir`, never replacing an
+    /// existing log
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let data = serde_json::to_string_pretty(self)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|age| age.as_millis())
+            .unwrap_or(0);
+
+        // Zero-padded, so file names sort chronologically
+        for attempt in 0u128.. {
+            let path = dir.join(format!("{}{:020}.json", LOG_PREFIX, millis + attempt));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(data.as_bytes())
+                        .with_context(|| format!("Failed to write undo log {}", path.display()))?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to create undo log {}", path.display()))
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read undo log {}", path.display()))?;
+        serde_json::from_str(&data).with_context(|| format!("Invalid undo log {}", path.display()))
+    }
+}
+
+pub fn default_dir() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("com", "dupcheck", "DupCheck")
+        .ok_or_else(|| anyhow::anyhow!("Could not determine project directories"))?;
+
+    Ok(project_dirs.cache_dir().join("undo"))
+}
+
+/// The most recent undo log in `dir`, if there is one
+pub fn latest(dir: &Path) -> Result<Option<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut latest = None;
+    for entry in entries {
+        let path = entry?.path();
+        let is_log = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(".json"));
+        if is_log && latest.as_ref().is_none_or(|latest| path > *latest) {
+            latest = Some(path);
+        }
+    }
+    Ok(latest)
+}
+
+/// Restore the files of the most recent deletion batch logged in `dir` from
+/// the trash. Fails if there's nothing to undo or the batch was deleted
+/// permanently. The log is removed once every file is back or gone from the
+/// trash, so the next undo reaches the batch before it.
+pub fn undo_latest(dir: &Path) -> Result<UndoSummary> {
+    let Some(path) = latest(dir)? else {
+        bail!("There are no deletions to undo");
+    };
+    let log = UndoLog::load(&path)?;
+    if !log.trash {
+        bail!(
+            "The last deletion of {} files was permanent, so it can't be undone. \
+             Use --trash to be able to undo deletions",
+            log.files.len()
+        );
+    }
+
+    let summary = restore(&log)?;
+    if summary.failed.is_empty() {
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove undo log {}", path.display()))?;
+    }
+    Ok(summary)
+}
+
+#[cfg(any(
+    windows,
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore(log: &UndoLog) -> Result<UndoSummary> {
+    let items = trash::os_limited::list().context("Failed to list the trash")?;
+    let mut summary = UndoSummary::default();
+
+    for entry in &log.files {
+        let item = match &entry.trash_id {
+            Some(id) => items.iter().find(|item| item.id.to_string_lossy() == *id),
+            None => items
+                .iter()
+                .filter(|item| item.original_path() == entry.path)
+                .max_by_key(|item| item.time_deleted),
+        };
+        let Some(item) = item else {
+            summary.missing.push(entry.path.clone());
+            continue;
+        };
+
+        // One at a time, so a file that's in the way doesn't stop the rest
+        match trash::os_limited::restore_all([item.clone()]) {
+            Ok(()) => summary.restored.push(entry.path.clone()),
+            Err(trash::Error::RestoreCollision { .. }) => summary.failed.push((
+                entry.path.clone(),
+                "another file now exists at this path".to_string(),
+            )),
+            Err(e) => summary.failed.push((entry.path.clone(), e.to_string())),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore(_log: &UndoLog) -> Result<UndoSummary> {
+    bail!(
+        "Restoring from the trash isn't supported on this platform; use the system's trash instead"
+    )
+}
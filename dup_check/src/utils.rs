@@ -1,17 +1,21 @@
 use anyhow::Result;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, Read, Seek, SeekFrom},
     path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use xxhash_rust::xxh3::Xxh3;
 
-#[cfg(windows)]
+#[cfg(all(windows, feature = "windows-hidden"))]
 use windows::Win32::Storage::FileSystem::{GetFileAttributesW, FILE_ATTRIBUTE_HIDDEN};
 
 const BUFFER_SIZE: usize = 1024 * 1024; // 1MB buffer
 
-#[cfg(windows)]
+#[cfg(all(windows, feature = "windows-hidden"))]
 pub fn is_hidden(path: &Path) -> bool {
     let wide_path: Vec<u16> = path
         .to_string_lossy()
@@ -28,7 +32,8 @@ pub fn is_hidden(path: &Path) -> bool {
     }
 }
 
-#[cfg(unix)]
+/// Name-based check, also used on Windows when built without `windows-hidden`
+#[cfg(any(unix, all(windows, not(feature = "windows-hidden"))))]
 pub fn is_hidden(path: &Path) -> bool {
     path.file_name()
         .and_then(|name| name.to_str())
@@ -41,11 +46,164 @@ pub fn is_hidden(_path: &Path) -> bool {
     false // Default implementation for other platforms
 }
 
+/// Whether another process holds `path` in a way that makes reading or
+/// deleting it fail: an exclusive lock, or on Windows an open handle that
+/// doesn't share access.
+///
+/// On Unix this only sees advisory locks taken with `flock`; `fcntl`
+/// record locks and files that are merely open elsewhere go unnoticed.
+pub fn is_locked(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+
+        if let Err(e) = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(path)
+        {
+            return e.raw_os_error() == Some(ERROR_SHARING_VIOLATION);
+        }
+    }
+
+    match File::open(path) {
+        Ok(file) => matches!(
+            file.try_lock_shared(),
+            Err(std::fs::TryLockError::WouldBlock)
+        ),
+        Err(_) => false,
+    }
+}
+
+/// Whether `path` itself is a directory reparse point on Windows: a
+/// junction, a mount point or a directory symlink. Always false elsewhere.
+pub fn is_directory_reparse_point(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+        let wanted = FILE_ATTRIBUTE_DIRECTORY | FILE_ATTRIBUTE_REPARSE_POINT;
+        std::fs::symlink_metadata(path)
+            .is_ok_and(|metadata| metadata.file_attributes() & wanted == wanted)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Content hash algorithms selectable with `--hash-algo`.
+///
+/// SHA-256 is the default. BLAKE3 and XXH3 are much faster; XXH3 is not
+/// cryptographic, which is fine for finding duplicates on your own disks.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+    #[value(name = "xxhash")]
+    #[serde(rename = "xxhash")]
+    XxHash,
+}
+
+impl HashAlgorithm {
+    /// Name stored alongside cached hashes
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::XxHash => "xxhash",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::XxHash,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.name() == name)
+    }
+}
+
+/// Incremental state of any [`HashAlgorithm`]
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    XxHash(Box<Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::XxHash => Hasher::XxHash(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::XxHash(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::XxHash(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
+}
+
+/// SHA-256 of a file's content
 pub fn calculate_hash(path: &Path) -> Result<String> {
+    calculate_hash_with(path, HashAlgorithm::Sha256)
+}
+
+pub fn calculate_hash_with(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
     let file = File::open(path)?;
-    let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
-    let mut hasher = Sha256::new();
-    let mut buffer = vec![0; BUFFER_SIZE];
+    let len = file.metadata()?.len();
+    hash_stream(file, algorithm, buffer_size_for(len))
+}
+
+/// Read buffer size for a file of `len` bytes: just large enough for small
+/// files, so many threads hashing tiny files don't each hold a full buffer
+pub fn buffer_size_for(len: u64) -> usize {
+    len.checked_next_power_of_two()
+        .map_or(BUFFER_SIZE, |size| size.min(BUFFER_SIZE as u64) as usize)
+}
+
+/// Hash at most `len` bytes starting at `offset`. Files shorter than
+/// `offset + len` contribute whatever bytes they have in that window.
+pub fn calculate_partial_hash(path: &Path, offset: u64, len: u64) -> Result<String> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    hash_stream(file.take(len), HashAlgorithm::Sha256, buffer_size_for(len))
+}
+
+/// Hash everything `reader` yields, e.g. stdin, the same way files are hashed
+pub fn hash_reader<R: Read>(reader: R, algorithm: HashAlgorithm) -> Result<String> {
+    hash_stream(reader, algorithm, BUFFER_SIZE)
+}
+
+fn hash_stream<R: Read>(reader: R, algorithm: HashAlgorithm, buffer_size: usize) -> Result<String> {
+    let mut reader = BufReader::with_capacity(buffer_size, reader);
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; buffer_size];
 
     loop {
         let bytes_read = reader.read(&mut buffer)?;
@@ -55,7 +213,177 @@ pub fn calculate_hash(path: &Path) -> Result<String> {
         hasher.update(&buffer[..bytes_read]);
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    Ok(hasher.finalize())
+}
+
+/// Whether the two files have exactly the same content, compared byte by byte
+pub fn files_equal(a: &Path, b: &Path) -> Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let len = a.metadata()?.len();
+    if len != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let buffer_size = buffer_size_for(len);
+    let (mut buffer_a, mut buffer_b) = (vec![0; buffer_size], vec![0; buffer_size]);
+    loop {
+        let read_a = read_full(&mut a, &mut buffer_a)?;
+        let read_b = read_full(&mut b, &mut buffer_b)?;
+        if buffer_a[..read_a] != buffer_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Like [`files_equal`], but compares memory-mapped contents instead of
+/// reading through buffers. Faster for very large files, as nothing is
+/// copied; falls back to [`files_equal`] if either file can't be mapped.
+pub fn files_equal_mapped(a: &Path, b: &Path) -> Result<bool> {
+    let (file_a, file_b) = (File::open(a)?, File::open(b)?);
+    if file_a.metadata()?.len() != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    // SAFETY: the maps are only read while comparing. A file truncated by
+    // another process meanwhile can fault the read, the same caveat every
+    // mmap-based reader has.
+    let maps = unsafe { (Mmap::map(&file_a), Mmap::map(&file_b)) };
+    match maps {
+        (Ok(map_a), Ok(map_b)) => {
+            #[cfg(unix)]
+            for map in [&map_a, &map_b] {
+                let _ = map.advise(memmap2::Advice::Sequential);
+            }
+            Ok(map_a[..] == map_b[..])
+        }
+        _ => files_equal(a, b),
+    }
+}
+
+/// Read until `buffer` is full or the end of the file
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+pub fn hash_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Lowercase text for case-insensitive comparison. Valid UTF-8 gets full
+/// Unicode lowercasing; anything else falls back to ASCII.
+pub fn fold_case(data: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_lowercase().into_bytes(),
+        Err(_) => data.to_ascii_lowercase(),
+    }
+}
+
+/// Digest of a file's extended attributes, names and values alike, so two
+/// files with the same attributes get the same digest. A file without any
+/// gets a fixed digest.
+///
+/// Which attributes are visible depends on the platform: Linux lists the
+/// `user.*` namespace (plus `security.*` and others where readable), macOS
+/// every attribute, including Finder and quarantine metadata.
+#[cfg(unix)]
+pub fn xattr_digest(path: &Path) -> Result<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut names: Vec<_> = xattr::list(path)?.collect();
+    names.sort();
+    let mut data = Vec::new();
+    for name in names {
+        let value = xattr::get(path, &name)?.unwrap_or_default();
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+        data.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        data.extend_from_slice(&value);
+    }
+    Ok(hash_bytes(&data))
+}
+
+/// Extended attributes aren't read on this platform; Windows alternate data
+/// streams are not supported, so every file gets the same digest
+#[cfg(not(unix))]
+pub fn xattr_digest(_path: &Path) -> Result<String> {
+    Ok(hash_bytes(&[]))
+}
+
+/// A point in time as `YYYY-MM-DD HH:MM:SS` in UTC. Times before the Unix
+/// epoch are clamped to it.
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|age| age.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, rest) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // Inverse of the days-since-epoch calculation for a proleptic Gregorian date
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+/// Parse a duration such as '30d', '12h' or '2w'. The unit is one of s, m,
+/// h, d, w or y (365 days) and case-insensitive; it is required. Every
+/// time-based option uses this grammar.
+pub fn parse_duration(duration: &str) -> Result<Duration> {
+    let duration = duration.trim().to_lowercase();
+    let Some(unit) = duration.chars().last().filter(char::is_ascii_alphabetic) else {
+        anyhow::bail!(
+            "Duration needs a unit (s, m, h, d, w or y), e.g. '{}d'",
+            duration
+        );
+    };
+    let number = &duration[..duration.len() - 1];
+    let count: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid number: '{}'", number))?;
+
+    let seconds: u64 = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        'y' => 365 * 24 * 60 * 60,
+        _ => anyhow::bail!("Invalid duration unit: '{}' (use s, m, h, d, w or y)", unit),
+    };
+    count
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow::anyhow!("Duration too large: {}", duration))
 }
 
 pub fn format_size(size: u64) -> String {
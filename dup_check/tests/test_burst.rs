@@ -0,0 +1,61 @@
+use dup_check::{
+    burst::{self, BurstWindow},
+    file_info::{FileInfo, MatchKind},
+};
+use filetime::FileTime;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn create_shot(dir: &TempDir, name: &str, size: usize, taken: i64) -> FileInfo {
+    let path = dir.path().join(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, vec![b'x'; size]).unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_unix_time(taken, 0)).unwrap();
+    FileInfo::new(path, size as u64)
+}
+
+#[test]
+fn test_burst_clusters_close_timestamps() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = vec![
+        create_shot(&temp_dir, "IMG_001.jpg", 1000, 1_000_000),
+        create_shot(&temp_dir, "IMG_002.jpg", 1010, 1_000_002),
+        create_shot(&temp_dir, "IMG_003.JPG", 990, 1_000_004),
+        // Too late to join the burst
+        create_shot(&temp_dir, "IMG_004.jpg", 1000, 1_000_100),
+        // Not a photo
+        create_shot(&temp_dir, "notes.txt", 1000, 1_000_001),
+        // Same moment, different directory
+        create_shot(&temp_dir, "other/IMG_005.jpg", 1000, 1_000_001),
+        // Same moment, very different size
+        create_shot(&temp_dir, "IMG_006.jpg", 50_000, 1_000_003),
+    ];
+
+    let window = BurstWindow {
+        seconds: 3,
+        size_delta: 100,
+    };
+    let groups = burst::burst_groups(files, &window);
+
+    assert_eq!(groups.len(), 1, "Only one burst should be found");
+    let burst = groups.values().next().unwrap();
+    let names: Vec<PathBuf> = burst
+        .iter()
+        .map(|file| PathBuf::from(file.path.file_name().unwrap()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            PathBuf::from("IMG_001.jpg"),
+            PathBuf::from("IMG_002.jpg"),
+            PathBuf::from("IMG_003.JPG")
+        ],
+        "Shots are chained in capture order"
+    );
+    assert!(burst.iter().all(|file| file.match_kind == MatchKind::Burst));
+    assert!(
+        !MatchKind::Burst.can_delete(true),
+        "Bursts are never deleted"
+    );
+}
@@ -1,7 +1,12 @@
-use dup_check::cache::Cache;
+use dup_check::cache::{Cache, CachePragma, DirSnapshot, SnapshotEntry};
+use dup_check::cancel::CancellationToken;
+use dup_check::error::DupCheckError;
+use dup_check::file_info::FileId;
+use dup_check::{scanner::ScanOptions, utils, utils::HashAlgorithm, Scanner};
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tempfile::TempDir;
 
 /// Helper function to create a temporary file with specific content
@@ -17,15 +22,15 @@ fn test_cache_store_and_retrieve() {
     let temp_dir = TempDir::new().unwrap();
     let file_path = create_temp_file(&temp_dir, "test.txt", b"Hello, World!");
 
-    let cache = Cache::new().unwrap();
+    let cache = Cache::in_memory().unwrap();
     let hash = "test_hash".to_string();
     let size = 13;
 
     // Store hash
-    cache.store_hash(&file_path, size, &hash).unwrap();
+    cache.store_hash(&file_path, size, None, &hash).unwrap();
 
     // Retrieve hash
-    let retrieved = cache.get_hash(&file_path, size).unwrap();
+    let retrieved = cache.get_hash(&file_path, size, None).unwrap();
     assert_eq!(
         retrieved,
         Some(hash),
@@ -33,19 +38,43 @@ fn test_cache_store_and_retrieve() {
     );
 }
 
+#[test]
+fn test_in_memory_cache_leaves_no_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = create_temp_file(&temp_dir, "test.txt", b"Hello, World!");
+
+    let cache = Cache::in_memory().unwrap();
+    cache.store_hash(&file_path, 13, None, "test_hash").unwrap();
+    assert_eq!(
+        cache.get_hash(&file_path, 13, None).unwrap(),
+        Some("test_hash".to_string())
+    );
+
+    assert_eq!(cache.pragma_value("journal_mode").unwrap(), "memory");
+    let files: Vec<_> = std::fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(files, vec!["test.txt"]);
+
+    // A second in-memory cache starts out empty
+    let other = Cache::in_memory().unwrap();
+    assert_eq!(other.get_hash(&file_path, 13, None).unwrap(), None);
+}
+
 #[test]
 fn test_cache_size_mismatch() {
     let temp_dir = TempDir::new().unwrap();
     let file_path = create_temp_file(&temp_dir, "test.txt", b"Hello, World!");
 
-    let cache = Cache::new().unwrap();
+    let cache = Cache::in_memory().unwrap();
     let hash = "test_hash".to_string();
 
     // Store hash with original size
-    cache.store_hash(&file_path, 13, &hash).unwrap();
+    cache.store_hash(&file_path, 13, None, &hash).unwrap();
 
     // Try to retrieve with different size
-    let retrieved = cache.get_hash(&file_path, 14).unwrap();
+    let retrieved = cache.get_hash(&file_path, 14, None).unwrap();
     assert_eq!(
         retrieved, None,
         "Should not retrieve hash when size doesn't match"
@@ -54,17 +83,17 @@ fn test_cache_size_mismatch() {
 
 #[test]
 fn test_cache_nonexistent_file() {
-    let cache = Cache::new().unwrap();
+    let cache = Cache::in_memory().unwrap();
     let nonexistent = PathBuf::from("/nonexistent/file.txt");
 
-    let retrieved = cache.get_hash(&nonexistent, 0).unwrap();
+    let retrieved = cache.get_hash(&nonexistent, 0, None).unwrap();
     assert_eq!(retrieved, None, "Should return None for nonexistent file");
 }
 
 #[test]
 fn test_cache_multiple_files() {
     let temp_dir = TempDir::new().unwrap();
-    let cache = Cache::new().unwrap();
+    let cache = Cache::in_memory().unwrap();
 
     // Create multiple files with different content
     let files = vec![
@@ -87,12 +116,12 @@ fn test_cache_multiple_files() {
 
     // Store all hashes
     for (path, hash, size) in &files {
-        cache.store_hash(path, *size, hash).unwrap();
+        cache.store_hash(path, *size, None, hash).unwrap();
     }
 
     // Verify all hashes
     for (path, expected_hash, size) in &files {
-        let retrieved = cache.get_hash(path, *size).unwrap();
+        let retrieved = cache.get_hash(path, *size, None).unwrap();
         assert_eq!(
             retrieved,
             Some(expected_hash.to_string()),
@@ -107,16 +136,20 @@ fn test_cache_update_hash() {
     let file_path = create_temp_file(&temp_dir, "test.txt", b"Initial content");
     let size = 15;
 
-    let cache = Cache::new().unwrap();
+    let cache = Cache::in_memory().unwrap();
 
     // Store initial hash
-    cache.store_hash(&file_path, size, "initial_hash").unwrap();
+    cache
+        .store_hash(&file_path, size, None, "initial_hash")
+        .unwrap();
 
     // Update with new hash
-    cache.store_hash(&file_path, size, "updated_hash").unwrap();
+    cache
+        .store_hash(&file_path, size, None, "updated_hash")
+        .unwrap();
 
     // Verify updated hash
-    let retrieved = cache.get_hash(&file_path, size).unwrap();
+    let retrieved = cache.get_hash(&file_path, size, None).unwrap();
     assert_eq!(
         retrieved,
         Some("updated_hash".to_string()),
@@ -130,7 +163,7 @@ fn test_cache_concurrent_access() {
     use std::thread;
 
     let temp_dir = TempDir::new().unwrap();
-    let cache = Arc::new(Cache::new().unwrap());
+    let cache = Arc::new(Cache::in_memory().unwrap());
     let mut handles = vec![];
 
     // Create 10 threads that simultaneously access the cache
@@ -150,10 +183,12 @@ fn test_cache_concurrent_access() {
             // Store and retrieve hash
             let hash = format!("hash{}", i);
             cache
-                .store_hash(&file_path, content.len() as u64, &hash)
+                .store_hash(&file_path, content.len() as u64, None, &hash)
                 .unwrap();
 
-            let retrieved = cache.get_hash(&file_path, content.len() as u64).unwrap();
+            let retrieved = cache
+                .get_hash(&file_path, content.len() as u64, None)
+                .unwrap();
             assert_eq!(
                 retrieved,
                 Some(hash),
@@ -170,3 +205,611 @@ fn test_cache_concurrent_access() {
         handle.join().unwrap();
     }
 }
+
+#[test]
+fn test_cache_pragma_applied() {
+    let temp_dir = TempDir::new().unwrap();
+    let pragma: CachePragma = "synchronous=OFF".parse().unwrap();
+
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[pragma]).unwrap();
+
+    // synchronous is reported numerically: OFF = 0
+    assert_eq!(
+        cache.pragma_value("synchronous").unwrap(),
+        "0",
+        "Configured synchronous=OFF should be applied on open"
+    );
+}
+
+#[test]
+fn test_cache_uses_wal() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+
+    assert_eq!(cache.pragma_value("journal_mode").unwrap(), "wal");
+    // NORMAL = 1
+    assert_eq!(cache.pragma_value("synchronous").unwrap(), "1");
+}
+
+#[test]
+fn test_cache_batch_insert_10k_rows() {
+    use dup_check::cache::CacheEntry;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    let entries: Vec<CacheEntry> = (0..10_000)
+        .map(|i| CacheEntry {
+            path: format!("/photos/{}.jpg", i),
+            size: i,
+            modified: Some(i as i64),
+            algorithm: HashAlgorithm::Blake3,
+            hash: format!("{:064x}", i),
+        })
+        .collect();
+
+    let started = Instant::now();
+    for batch in entries.chunks(500) {
+        cache.store_batch(batch).unwrap();
+    }
+    // Takes tens of milliseconds; the bound only catches a sync per row
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(cache.entries().unwrap().len(), 10_000);
+}
+
+#[test]
+fn test_cache_batch_entries_retrievable() {
+    use dup_check::cache::CacheEntry;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let entries: Vec<CacheEntry> = (0..100u64)
+        .map(|i| CacheEntry {
+            path: format!("/photos/{}.jpg", i),
+            size: i,
+            modified: Some(1_700_000_000 * 1_000_000_000),
+            algorithm: HashAlgorithm::Sha256,
+            hash: format!("hash{}", i),
+        })
+        .collect();
+    cache.store_batch(&entries).unwrap();
+
+    for i in 0..100u64 {
+        let path = PathBuf::from(format!("/photos/{}.jpg", i));
+        assert_eq!(
+            cache
+                .get_hash_with(&path, i, Some(modified), HashAlgorithm::Sha256)
+                .unwrap(),
+            Some(format!("hash{}", i))
+        );
+    }
+}
+
+#[test]
+fn test_cache_batch_rolls_back_on_error() {
+    use dup_check::cache::CacheEntry;
+
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    let entry = |path: &str, size| CacheEntry {
+        path: path.to_string(),
+        size,
+        modified: None,
+        algorithm: HashAlgorithm::Sha256,
+        hash: "hash".to_string(),
+    };
+    // SQLite integers are signed, so the middle entry can't be stored
+    let batch = [entry("/a", 1), entry("/b", u64::MAX), entry("/c", 3)];
+
+    assert!(cache.store_batch(&batch).is_err());
+    assert!(
+        cache.entries().unwrap().is_empty(),
+        "Entries before the failing one are rolled back"
+    );
+}
+
+#[test]
+fn test_cache_pragma_validation() {
+    assert!("cache_size=-2000".parse::<CachePragma>().is_ok());
+    assert!("temp_store=MEMORY".parse::<CachePragma>().is_ok());
+
+    assert!(
+        "journal_mode=OFF".parse::<CachePragma>().is_err(),
+        "Pragmas outside the allowlist should be rejected"
+    );
+    assert!(
+        "synchronous=OFF; DROP TABLE file_hashes"
+            .parse::<CachePragma>()
+            .is_err(),
+        "Values must be plain numbers or keywords"
+    );
+    assert!("synchronous".parse::<CachePragma>().is_err());
+}
+
+#[test]
+fn test_cache_export_import_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let source = Cache::open(&temp_dir.path().join("source.db"), &[]).unwrap();
+    for i in 0..50u64 {
+        let path = PathBuf::from(format!("/data/file{}.bin", i));
+        source
+            .store_hash(&path, i * 10, None, &format!("hash{}", i))
+            .unwrap();
+    }
+
+    let export_path = temp_dir.path().join("cache.bin");
+    assert_eq!(source.export_to(&export_path).unwrap(), 50);
+
+    let target = Cache::open(&temp_dir.path().join("target.db"), &[]).unwrap();
+    let summary = target.import_from(&export_path).unwrap();
+    assert_eq!(summary.inserted, 50);
+    assert_eq!(summary.conflicts, 0);
+    assert_eq!(
+        target.entries().unwrap(),
+        source.entries().unwrap(),
+        "Every entry should survive the round trip"
+    );
+
+    // Importing again changes nothing
+    let summary = target.import_from(&export_path).unwrap();
+    assert_eq!(summary.inserted, 0);
+    assert_eq!(summary.unchanged, 50);
+}
+
+#[test]
+fn test_cache_import_conflict_keeps_local_entry() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = PathBuf::from("/data/shared.bin");
+
+    let source = Cache::open(&temp_dir.path().join("source.db"), &[]).unwrap();
+    source.store_hash(&path, 10, None, "remote_hash").unwrap();
+    let export_path = temp_dir.path().join("cache.bin");
+    source.export_to(&export_path).unwrap();
+
+    let target = Cache::open(&temp_dir.path().join("target.db"), &[]).unwrap();
+    target.store_hash(&path, 10, None, "local_hash").unwrap();
+    let summary = target.import_from(&export_path).unwrap();
+
+    assert_eq!(summary.conflicts, 1);
+    assert_eq!(
+        target.get_hash(&path, 10, None).unwrap(),
+        Some("local_hash".to_string())
+    );
+}
+
+#[test]
+fn test_cache_import_rejects_foreign_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let bogus = create_temp_file(&temp_dir, "bogus.bin", b"definitely not a cache export");
+
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    assert!(matches!(
+        cache.import_from(&bogus),
+        Err(DupCheckError::InvalidCacheFile(_))
+    ));
+    assert!(cache.entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_cache_verify_removes_stale_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+
+    let kept = create_temp_file(&temp_dir, "kept.txt", b"still here");
+    let resized = create_temp_file(&temp_dir, "resized.txt", b"grew");
+    cache.store_hash(&kept, 10, None, "kept_hash").unwrap();
+    cache.store_hash(&resized, 2, None, "resized_hash").unwrap();
+    cache
+        .store_hash(&temp_dir.path().join("gone.txt"), 5, None, "gone_hash")
+        .unwrap();
+
+    let summary = cache.verify(&CancellationToken::new(), |_, _| {}).unwrap();
+
+    assert_eq!(summary.checked, 3);
+    assert_eq!(summary.removed, 2);
+    assert!(!summary.cancelled);
+    assert_eq!(
+        cache.get_hash(&kept, 10, None).unwrap(),
+        Some("kept_hash".into())
+    );
+}
+
+#[test]
+fn test_cache_verify_cancellation_leaves_db_consistent() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("cache.db");
+    let cache = Cache::open(&db_path, &[]).unwrap();
+
+    // 1200 entries for files that don't exist, spanning several commit batches
+    for i in 0..1200 {
+        let path = temp_dir.path().join(format!("missing{:04}.txt", i));
+        cache.store_hash(&path, 1, None, "hash").unwrap();
+    }
+
+    let cancel = CancellationToken::new();
+    let summary = cache
+        .verify(&cancel, |checked, _| {
+            if checked == 700 {
+                cancel.cancel();
+            }
+        })
+        .unwrap();
+
+    assert!(summary.cancelled, "Verification should stop when cancelled");
+    assert_eq!(summary.checked, 700);
+    assert_eq!(summary.removed, 700);
+    drop(cache);
+
+    // Everything removed before the cancel is committed, the rest is untouched
+    let reopened = Cache::open(&db_path, &[]).unwrap();
+    let remaining = reopened.entries().unwrap();
+    assert_eq!(remaining.len(), 500);
+    assert!(remaining[0].path.ends_with("missing0700.txt"));
+}
+
+#[test]
+fn test_scan_persists_hashes_through_background_writer() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    std::fs::create_dir(&scan_dir).unwrap();
+
+    let mut paths = Vec::new();
+    for group in 0..20 {
+        let content = format!("group {} content", group);
+        for copy in 0..3 {
+            let name = format!("scan/file_{}_{}.txt", group, copy);
+            paths.push(create_temp_file(&temp_dir, &name, content.as_bytes()));
+        }
+    }
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+    assert_eq!(duplicates.len(), 20);
+
+    // The writer is drained and joined before the scan returns
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), paths.len(), "Every hash should be persisted");
+    for path in &paths {
+        let metadata = std::fs::metadata(path).unwrap();
+        let modified = metadata.modified().ok();
+        assert_eq!(
+            cache.get_hash(path, metadata.len(), modified).unwrap(),
+            Some(utils::calculate_hash(path).unwrap())
+        );
+    }
+}
+
+#[test]
+fn test_cache_hashes_are_kept_per_algorithm() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = create_temp_file(&temp_dir, "file.txt", b"content");
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+
+    cache
+        .store_hash_with(&path, 7, None, HashAlgorithm::Sha256, "sha_hash")
+        .unwrap();
+    assert_eq!(
+        cache
+            .get_hash_with(&path, 7, None, HashAlgorithm::Blake3)
+            .unwrap(),
+        None,
+        "A SHA-256 hash must not be returned for BLAKE3"
+    );
+
+    cache
+        .store_hash_with(&path, 7, None, HashAlgorithm::Blake3, "blake_hash")
+        .unwrap();
+    assert_eq!(
+        cache
+            .get_hash_with(&path, 7, None, HashAlgorithm::Blake3)
+            .unwrap(),
+        Some("blake_hash".to_string())
+    );
+    assert_eq!(cache.get_hash(&path, 7, None).unwrap(), None);
+}
+
+#[test]
+fn test_cache_migrates_old_schema() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("old.db");
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file_hashes (
+                path TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                hash TEXT NOT NULL
+            );
+            INSERT INTO file_hashes VALUES ('/old/file', 3, 'old_hash');",
+        )
+        .unwrap();
+    }
+
+    let cache = Cache::open(&db_path, &[]).unwrap();
+    assert_eq!(
+        cache
+            .get_hash(&PathBuf::from("/old/file"), 3, None)
+            .unwrap(),
+        Some("old_hash".to_string()),
+        "Existing hashes are treated as SHA-256"
+    );
+}
+
+#[test]
+fn test_scan_with_blake3_caches_blake3_hashes() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    std::fs::create_dir(&scan_dir).unwrap();
+    let a = create_temp_file(&temp_dir, "scan/a.txt", b"same");
+    create_temp_file(&temp_dir, "scan/b.txt", b"same");
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let options = ScanOptions {
+        hash_algorithm: HashAlgorithm::Blake3,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(Some(cache.clone()), options);
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+
+    let expected = utils::calculate_hash_with(&a, HashAlgorithm::Blake3).unwrap();
+    assert!(duplicates.contains_key(&expected));
+    let modified = std::fs::metadata(&a).unwrap().modified().ok();
+    assert_eq!(
+        cache
+            .get_hash_with(&a, 4, modified, HashAlgorithm::Blake3)
+            .unwrap(),
+        Some(expected)
+    );
+    assert_eq!(cache.get_hash(&a, 4, modified).unwrap(), None);
+}
+
+#[test]
+fn test_touched_file_is_rehashed() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    std::fs::create_dir(&scan_dir).unwrap();
+    let a = create_temp_file(&temp_dir, "scan/a.txt", b"same");
+    create_temp_file(&temp_dir, "scan/b.txt", b"same");
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    scanner.find_duplicates(&scan_dir).unwrap();
+    let before = std::fs::metadata(&a).unwrap().modified().ok();
+    assert!(cache.get_hash(&a, 4, before).unwrap().is_some());
+
+    // Same size, new content and a later modification time
+    std::fs::write(&a, b"diff").unwrap();
+    let later = filetime::FileTime::from_unix_time(2_000_000_000, 0);
+    filetime::set_file_mtime(&a, later).unwrap();
+    let after = std::fs::metadata(&a).unwrap().modified().ok();
+    assert_eq!(
+        cache.get_hash(&a, 4, after).unwrap(),
+        None,
+        "A changed modification time must miss the cache"
+    );
+
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+    assert!(duplicates.is_empty(), "The stale hash must not be reused");
+    assert_eq!(
+        cache.get_hash(&a, 4, after).unwrap(),
+        Some(utils::calculate_hash(&a).unwrap())
+    );
+}
+
+#[test]
+fn test_cache_clear_all() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    let a = create_temp_file(&temp_dir, "a.txt", b"a");
+    let b = create_temp_file(&temp_dir, "b.txt", b"b");
+    cache.store_hash(&a, 1, None, "a_hash").unwrap();
+    cache.store_hash(&b, 1, None, "b_hash").unwrap();
+
+    assert_eq!(cache.clear_all().unwrap(), 2);
+    assert!(cache.entries().unwrap().is_empty());
+    assert_eq!(cache.clear_all().unwrap(), 0);
+}
+
+#[test]
+fn test_cache_clean_old_entries_removes_missing_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap();
+    let kept = create_temp_file(&temp_dir, "kept.txt", b"still here");
+    cache.store_hash(&kept, 10, None, "kept_hash").unwrap();
+    cache
+        .store_hash(&temp_dir.path().join("gone.txt"), 5, None, "gone_hash")
+        .unwrap();
+
+    let summary = cache
+        .clean_old_entries(&CancellationToken::new(), |_, _| {})
+        .unwrap();
+    assert_eq!(summary.checked, 2);
+    assert_eq!(summary.removed, 1);
+    assert_eq!(
+        cache.get_hash(&kept, 10, None).unwrap(),
+        Some("kept_hash".into())
+    );
+    assert_eq!(cache.entries().unwrap().len(), 1);
+}
+
+/// Pretend the entry for `path` was inserted `days` days ago
+fn backdate(db_path: &std::path::Path, path: &std::path::Path, days: i64) {
+    let conn = rusqlite::Connection::open(db_path).unwrap();
+    conn.execute(
+        "UPDATE file_hashes SET created_at = created_at - ? WHERE path = ?",
+        rusqlite::params![days * 86_400, path.to_string_lossy()],
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_cache_evict_older_than_removes_only_stale_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("cache.db");
+    let cache = Cache::open(&db_path, &[]).unwrap();
+    let stale = PathBuf::from("/data/stale.txt");
+    let fresh = PathBuf::from("/data/fresh.txt");
+    cache.store_hash(&stale, 1, None, "stale_hash").unwrap();
+    cache.store_hash(&fresh, 1, None, "fresh_hash").unwrap();
+    backdate(&db_path, &stale, 40);
+
+    let thirty_days = std::time::Duration::from_secs(30 * 86_400);
+    assert_eq!(cache.evict_older_than(thirty_days).unwrap(), 1);
+    assert_eq!(cache.get_hash(&stale, 1, None).unwrap(), None);
+    assert_eq!(
+        cache.get_hash(&fresh, 1, None).unwrap(),
+        Some("fresh_hash".to_string())
+    );
+
+    // Storing a hash again renews its entry
+    backdate(&db_path, &fresh, 40);
+    cache.store_hash(&fresh, 1, None, "fresh_hash").unwrap();
+    assert_eq!(cache.evict_older_than(thirty_days).unwrap(), 0);
+}
+
+#[test]
+fn test_cache_migration_keeps_entries_without_created_at() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("old.db");
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file_hashes (
+                path TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                algorithm TEXT NOT NULL DEFAULT 'sha256',
+                modified INTEGER
+            );
+            INSERT INTO file_hashes (path, size, hash) VALUES ('/old/file', 3, 'old_hash');",
+        )
+        .unwrap();
+    }
+
+    let cache = Cache::open(&db_path, &[]).unwrap();
+    let one_day = std::time::Duration::from_secs(86_400);
+    assert_eq!(
+        cache.evict_older_than(one_day).unwrap(),
+        0,
+        "Migrated entries count as inserted at migration time"
+    );
+    assert_eq!(
+        cache
+            .get_hash(&PathBuf::from("/old/file"), 3, None)
+            .unwrap(),
+        Some("old_hash".to_string())
+    );
+}
+
+#[test]
+fn test_cache_max_age_parsing() {
+    use clap::Parser;
+    use dup_check::cli::Args;
+
+    let args = Args::try_parse_from(["dupcheck", "--cache-max-age", "30d"]).unwrap();
+    assert_eq!(
+        args.cache_max_age,
+        Some(std::time::Duration::from_secs(30 * 86_400))
+    );
+    let args = Args::try_parse_from(["dupcheck", "--cache-max-age", "12H"]).unwrap();
+    assert_eq!(
+        args.cache_max_age,
+        Some(std::time::Duration::from_secs(12 * 3_600))
+    );
+    let args = Args::try_parse_from(["dupcheck", "--cache-max-age", "1y"]).unwrap();
+    assert_eq!(
+        args.cache_max_age,
+        Some(std::time::Duration::from_secs(365 * 86_400))
+    );
+
+    for invalid in ["30", "d", "30x", "-1d"] {
+        assert!(
+            Args::try_parse_from(["dupcheck", "--cache-max-age", invalid]).is_err(),
+            "{} should be rejected",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_dir_snapshot_roundtrip() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let cache = Cache::in_memory().unwrap();
+    let dir = PathBuf::from("/photos/2023");
+    let snapshot = DirSnapshot {
+        modified: UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789),
+        entries: vec![
+            SnapshotEntry {
+                name: "a.jpg".to_string(),
+                is_dir: false,
+                size: 2048,
+                modified: Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+                allocated: 1024,
+                file_id: Some(FileId { dev: 1, ino: 42 }),
+            },
+            SnapshotEntry {
+                name: "raw".to_string(),
+                is_dir: true,
+                size: 0,
+                modified: None,
+                allocated: 0,
+                file_id: None,
+            },
+        ],
+    };
+    assert_eq!(cache.dir_snapshot(&dir).unwrap(), None);
+
+    cache
+        .store_dir_snapshots(&[(dir.clone(), snapshot.clone())])
+        .unwrap();
+    assert_eq!(cache.dir_snapshot(&dir).unwrap(), Some(snapshot.clone()));
+
+    // Storing again replaces the entries
+    let mut changed = snapshot;
+    changed.entries.truncate(1);
+    cache
+        .store_dir_snapshots(&[(dir.clone(), changed.clone())])
+        .unwrap();
+    assert_eq!(cache.dir_snapshot(&dir).unwrap(), Some(changed));
+
+    cache.remove_dir_snapshot(&dir).unwrap();
+    assert_eq!(cache.dir_snapshot(&dir).unwrap(), None);
+}
+
+#[test]
+fn test_dir_snapshots_under_root() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let cache = Cache::in_memory().unwrap();
+    let snapshot = |name: &str| DirSnapshot {
+        modified: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        entries: vec![SnapshotEntry {
+            name: name.to_string(),
+            is_dir: false,
+            size: 1,
+            modified: None,
+            allocated: 0,
+            file_id: None,
+        }],
+    };
+    let dirs = ["/photos", "/photos/2023", "/photos-old", "/music"];
+    let stored: Vec<_> = dirs
+        .iter()
+        .map(|dir| (PathBuf::from(dir), snapshot(dir)))
+        .collect();
+    cache.store_dir_snapshots(&stored).unwrap();
+
+    let under = cache.dir_snapshots_under(Path::new("/photos")).unwrap();
+    let mut found: Vec<_> = under.keys().cloned().collect();
+    found.sort();
+    assert_eq!(
+        found,
+        [PathBuf::from("/photos"), PathBuf::from("/photos/2023")],
+        "Siblings sharing the root's name as a prefix are left out"
+    );
+    assert_eq!(under[Path::new("/photos/2023")], snapshot("/photos/2023"));
+}
@@ -0,0 +1,792 @@
+use dup_check::{ui, utils};
+use std::fs::File;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) {
+    let mut file = File::create(dir.path().join(name)).unwrap();
+    file.write_all(content).unwrap();
+}
+
+/// Run the dupcheck binary with the given arguments
+fn dupcheck(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .args(args)
+        .output()
+        .expect("failed to run dupcheck")
+}
+
+#[test]
+fn test_fail_on_duplicates_with_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "logo.png", b"same bytes");
+    create_temp_file(&temp_dir, "logo_copy.png", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--fail-on-duplicates"]);
+
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "Duplicates should fail the run"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("logo_copy.png"),
+        "Offending files should be listed"
+    );
+}
+
+#[test]
+fn test_fail_on_duplicates_clean_tree() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.png", b"unique a");
+    create_temp_file(&temp_dir, "b.png", b"unique b");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--fail-on-duplicates"]);
+
+    assert_eq!(output.status.code(), Some(0), "A clean tree should pass");
+}
+
+#[test]
+fn test_hash_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .args(["hash", "--hash-algo", "sha256", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run dupcheck");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"hello world")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9  -",
+        "Should match the reference SHA-256 digest"
+    );
+}
+
+#[test]
+fn test_json_format() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "b.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "unique.txt", b"something else");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--format", "json"]);
+    assert!(output.status.success());
+
+    let groups: Vec<ui::JsonGroup> =
+        serde_json::from_slice(&output.stdout).expect("stdout should be pure JSON");
+    assert_eq!(groups.len(), 1);
+    let group = &groups[0];
+    assert_eq!(group.size, 17);
+    assert_eq!(group.wasted_bytes, 17);
+    assert_eq!(
+        group.hash,
+        utils::calculate_hash(&temp_dir.path().join("a.txt")).unwrap()
+    );
+    assert_eq!(group.paths.len(), 2);
+    assert!(group.paths.iter().all(|p| p.is_absolute()));
+}
+
+/// Newlines are allowed in Unix file names, which is what --print0 is for
+#[cfg(unix)]
+#[test]
+fn test_print0_lists_deletable_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "b\nnewline.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "c.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "unique.txt", b"something else");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--print0"]);
+    assert!(output.status.success());
+
+    let mut expected = Vec::new();
+    for name in ["b\nnewline.txt", "c.txt"] {
+        let path = std::path::absolute(temp_dir.path().join(name)).unwrap();
+        expected.extend_from_slice(path.to_str().unwrap().as_bytes());
+        expected.push(0);
+    }
+    assert_eq!(output.stdout, expected, "Only the copies, NUL-terminated");
+}
+
+#[test]
+fn test_output_writes_report_to_file() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::create_dir(temp_dir.path().join("scan")).unwrap();
+    create_temp_file(&temp_dir, "scan/a.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "scan/b.txt", b"duplicate content");
+    let report = temp_dir.path().join("report.json");
+
+    let path = temp_dir.path().join("scan");
+    let output = dupcheck(&[
+        "--path",
+        path.to_str().unwrap(),
+        "--no-cache",
+        "--format",
+        "json",
+        "--output",
+        report.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    assert!(output.stdout.is_empty(), "Nothing is printed to stdout");
+    let groups: Vec<ui::JsonGroup> =
+        serde_json::from_slice(&std::fs::read(&report).unwrap()).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].paths.len(), 2);
+}
+
+#[test]
+fn test_output_file_gets_extra_report_sections() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::create_dir(temp_dir.path().join("scan")).unwrap();
+    create_temp_file(&temp_dir, "scan/a.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "scan/b.txt", b"duplicate content");
+    let report = temp_dir.path().join("report.txt");
+
+    let path = temp_dir.path().join("scan");
+    let output = dupcheck(&[
+        "--path",
+        path.to_str().unwrap(),
+        "--no-cache",
+        "--yes",
+        "--deepest",
+        "5",
+        "--savings",
+        "--by-folder",
+        "--output",
+        report.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report = std::fs::read_to_string(&report).unwrap();
+    for section in [
+        "Most Deeply Nested Duplicates",
+        "Potential Savings by Action",
+        "Wasted Space by Top-Level Folder",
+    ] {
+        assert!(
+            report.contains(section),
+            "{} missing from {}",
+            section,
+            report
+        );
+        assert!(!stdout.contains(section), "{} printed to stdout", section);
+    }
+}
+
+#[test]
+fn test_csv_format_round_trips() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "report, final.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "report.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "copy.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "photo.jpg", b"other content");
+    #[cfg(unix)]
+    create_temp_file(&temp_dir, "\"quoted\" photo.jpg", b"other content");
+    #[cfg(not(unix))]
+    create_temp_file(&temp_dir, "photo copy.jpg", b"other content");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--format", "csv"]);
+    assert!(output.status.success());
+
+    let mut reader = csv::Reader::from_reader(output.stdout.as_slice());
+    let rows: Vec<ui::CsvRow> = reader.deserialize().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 5, "One row per file");
+    assert!(rows
+        .iter()
+        .any(|row| row.path.ends_with("report, final.txt")));
+    for group_id in [1, 2] {
+        let originals = rows
+            .iter()
+            .filter(|row| row.group_id == group_id && row.is_original)
+            .count();
+        assert_eq!(originals, 1, "Each group has exactly one original");
+    }
+    assert_eq!(
+        rows[0].size_bytes, 17,
+        "The most wasteful group comes first"
+    );
+}
+
+#[test]
+fn test_path_with_inline_filters() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"duplicate content");
+    create_temp_file(&temp_dir, "b.txt", b"duplicate content");
+
+    let path = format!("{}:min=1K", temp_dir.path().display());
+    let output = dupcheck(&["--path", &path, "--no-cache", "--format", "json"]);
+    assert!(output.status.success());
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        groups.is_empty(),
+        "Files below the path's minimum are skipped"
+    );
+}
+
+/// Uses the freedesktop trash, located through XDG_DATA_HOME
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn test_trash_moves_duplicates_to_trash() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_home = temp_dir.path().join("data");
+    std::fs::create_dir_all(temp_dir.path().join("scan")).unwrap();
+    create_temp_file(&temp_dir, "scan/a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "scan/b.txt", b"same bytes");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .args(["--path", temp_dir.path().join("scan").to_str().unwrap()])
+        .args(["--no-cache", "--trash"])
+        .env("XDG_DATA_HOME", &data_home)
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to run dupcheck");
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    // The run ends at the "scan another directory?" prompt, which needs a terminal
+    let output = child.wait_with_output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Moved 1 duplicate files to the trash"));
+    assert!(stdout.contains("Permanently deleted 0 files"));
+    let trashed: Vec<_> = std::fs::read_dir(data_home.join("Trash/files"))
+        .unwrap()
+        .collect();
+    assert_eq!(trashed.len(), 1, "The duplicate should be in the trash");
+    let remaining = std::fs::read_dir(temp_dir.path().join("scan")).unwrap();
+    assert_eq!(remaining.count(), 1, "The original stays in place");
+}
+
+/// Trash a duplicate, then bring it back with --undo
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn test_undo_restores_trashed_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    std::fs::create_dir_all(&scan_dir).unwrap();
+    create_temp_file(&temp_dir, "scan/a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "scan/b.txt", b"same bytes");
+    let run = |args: &[&str], input: &[u8]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+            .args(args)
+            .env("XDG_DATA_HOME", temp_dir.path().join("data"))
+            .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to run dupcheck");
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let path = scan_dir.to_str().unwrap();
+    run(&["--path", path, "--no-cache", "--trash"], b"y\n");
+    assert_eq!(std::fs::read_dir(&scan_dir).unwrap().count(), 1);
+
+    let output = run(&["--undo"], b"");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Restored 1 files"));
+    assert_eq!(
+        std::fs::read_dir(&scan_dir).unwrap().count(),
+        2,
+        "The trashed duplicate is back in place"
+    );
+
+    let output = run(&["--undo"], b"");
+    assert!(!output.status.success(), "The batch was already undone");
+}
+
+#[test]
+fn test_bytes_flag_prints_exact_sizes() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.bin", &[7u8; 2048]);
+    create_temp_file(&temp_dir, "b.bin", &[7u8; 2048]);
+    let path = temp_dir.path().to_str().unwrap();
+
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--fail-on-duplicates",
+        "--bytes",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Wasted space: 2048\n"), "{}", stdout);
+    assert!(stdout.contains("(Size: 2048)"));
+
+    let output = dupcheck(&["--path", path, "--no-cache", "--fail-on-duplicates"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Wasted space: 2.00 KB"), "{}", stdout);
+}
+
+#[test]
+fn test_multiple_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::create_dir(temp_dir.path().join("Downloads")).unwrap();
+    std::fs::create_dir(temp_dir.path().join("Desktop")).unwrap();
+    create_temp_file(&temp_dir, "Downloads/report.pdf", b"same report");
+    create_temp_file(&temp_dir, "Desktop/report.pdf", b"same report");
+    let downloads = temp_dir.path().join("Downloads");
+    let desktop = temp_dir.path().join("Desktop");
+
+    let output = dupcheck(&[
+        "--path",
+        downloads.to_str().unwrap(),
+        "--path",
+        desktop.to_str().unwrap(),
+        "--no-cache",
+        "--format",
+        "json",
+    ]);
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(groups.len(), 1, "Copies across paths should be found");
+    assert_eq!(groups[0].paths.len(), 2);
+
+    // Overlapping paths reach Downloads twice, but each file counts once
+    let output = dupcheck(&[
+        "--path",
+        temp_dir.path().to_str().unwrap(),
+        "--path",
+        downloads.to_str().unwrap(),
+        "--no-cache",
+        "--format",
+        "json",
+    ]);
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].paths.len(), 2);
+}
+
+#[test]
+fn test_yes_only_reports_without_prompting() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    // No stdin at all: any prompt would fail the run
+    let output = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .args(["--path", path, "--no-cache", "--yes"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "Found duplicates exit 1");
+    assert!(temp_dir.path().join("a.txt").exists());
+    assert!(temp_dir.path().join("b.txt").exists());
+}
+
+#[test]
+fn test_yes_with_delete_deletes_without_prompting() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .args([
+            "--path",
+            path,
+            "--no-cache",
+            "--keep",
+            "first-alphabetical",
+            "--yes",
+            "--delete",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "Handled duplicates exit 1");
+    assert!(temp_dir.path().join("a.txt").exists());
+    assert!(!temp_dir.path().join("b.txt").exists());
+
+    let output = dupcheck(&["--path", path, "--no-cache", "--non-interactive"]);
+    assert_eq!(output.status.code(), Some(0), "Nothing left to do exits 0");
+}
+
+#[test]
+fn test_delete_requires_yes() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--delete"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_ignore_case_names_needs_name_matching() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "Notes.txt", b"same bytes");
+    create_temp_file(&temp_dir, "notes.TXT", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--yes", "--ignore-case-names"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--ignore-case-names"),
+        "{:?}",
+        output
+    );
+
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--yes",
+        "--by",
+        "name",
+        "--ignore-case-names",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+}
+
+#[test]
+fn test_yes_with_dry_run_only_reports() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--yes",
+        "--delete",
+        "--dry-run",
+    ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(temp_dir.path().join("a.txt").exists());
+    assert!(temp_dir.path().join("b.txt").exists());
+}
+
+/// The freed space matches the scan report, which counts allocated blocks
+#[cfg(unix)]
+#[test]
+fn test_dry_run_frees_only_allocated_blocks_of_sparse_files() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let size = 8 * 1024 * 1024;
+    for name in ["sparse1.img", "sparse2.img"] {
+        let mut file = File::create(temp_dir.path().join(name)).unwrap();
+        file.set_len(size).unwrap();
+        file.write_all(b"header").unwrap();
+    }
+    let metadata = std::fs::metadata(temp_dir.path().join("sparse2.img")).unwrap();
+    let allocated = size.min(metadata.blocks() * 512);
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--bytes",
+        "--yes",
+        "--delete",
+        "--dry-run",
+    ]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!("Would free up {}\n", allocated)),
+        "Got:\n{}",
+        stdout
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_with_preserved_link_times() {
+    use filetime::FileTime;
+
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+    let times = [
+        FileTime::from_unix_time(1_500_000_000, 0),
+        FileTime::from_unix_time(1_600_000_000, 0),
+    ];
+    for (name, time) in ["a.txt", "b.txt"].iter().zip(times) {
+        filetime::set_file_mtime(temp_dir.path().join(name), time).unwrap();
+    }
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--yes",
+        "--delete",
+        "--symlink",
+        "--preserve-link-times",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let linked: Vec<_> = ["a.txt", "b.txt"]
+        .iter()
+        .zip(times)
+        .filter_map(|(name, time)| {
+            let metadata = std::fs::symlink_metadata(temp_dir.path().join(name)).unwrap();
+            metadata.file_type().is_symlink().then(|| {
+                assert_eq!(FileTime::from_last_modification_time(&metadata), time);
+                name
+            })
+        })
+        .collect();
+    assert_eq!(linked.len(), 1, "Exactly one copy becomes a link");
+    for name in ["a.txt", "b.txt"] {
+        assert_eq!(
+            std::fs::read(temp_dir.path().join(name)).unwrap(),
+            b"same bytes"
+        );
+    }
+}
+
+#[test]
+fn test_yes_exits_2_on_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"unique");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let report = temp_dir.path().join("missing").join("report.txt");
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--yes",
+        "--output",
+        report.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_piped_output_is_plain() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&["--path", path, "--no-cache", "--fail-on-duplicates"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[dup] "), "Got:\n{}", stdout);
+    assert!(!stdout.contains('🔒'));
+}
+
+#[test]
+fn test_no_color_output_has_no_emoji() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--no-color",
+        "--live",
+        "--log-skipped",
+        "--savings",
+        "--by-folder",
+        "--deepest",
+        "5",
+        "--yes",
+        "--delete",
+        "--dry-run",
+    ]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("Would delete"), "Got:\n{}", stdout);
+    assert!(stdout.is_ascii(), "No emoji expected in:\n{}", stdout);
+    assert!(stderr.is_ascii(), "No emoji expected in:\n{}", stderr);
+}
+
+#[test]
+fn test_no_recursive_ignores_nested_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::create_dir(temp_dir.path().join("nested")).unwrap();
+    create_temp_file(&temp_dir, "top.txt", b"top level");
+    create_temp_file(&temp_dir, "nested/top.txt", b"top level");
+    create_temp_file(&temp_dir, "nested/a.txt", b"nested");
+    create_temp_file(&temp_dir, "nested/b.txt", b"nested");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--no-recursive",
+        "--format",
+        "json",
+    ]);
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(groups.is_empty(), "Only top.txt is scanned: {:?}", groups);
+
+    let output = dupcheck(&["--path", path, "--no-cache", "--format", "json"]);
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(groups.len(), 2);
+
+    let output = dupcheck(&["--path", path, "--no-recursive", "--max-depth", "2"]);
+    assert!(!output.status.success(), "The flags conflict");
+}
+
+#[test]
+fn test_log_skipped_prints_counts_per_reason() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"some content");
+    create_temp_file(&temp_dir, ".hidden", b"some content");
+    create_temp_file(&temp_dir, "small.txt", b"x");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--min-size",
+        "2",
+        "--log-skipped",
+        "--format",
+        "json",
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Skipped: 1 hidden, 1 too small"),
+        "stderr: {}",
+        stderr
+    );
+
+    let output = dupcheck(&["--path", path, "--no-cache", "--format", "json"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Skipped:"));
+}
+
+#[test]
+fn test_size_exact_matches_one_size() {
+    let temp_dir = TempDir::new().unwrap();
+    for (name, content) in [
+        ("a.txt", "1234"),
+        ("b.txt", "1234"),
+        ("c.txt", "12345"),
+        ("d.txt", "12345"),
+    ] {
+        create_temp_file(&temp_dir, name, content.as_bytes());
+    }
+
+    let path = temp_dir.path().to_str().unwrap();
+    let output = dupcheck(&[
+        "--path",
+        path,
+        "--no-cache",
+        "--size-exact",
+        "5",
+        "--format",
+        "json",
+    ]);
+    let groups: Vec<ui::JsonGroup> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].size, 5);
+
+    let output = dupcheck(&["--path", path, "--size-exact", "5", "--min-size", "1"]);
+    assert!(!output.status.success(), "The flags conflict");
+}
+
+#[test]
+fn test_cache_path_from_option_and_env() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"cached content");
+    create_temp_file(&temp_dir, "b.txt", b"cached content");
+    let scratch = TempDir::new().unwrap();
+    let xdg = scratch.path().join("xdg");
+    let from_option = scratch.path().join("option.db");
+    let from_env = scratch.path().join("env.db");
+
+    let path = temp_dir.path().to_str().unwrap();
+    let journal = scratch.path().join("journal.jsonl");
+    let run = |cache_path: Option<&std::path::Path>, env: Option<&std::path::Path>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_dup_check"));
+        command
+            .args(["--path", path, "--format", "json", "--journal"])
+            .arg(&journal)
+            .env("XDG_CACHE_HOME", &xdg)
+            .env_remove("DUPCHECK_CACHE");
+        if let Some(cache_path) = cache_path {
+            command.arg("--cache-path").arg(cache_path);
+        }
+        if let Some(env) = env {
+            command.env("DUPCHECK_CACHE", env);
+        }
+        let output = command.output().expect("failed to run dupcheck");
+        assert!(output.status.success(), "{:?}", output);
+    };
+
+    run(None, Some(&from_env));
+    assert!(from_env.exists());
+    assert!(!from_option.exists());
+
+    // The option takes precedence over the environment
+    std::fs::remove_file(&from_env).unwrap();
+    run(Some(&from_option), Some(&from_env));
+    assert!(from_option.exists());
+    assert!(!from_env.exists());
+
+    let default_cache = walkdir::WalkDir::new(&xdg)
+        .into_iter()
+        .filter_map(Result::ok)
+        .find(|entry| entry.file_name() == "cache.db");
+    assert!(
+        default_cache.is_none(),
+        "The default cache was created: {:?}",
+        default_cache
+    );
+}
+
+/// A bare run takes its options from the config file instead of asking
+#[cfg(target_os = "linux")]
+#[test]
+fn test_bare_run_uses_config_file() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same bytes");
+    create_temp_file(&temp_dir, "b.txt", b"same bytes");
+    let config_home = TempDir::new().unwrap();
+    std::fs::create_dir(config_home.path().join("dupcheck")).unwrap();
+    let config = format!(
+        "paths = [{:?}]\nno-cache = true\nformat = \"json\"\n",
+        temp_dir.path().to_str().unwrap()
+    );
+    std::fs::write(config_home.path().join("dupcheck/config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dup_check"))
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.contains("b.txt"), "Got:\n{}", stdout);
+}
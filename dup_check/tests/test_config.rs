@@ -0,0 +1,145 @@
+use clap::{CommandFactory, FromArgMatches};
+use dup_check::{cli::Args, config::Config, utils::HashAlgorithm};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// Parse `argv` and layer it over `config`, as `dupcheck` does
+fn merged(config: &str, argv: &[&str]) -> anyhow::Result<Args> {
+    let config: Config = toml::from_str(config)?;
+    let matches = Args::command()
+        .try_get_matches_from(std::iter::once("dupcheck").chain(argv.iter().copied()))?;
+    let mut args = Args::from_arg_matches(&matches)?;
+    config.merge(&mut args, &matches)?;
+    Ok(args)
+}
+
+const CONFIG: &str = r#"
+paths = ["/data/photos", "/data/downloads:min=1M"]
+min-size = "4K"
+exclude = ["node_modules", "*.tmp"]
+hash-algo = "blake3"
+include-hidden = true
+ext = ["JPG", ".png"]
+"#;
+
+#[test]
+fn test_config_fills_unset_options() {
+    let args = merged(CONFIG, &[]).unwrap();
+
+    let paths: Vec<PathBuf> = args.paths.iter().map(|root| root.path.clone()).collect();
+    assert_eq!(
+        paths,
+        [
+            PathBuf::from("/data/photos"),
+            PathBuf::from("/data/downloads")
+        ]
+    );
+    assert_eq!(args.paths[1].min_size, Some(1024 * 1024));
+    assert_eq!(args.min_size, Some(4 * 1024));
+    assert_eq!(args.exclude.len(), 2);
+    assert_eq!(args.hash_algo, HashAlgorithm::Blake3);
+    assert!(args.include_hidden);
+    assert_eq!(
+        args.extensions,
+        Some(vec!["jpg".to_string(), "png".to_string()])
+    );
+    assert_eq!(
+        args.max_size, None,
+        "Options missing from the file keep their defaults"
+    );
+    assert_eq!(args.min_copies, 2);
+}
+
+#[test]
+fn test_command_line_overrides_config() {
+    let args = merged(
+        CONFIG,
+        &[
+            "--path",
+            "/elsewhere",
+            "--min-size",
+            "1M",
+            "--hash-algo",
+            "xxhash",
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(args.paths.len(), 1);
+    assert_eq!(args.paths[0].path, PathBuf::from("/elsewhere"));
+    assert_eq!(args.min_size, Some(1024 * 1024));
+    assert_eq!(args.hash_algo, HashAlgorithm::XxHash);
+    assert_eq!(
+        args.exclude.len(),
+        2,
+        "Options not on the command line still come from the file"
+    );
+}
+
+#[test]
+fn test_invalid_config_values_are_rejected() {
+    let error = merged(r#"min-size = "lots""#, &[]).unwrap_err();
+    assert!(error.to_string().contains("min-size"));
+
+    assert!(merged(r#"hash-algo = "md5""#, &[]).is_err());
+    assert!(
+        toml::from_str::<Config>("no-such-option = 1").is_err(),
+        "Typos in option names are reported"
+    );
+}
+
+#[test]
+fn test_config_sets_cache_pragmas() {
+    let config = r#"cache-pragma = ["synchronous=OFF", "cache_size=-8000"]"#;
+    let args = merged(config, &[]).unwrap();
+    let pragmas: Vec<(&str, &str)> = args
+        .cache_pragmas
+        .iter()
+        .map(|pragma| (pragma.name.as_str(), pragma.value.as_str()))
+        .collect();
+    assert_eq!(pragmas, [("synchronous", "OFF"), ("cache_size", "-8000")]);
+
+    let args = merged(config, &["--cache-pragma", "temp_store=MEMORY"]).unwrap();
+    assert_eq!(
+        args.cache_pragmas.len(),
+        1,
+        "The command line replaces the file's"
+    );
+
+    let error = merged(r#"cache-pragma = ["journal_mode=OFF"]"#, &[]).unwrap_err();
+    assert!(error.to_string().contains("cache-pragma"));
+}
+
+#[test]
+fn test_explicit_config_file_must_exist() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    assert!(Config::find(Some(&path)).is_err());
+
+    fs::write(&path, "threads = 1\nverify = true\n").unwrap();
+    let config = Config::find(Some(&path)).unwrap().unwrap();
+    assert_eq!(config.threads, Some(1));
+    assert_eq!(config.verify, Some(true));
+}
+
+#[test]
+fn test_command_line_turns_off_config_switches() {
+    let config = "include-hidden = true\nno-cache = true\n";
+    let args = merged(config, &["--no-include-hidden", "--cache"]).unwrap();
+    assert!(!args.include_hidden);
+    assert!(!args.no_cache);
+
+    let args = merged("include-hidden = false", &["--include-hidden"]).unwrap();
+    assert!(args.include_hidden, "The command line wins either way");
+}
+
+#[test]
+fn test_conflicts_are_checked_after_merging() {
+    let error = merged("trash = true", &["--hardlink"]).unwrap_err();
+    assert!(error.to_string().contains("--hardlink"), "Got: {}", error);
+    assert!(merged("trash = true", &["--hardlink", "--no-trash"]).is_ok());
+
+    assert!(merged(r#"min-size = "1K""#, &["--size-exact", "4K"]).is_err());
+    assert!(merged(r#"by = "name""#, &["--quick"]).is_err());
+}
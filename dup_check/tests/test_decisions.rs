@@ -0,0 +1,78 @@
+use dup_check::{
+    decisions::{Decision, DecisionStore},
+    scanner::Scanner,
+};
+use std::fs::File;
+use std::io::Write;
+use tempfile::TempDir;
+
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) {
+    let mut file = File::create(dir.path().join(name)).unwrap();
+    file.write_all(content).unwrap();
+}
+
+#[test]
+fn test_decisions_persist_across_runs() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a1.txt", b"first group");
+    create_temp_file(&temp_dir, "a2.txt", b"first group");
+    create_temp_file(&temp_dir, "b1.txt", b"the second group");
+    create_temp_file(&temp_dir, "b2.txt", b"the second group");
+
+    let state_dir = TempDir::new().unwrap();
+    let decisions_path = state_dir.path().join("decisions.json");
+    let scanner = Scanner::new(false, None, None).unwrap();
+
+    // First run: decide only one of the two groups
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let mut store = DecisionStore::load(&decisions_path).unwrap();
+    assert_eq!(store.pending(&duplicates).len(), 2);
+    let decided = store.pending(&duplicates)[0].clone();
+    store
+        .record(&decided, &duplicates[&decided], Decision::Delete)
+        .unwrap();
+
+    // Second run over the same tree picks up the saved decision
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let store = DecisionStore::load(&decisions_path).unwrap();
+    assert_eq!(
+        store.get(&decided, &duplicates[&decided]),
+        Some(Decision::Delete)
+    );
+
+    let pending = store.pending(&duplicates);
+    assert_eq!(pending.len(), 1, "Only the undecided group should remain");
+    assert_ne!(pending[0], &decided);
+
+    let to_delete = store.groups_to_delete(&duplicates);
+    assert_eq!(to_delete.len(), 1);
+    assert!(to_delete.contains_key(&decided));
+}
+
+#[test]
+fn test_decision_is_asked_again_when_a_copy_joins_the_group() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a1.txt", b"decided group");
+    create_temp_file(&temp_dir, "a2.txt", b"decided group");
+
+    let state_dir = TempDir::new().unwrap();
+    let decisions_path = state_dir.path().join("decisions.json");
+    let scanner = Scanner::new(false, None, None).unwrap();
+
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let mut store = DecisionStore::load(&decisions_path).unwrap();
+    let (hash, files) = duplicates.iter().next().unwrap();
+    store.record(hash, files, Decision::Delete).unwrap();
+
+    // A new copy appears after the decision was made
+    create_temp_file(&temp_dir, "a3.txt", b"decided group");
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let store = DecisionStore::load(&decisions_path).unwrap();
+
+    assert_eq!(store.get(hash, &duplicates[hash]), None);
+    assert_eq!(store.pending(&duplicates), [hash]);
+    assert!(
+        store.groups_to_delete(&duplicates).is_empty(),
+        "The new copy must be shown before anything is deleted"
+    );
+}
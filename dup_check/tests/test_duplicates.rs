@@ -0,0 +1,516 @@
+use dup_check::{
+    duplicates::{self, KeepPolicy, KeepStrategy, TopGroups},
+    file_info::FileInfo,
+    interactive,
+    scanner::Scanner,
+    ui,
+};
+use filetime::FileTime;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+use tempfile::TempDir;
+
+/// Helper function to create a temporary file with specific content
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+    let file_path = dir.path().join(name);
+    let mut file = File::create(&file_path).unwrap();
+    file.write_all(content).unwrap();
+    file_path
+}
+
+#[test]
+fn test_keep_path_survives_deletion() {
+    let temp_dir = TempDir::new().unwrap();
+    let content = b"identical content";
+    let a = create_temp_file(&temp_dir, "a.txt", content);
+    let b = create_temp_file(&temp_dir, "b.txt", content);
+    let keep = create_temp_file(&temp_dir, "keep_me.txt", content);
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    let policy = KeepPolicy {
+        keep_paths: vec![keep.clone()],
+        ..Default::default()
+    };
+    assert!(policy.apply(&mut duplicates).is_empty());
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+
+    assert!(keep.exists(), "The designated original should survive");
+    assert!(!a.exists() && !b.exists(), "Other copies should be deleted");
+}
+
+#[test]
+fn test_keep_path_conflict_picks_first_listed() {
+    let temp_dir = TempDir::new().unwrap();
+    let content = b"identical content";
+    create_temp_file(&temp_dir, "a.txt", content);
+    let second = create_temp_file(&temp_dir, "b.txt", content);
+    let first = create_temp_file(&temp_dir, "c.txt", content);
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    let policy = KeepPolicy {
+        keep_paths: vec![first.clone(), second.clone()],
+        ..Default::default()
+    };
+    let conflicts = policy.apply(&mut duplicates);
+
+    assert_eq!(conflicts.len(), 1, "The conflict should be reported");
+    let files = duplicates.values().next().unwrap();
+    assert!(files[0].path.ends_with("c.txt"), "First listed path wins");
+    assert!(conflicts[0].also_listed[0].ends_with("b.txt"));
+}
+
+#[test]
+fn test_deletable_extensions_protect_other_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let docs = [
+        create_temp_file(&temp_dir, "report.docx", b"word document"),
+        create_temp_file(&temp_dir, "report copy.docx", b"word document"),
+    ];
+    let photos = [
+        create_temp_file(&temp_dir, "photo.jpg", b"jpeg bytes"),
+        create_temp_file(&temp_dir, "photo copy.JPG", b"jpeg bytes"),
+    ];
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 2);
+
+    let options = ui::DeleteOptions {
+        deletable_extensions: Some(vec!["jpg".to_string(), "png".to_string()]),
+        ..Default::default()
+    };
+    ui::delete_duplicates(&duplicates, &options).unwrap();
+
+    assert!(
+        docs.iter().all(|path| path.exists()),
+        "Duplicates outside the allowlist must survive"
+    );
+    assert_eq!(
+        photos.iter().filter(|path| path.exists()).count(),
+        1,
+        "Allowed duplicates are removed, keeping the original"
+    );
+}
+
+#[test]
+fn test_verify_deletion_reports_files_that_survive() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = create_temp_file(&temp_dir, "a.txt", b"same");
+    let b = create_temp_file(&temp_dir, "b.txt", b"same");
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    // A filesystem where removal reports success but does nothing
+    let no_op = |_: &std::path::Path| Ok(());
+
+    let unverified = ui::DeleteOptions::default();
+    assert!(ui::delete_duplicates_with(&duplicates, &unverified, no_op).is_ok());
+
+    let verified = ui::DeleteOptions {
+        verify_deletion: true,
+        ..Default::default()
+    };
+    let err = ui::delete_duplicates_with(&duplicates, &verified, no_op).unwrap_err();
+    assert!(err.to_string().contains("1 deleted file(s) still exist"));
+    assert!(a.exists() && b.exists());
+
+    ui::delete_duplicates(&duplicates, &verified).unwrap();
+    assert_eq!(a.exists() as u8 + b.exists() as u8, 1);
+}
+
+#[test]
+fn test_dry_run_leaves_files_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    let paths = [
+        create_temp_file(&temp_dir, "a.txt", b"same"),
+        create_temp_file(&temp_dir, "b.txt", b"same"),
+        create_temp_file(&temp_dir, "c.txt", b"same"),
+    ];
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    let options = ui::DeleteOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let mut removed = 0;
+    ui::delete_duplicates_with(&duplicates, &options, |_| {
+        removed += 1;
+        Ok(())
+    })
+    .unwrap();
+    ui::delete_duplicates(&duplicates, &options).unwrap();
+
+    assert_eq!(removed, 0, "A dry run must never remove files");
+    assert!(paths.iter().all(|path| path.exists()));
+}
+
+#[test]
+fn test_readonly_file_kept_as_original() {
+    let temp_dir = TempDir::new().unwrap();
+    let writable = create_temp_file(&temp_dir, "a_writable.txt", b"same content");
+    let readonly = create_temp_file(&temp_dir, "b_readonly.txt", b"same content");
+    let mut permissions = std::fs::metadata(&readonly).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&readonly, permissions).unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    let policy = KeepPolicy {
+        original_if_readonly: true,
+        ..Default::default()
+    };
+    policy.apply(&mut duplicates);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(
+        files[0].path, readonly,
+        "The read-only copy is the original"
+    );
+    assert!(files[0].pinned);
+
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+    assert!(readonly.exists(), "The read-only copy must be kept");
+    assert!(!writable.exists(), "The writable copy is the duplicate");
+}
+
+#[test]
+fn test_top_groups_keeps_n_most_wasteful() {
+    let group = |size: u64, copies: usize| -> Vec<FileInfo> {
+        (0..copies)
+            .map(|i| FileInfo::new(PathBuf::from(format!("{}_{}", size, i)), size))
+            .collect()
+    };
+
+    let mut top = TopGroups::new(Some(2));
+    top.insert("a".to_string(), group(10, 2)); // wastes 10
+    top.insert("b".to_string(), group(50, 3)); // wastes 100
+    top.insert("c".to_string(), group(5, 2)); // wastes 5, rejected
+    top.insert("d".to_string(), group(30, 2)); // wastes 30, evicts "a"
+
+    assert_eq!(top.dropped(), 2);
+    let mut keys: Vec<String> = top.into_groups().into_keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["b".to_string(), "d".to_string()]);
+}
+
+#[test]
+fn test_linking_keeps_original_mtime() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = create_temp_file(&temp_dir, "original.txt", b"same bytes");
+    let duplicate = create_temp_file(&temp_dir, "duplicate.txt", b"same bytes");
+    let old = FileTime::from_unix_time(1_500_000_000, 0);
+    filetime::set_file_mtime(&original, old).unwrap();
+
+    let outcome = ui::replace_with_link(&original, &duplicate, ui::LinkKind::Hard, false).unwrap();
+
+    let mtime = FileTime::from_last_modification_time(&std::fs::metadata(&original).unwrap());
+    assert_eq!(mtime, old, "Linking must not touch the original's mtime");
+    assert_eq!(std::fs::read(&duplicate).unwrap(), b"same bytes");
+    assert!(
+        outcome.replaced_mtime.is_some(),
+        "The duplicate's newer mtime should be reported"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_takes_over_duplicate_times() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = create_temp_file(&temp_dir, "original.txt", b"same bytes");
+    let duplicate = create_temp_file(&temp_dir, "duplicate.txt", b"same bytes");
+    let original_time = FileTime::from_unix_time(1_500_000_000, 0);
+    let duplicate_time = FileTime::from_unix_time(1_600_000_000, 0);
+    filetime::set_file_mtime(&original, original_time).unwrap();
+    filetime::set_file_mtime(&duplicate, duplicate_time).unwrap();
+
+    ui::replace_with_link(&original, &duplicate, ui::LinkKind::Symbolic, true).unwrap();
+
+    let link = std::fs::symlink_metadata(&duplicate).unwrap();
+    assert!(link.file_type().is_symlink());
+    assert_eq!(FileTime::from_last_modification_time(&link), duplicate_time);
+    let target = std::fs::metadata(&original).unwrap();
+    assert_eq!(
+        FileTime::from_last_modification_time(&target),
+        original_time
+    );
+}
+
+#[test]
+fn test_keep_strategy_picks_survivor() {
+    let file = |path: &str, mtime_secs: Option<u64>| {
+        let mut file = FileInfo::new(PathBuf::from(path), 10);
+        file.modified = mtime_secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        file
+    };
+    let group = vec![
+        file("/photos/2021/b.jpg", Some(300)),
+        file("/a.jpg", Some(200)),
+        file("/photos/c.jpg", Some(100)),
+        file("/photos/unknown.jpg", None),
+    ];
+
+    let survivor = |strategy| {
+        let mut duplicates = HashMap::from([("hash".to_string(), group.clone())]);
+        let policy = KeepPolicy {
+            strategy: Some(strategy),
+            ..Default::default()
+        };
+        policy.apply(&mut duplicates);
+        duplicates["hash"][0].path.clone()
+    };
+
+    assert_eq!(
+        survivor(KeepStrategy::Oldest),
+        PathBuf::from("/photos/c.jpg")
+    );
+    assert_eq!(
+        survivor(KeepStrategy::Newest),
+        PathBuf::from("/photos/2021/b.jpg")
+    );
+    assert_eq!(
+        survivor(KeepStrategy::ShortestPath),
+        PathBuf::from("/a.jpg")
+    );
+    assert_eq!(
+        survivor(KeepStrategy::FirstAlphabetical),
+        PathBuf::from("/a.jpg")
+    );
+}
+
+#[test]
+fn test_reclaimable_bytes_excludes_survivor() {
+    let file = |path: &str, size: u64, mtime_secs: u64| {
+        let mut file = FileInfo::new(PathBuf::from(path), size);
+        file.modified = Some(UNIX_EPOCH + Duration::from_secs(mtime_secs));
+        file
+    };
+    // Sizes differ so the survivor shows in the total
+    let group = vec![
+        file("/b.bin", 100, 300),
+        file("/a.bin", 40, 200),
+        file("/c.bin", 7, 100),
+    ];
+
+    assert_eq!(duplicates::reclaimable_bytes(&group, None), 47);
+    assert_eq!(
+        duplicates::reclaimable_bytes(&group, Some(KeepStrategy::Oldest)),
+        140
+    );
+    assert_eq!(
+        duplicates::reclaimable_bytes(&group, Some(KeepStrategy::FirstAlphabetical)),
+        107
+    );
+    assert_eq!(
+        duplicates::reclaimable_bytes(&group, Some(KeepStrategy::Newest)),
+        47
+    );
+    assert_eq!(duplicates::reclaimable_bytes(&[], None), 0);
+}
+
+#[test]
+fn test_reclaimable_bytes_matches_policy_survivor() {
+    let group: Vec<FileInfo> = [("/long/path/x", 5), ("/y", 9), ("/mid/z", 3)]
+        .iter()
+        .map(|&(path, size)| FileInfo::new(PathBuf::from(path), size))
+        .collect();
+    let expected = duplicates::reclaimable_bytes(&group, Some(KeepStrategy::ShortestPath));
+
+    let mut groups = HashMap::from([("hash".to_string(), group)]);
+    KeepPolicy {
+        strategy: Some(KeepStrategy::ShortestPath),
+        ..Default::default()
+    }
+    .apply(&mut groups);
+
+    assert_eq!(expected, 8);
+    assert_eq!(duplicates::wasted_bytes(&groups["hash"]), expected);
+}
+
+#[test]
+fn test_keep_oldest_survives_deletion() {
+    let temp_dir = TempDir::new().unwrap();
+    let newer = create_temp_file(&temp_dir, "a.txt", b"same content");
+    let oldest = create_temp_file(&temp_dir, "b.txt", b"same content");
+    let old = FileTime::from_unix_time(1_500_000_000, 0);
+    filetime::set_file_mtime(&oldest, old).unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let policy = KeepPolicy {
+        strategy: Some(KeepStrategy::Oldest),
+        ..Default::default()
+    };
+    policy.apply(&mut duplicates);
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+
+    assert!(oldest.exists(), "The oldest copy should be kept");
+    assert!(!newer.exists());
+}
+
+#[test]
+fn test_path_prefer_keeps_copy_under_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    for dir in ["copies", "master", "other"] {
+        std::fs::create_dir(temp_dir.path().join(dir)).unwrap();
+    }
+    let copy = create_temp_file(&temp_dir, "copies/a.txt", b"master content");
+    let master = create_temp_file(&temp_dir, "master/z.txt", b"master content");
+    create_temp_file(&temp_dir, "other/a.txt", b"other content");
+    create_temp_file(&temp_dir, "other/b.txt", b"other content");
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let policy = KeepPolicy {
+        strategy: Some(KeepStrategy::FirstAlphabetical),
+        prefer_dir: Some(temp_dir.path().join("master")),
+        ..Default::default()
+    };
+    policy.apply(&mut duplicates);
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+
+    assert!(master.exists(), "The copy under the preferred dir is kept");
+    assert!(!copy.exists());
+    // Groups without a member under it fall back to the strategy
+    assert!(temp_dir.path().join("other/a.txt").exists());
+    assert!(!temp_dir.path().join("other/b.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hardlink_duplicates_keeps_every_path() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let paths = [
+        create_temp_file(&temp_dir, "a.txt", b"shared content"),
+        create_temp_file(&temp_dir, "b.txt", b"shared content"),
+        create_temp_file(&temp_dir, "c.txt", b"shared content"),
+    ];
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    ui::hardlink_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+
+    let inode = std::fs::metadata(&paths[0]).unwrap().ino();
+    for path in &paths {
+        let metadata = std::fs::metadata(path).unwrap();
+        assert_eq!(metadata.ino(), inode, "{} should be a link", path.display());
+        assert_eq!(std::fs::read(path).unwrap(), b"shared content");
+    }
+    assert_eq!(std::fs::metadata(&paths[0]).unwrap().nlink(), 3);
+}
+
+#[test]
+fn test_picked_files_are_deleted_keeping_the_rest() {
+    let temp_dir = TempDir::new().unwrap();
+    let paths = [
+        create_temp_file(&temp_dir, "a.txt", b"same"),
+        create_temp_file(&temp_dir, "b.txt", b"same"),
+        create_temp_file(&temp_dir, "c.txt", b"same"),
+    ];
+    let files: Vec<FileInfo> = paths
+        .iter()
+        .map(|path| FileInfo::new(path.clone(), 4))
+        .collect();
+
+    assert!(interactive::deletion_group(&files, &[]).is_none());
+    assert!(
+        interactive::deletion_group(&files, &[0, 1, 2]).is_none(),
+        "Never delete every copy"
+    );
+
+    // Picking the suggested original keeps another file instead
+    let group = interactive::deletion_group(&files, &[0, 2]).unwrap();
+    let duplicates = HashMap::from([("hash".to_string(), group)]);
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+
+    assert!(!paths[0].exists());
+    assert!(paths[1].exists());
+    assert!(!paths[2].exists());
+}
+
+#[test]
+fn test_display_duplicates_shows_modification_times() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut old = FileInfo::new(PathBuf::from("/a/old.txt"), 10);
+    old.modified = Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+    let mut new = FileInfo::new(PathBuf::from("/a/new.txt"), 10);
+    new.modified = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let duplicates = HashMap::from([("hash".to_string(), vec![old, new])]);
+
+    let mut out = Vec::new();
+    ui::display_duplicates(&duplicates, &ui::DisplayOptions::default(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("/a/old.txt  (modified 2001-09-09 01:46:40 UTC)\n"));
+    assert!(out.contains("/a/new.txt  (modified 2023-11-14 22:13:20 UTC, newest)\n"));
+}
+
+#[test]
+fn test_display_duplicates_ascii_markers() {
+    let duplicates = HashMap::from([(
+        "hash".to_string(),
+        vec![
+            FileInfo::new(PathBuf::from("/a/keep.txt"), 10),
+            FileInfo::new(PathBuf::from("/a/copy.txt"), 10),
+        ],
+    )]);
+
+    let mut out = Vec::new();
+    let options = ui::DisplayOptions {
+        markers: ui::Markers::Ascii,
+        ..Default::default()
+    };
+    ui::display_duplicates(&duplicates, &options, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.is_ascii(), "No emoji expected in:\n{}", out);
+    assert!(out.contains("[keep] /a/keep.txt\n"));
+    assert!(out.contains("[dup]  /a/copy.txt\n"));
+}
+
+#[test]
+fn test_display_duplicates_summary_only() {
+    let duplicates = HashMap::from([
+        (
+            "a".to_string(),
+            vec![
+                FileInfo::new(PathBuf::from("/a/keep.txt"), 10),
+                FileInfo::new(PathBuf::from("/a/copy.txt"), 10),
+            ],
+        ),
+        (
+            "b".to_string(),
+            vec![
+                FileInfo::new(PathBuf::from("/b/keep.bin"), 100),
+                FileInfo::new(PathBuf::from("/b/copy1.bin"), 100),
+                FileInfo::new(PathBuf::from("/b/copy2.bin"), 100),
+            ],
+        ),
+    ]);
+
+    let mut out = Vec::new();
+    let options = ui::DisplayOptions {
+        sizes: ui::SizeFormat::Bytes,
+        summary_only: true,
+        ..Default::default()
+    };
+    ui::display_duplicates(&duplicates, &options, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("Found 2 duplicate groups"));
+    assert!(out.contains("Total duplicate files: 5"));
+    assert!(out.contains("Wasted space: 210"));
+    assert!(!out.contains('/'), "No paths expected in:\n{}", out);
+}
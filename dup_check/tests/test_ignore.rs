@@ -0,0 +1,67 @@
+use dup_check::{
+    ignore::{IgnoreEntry, IgnoreList},
+    scanner::Scanner,
+};
+use std::fs::File;
+use std::io::Write;
+use tempfile::TempDir;
+
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) {
+    let mut file = File::create(dir.path().join(name)).unwrap();
+    file.write_all(content).unwrap();
+}
+
+#[test]
+fn test_ignored_group_does_not_reappear() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a1.txt", b"first group");
+    create_temp_file(&temp_dir, "a2.txt", b"first group");
+    create_temp_file(&temp_dir, "b1.txt", b"the second group");
+    create_temp_file(&temp_dir, "b2.txt", b"the second group");
+
+    let state_dir = TempDir::new().unwrap();
+    let list_path = state_dir.path().join("ignored.json");
+    let scanner = Scanner::new(false, None, None).unwrap();
+
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let ignored_hash = duplicates
+        .iter()
+        .find(|(_, files)| files.iter().any(|file| file.path.ends_with("a1.txt")))
+        .map(|(hash, _)| hash.clone())
+        .unwrap();
+    let mut list = IgnoreList::load(&list_path).unwrap();
+    list.add(IgnoreEntry::Hash {
+        hash: ignored_hash.clone(),
+    })
+    .unwrap();
+
+    // A later run loads the saved list and hides the group
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let list = IgnoreList::load(&list_path).unwrap();
+    assert_eq!(list.filter(&mut duplicates), 1);
+    assert_eq!(duplicates.len(), 1);
+    assert!(!duplicates.contains_key(&ignored_hash));
+}
+
+#[test]
+fn test_ignore_by_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same");
+    create_temp_file(&temp_dir, "b.txt", b"same");
+
+    let state_dir = TempDir::new().unwrap();
+    let mut list = IgnoreList::load(&state_dir.path().join("ignored.json")).unwrap();
+    list.add(IgnoreEntry::Paths {
+        paths: vec![temp_dir.path().join("a.txt"), temp_dir.path().join("b.txt")],
+    })
+    .unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(list.filter(&mut duplicates), 1);
+
+    // A new copy makes the group worth reporting again
+    create_temp_file(&temp_dir, "c.txt", b"same");
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(list.filter(&mut duplicates), 0);
+}
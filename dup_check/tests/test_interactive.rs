@@ -0,0 +1,175 @@
+use dup_check::{
+    file_info::FileInfo,
+    interactive,
+    ui::{DeleteOptions, LinkKind, Markers, SizeFormat},
+};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_describe_differences_text_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = temp_dir.path().join("notes.txt");
+    let other = temp_dir.path().join("notes copy.txt");
+    fs::write(&original, "first line\nsecond line\nthird line\n").unwrap();
+    fs::write(&other, "first line\nSECOND LINE\nthird line\n").unwrap();
+
+    let diff = interactive::describe_differences(&original, &other, SizeFormat::Human).unwrap();
+
+    assert!(
+        diff.contains("-second line"),
+        "Removed line is shown: {}",
+        diff
+    );
+    assert!(
+        diff.contains("+SECOND LINE"),
+        "Added line is shown: {}",
+        diff
+    );
+    assert!(diff.contains(" first line"), "Context is kept: {}", diff);
+    assert!(diff.contains("notes copy.txt"), "Header names the files");
+}
+
+#[test]
+fn test_describe_differences_binary_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = temp_dir.path().join("a.bin");
+    let other = temp_dir.path().join("b.bin");
+    fs::write(&original, [0xff, 0xfe, 0x00]).unwrap();
+    fs::write(&other, [0xff, 0xfe, 0x00, 0x01]).unwrap();
+
+    let comparison =
+        interactive::describe_differences(&original, &other, SizeFormat::Human).unwrap();
+
+    assert!(comparison.contains("size"));
+    assert!(comparison.contains("3 B") && comparison.contains("4 B"));
+}
+
+#[test]
+fn test_interactive_sizes_follow_size_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = temp_dir.path().join("a.bin");
+    let other = temp_dir.path().join("b.bin");
+    fs::write(&original, vec![0xff; 2048]).unwrap();
+    fs::write(&other, vec![0xfe; 2048]).unwrap();
+
+    let comparison =
+        interactive::describe_differences(&original, &other, SizeFormat::Bytes).unwrap();
+    assert!(comparison.contains("2048"), "{}", comparison);
+    assert!(!comparison.contains("KB"), "{}", comparison);
+
+    let files: Vec<FileInfo> = ["keep.bin", "copy.bin"]
+        .iter()
+        .map(|name| FileInfo::new(PathBuf::from(name), 2048))
+        .collect();
+    let options = DeleteOptions {
+        size_format: SizeFormat::Bytes,
+        ..ascii()
+    };
+    let mut output = Vec::new();
+    interactive::confirm_group_deletion_with(
+        &mut Cursor::new("n\n"),
+        &mut output,
+        &files,
+        &files[0],
+        None,
+        &mut false,
+        &options,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("(2048 each)"), "{}", output);
+}
+
+fn group() -> Vec<FileInfo> {
+    ["keep.txt", "copy1.txt", "copy2.txt"]
+        .iter()
+        .map(|name| FileInfo::new(PathBuf::from(name), 10))
+        .collect()
+}
+
+fn ascii() -> DeleteOptions {
+    DeleteOptions {
+        markers: Markers::Ascii,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_confirm_group_deletion_answers() {
+    let files = group();
+    let options = ascii();
+    let mut delete_remaining = false;
+    let mut ask = |answer: &str| {
+        let mut input = Cursor::new(answer.to_string());
+        interactive::confirm_group_deletion_with(
+            &mut input,
+            &mut Vec::new(),
+            &files,
+            &files[0],
+            None,
+            &mut delete_remaining,
+            &options,
+        )
+        .unwrap()
+    };
+
+    assert!(ask("y\n"));
+    assert!(!ask("n\n"));
+    assert!(!ask(""), "End of input declines");
+    assert!(ask("maybe\nY\n"), "Unclear answers ask again");
+}
+
+#[test]
+fn test_confirm_group_deletion_all_remaining() {
+    let files = group();
+    let options = ascii();
+    let mut delete_remaining = false;
+    let mut input = Cursor::new("a\n");
+
+    for _ in 0..3 {
+        assert!(interactive::confirm_group_deletion_with(
+            &mut input,
+            &mut Vec::new(),
+            &files,
+            &files[0],
+            None,
+            &mut delete_remaining,
+            &options,
+        )
+        .unwrap());
+    }
+    assert!(delete_remaining);
+}
+
+#[test]
+fn test_confirm_group_deletion_lists_affected_files() {
+    let mut files = group();
+    files[2].pinned = true;
+    let mut output = Vec::new();
+    interactive::confirm_group_deletion_with(
+        &mut Cursor::new("n\n"),
+        &mut output,
+        &files,
+        &files[0],
+        Some(LinkKind::Hard),
+        &mut false,
+        &ascii(),
+    )
+    .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains("Keep keep.txt (10 B each) and replace with hard links 1 duplicate(s):"),
+        "Got:\n{}",
+        output
+    );
+    assert!(output.contains("    copy1.txt\n"), "Got:\n{}", output);
+    assert!(
+        output.contains("Kept (marked as original): copy2.txt"),
+        "Got:\n{}",
+        output
+    );
+}
@@ -0,0 +1,230 @@
+use dup_check::{
+    cache::Cache,
+    file_info::FileInfo,
+    journal::{self, ScanJournal},
+    scanner::{ScanOptions, ScanRoot, Scanner},
+    utils::HashAlgorithm,
+};
+use filetime::FileTime;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+    let file_path = dir.path().join(name);
+    let mut file = File::create(&file_path).unwrap();
+    file.write_all(content).unwrap();
+    file_path
+}
+
+/// A file as the scanner collects it
+fn scanned(path: &PathBuf) -> FileInfo {
+    let metadata = fs::metadata(path).unwrap();
+    let mut file = FileInfo::new(path.clone(), metadata.len());
+    file.modified = metadata.modified().ok();
+    file
+}
+
+#[test]
+fn test_resume_after_crash() {
+    let temp_dir = TempDir::new().unwrap();
+    let first = [
+        create_temp_file(&temp_dir, "a1.txt", b"first"),
+        create_temp_file(&temp_dir, "a2.txt", b"first"),
+    ];
+    create_temp_file(&temp_dir, "b1.txt", b"second!");
+    create_temp_file(&temp_dir, "b2.txt", b"second!");
+
+    // The journal a scan leaves behind when it dies after finishing the
+    // 5-byte size group, torn in the middle of its next line
+    let state_dir = TempDir::new().unwrap();
+    let journal_path = state_dir.path().join("journal.jsonl");
+    let roots = [temp_dir.path().to_path_buf()];
+    let crashed = ScanJournal::create(&journal_path, &roots, HashAlgorithm::Sha256).unwrap();
+    let members: Vec<FileInfo> = first.iter().map(scanned).collect();
+    let group = vec![("journaled-hash".to_string(), members.clone())];
+    crashed
+        .record(5, &journal::fingerprint(&members), &group)
+        .unwrap();
+    drop(crashed);
+    let mut file = OpenOptions::new().append(true).open(&journal_path).unwrap();
+    write!(file, "{{\"size\":7,\"memb").unwrap();
+
+    let scanner = Scanner::with_options(
+        None,
+        ScanOptions {
+            journal: Some(journal_path.clone()),
+            resume: true,
+            ..Default::default()
+        },
+    );
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 2, "Recovered and new groups are merged");
+    assert_eq!(
+        duplicates["journaled-hash"].len(),
+        2,
+        "The finished size group is taken from the journal, not rehashed"
+    );
+    assert!(
+        !journal_path.exists(),
+        "A completed scan removes its journal"
+    );
+}
+
+#[test]
+fn test_resume_from_journal_in_cache() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = [
+        create_temp_file(&temp_dir, "a1.txt", b"first"),
+        create_temp_file(&temp_dir, "a2.txt", b"first"),
+    ];
+    let cache = Arc::new(Cache::in_memory().unwrap());
+    let roots = [temp_dir.path().to_path_buf()];
+    let journal = |resume| {
+        ScanJournal::in_cache(Arc::clone(&cache), &roots, HashAlgorithm::Sha256, resume).unwrap()
+    };
+
+    let crashed = journal(false);
+    let members: Vec<FileInfo> = files.iter().map(scanned).collect();
+    let group = vec![("journaled-hash".to_string(), members.clone())];
+    crashed
+        .record(5, &journal::fingerprint(&members), &group)
+        .unwrap();
+    drop(crashed);
+
+    let options = ScanOptions {
+        resume: true,
+        ..Default::default()
+    };
+    let duplicates = Scanner::with_options(Some(Arc::clone(&cache)), options)
+        .find_duplicates(temp_dir.path())
+        .unwrap();
+    assert!(duplicates.contains_key("journaled-hash"));
+    assert_eq!(
+        journal(true).recovered_len(),
+        0,
+        "A completed scan clears its journal"
+    );
+
+    let other = TempDir::new().unwrap();
+    let other_journal = ScanJournal::in_cache(
+        Arc::clone(&cache),
+        &[other.path().to_path_buf()],
+        HashAlgorithm::Sha256,
+        false,
+    )
+    .unwrap();
+    other_journal.record(1, "members", &[]).unwrap();
+    assert_eq!(
+        journal(true).recovered_len(),
+        0,
+        "Journals of other roots are kept apart"
+    );
+}
+
+#[test]
+fn test_changed_files_are_not_taken_from_journal() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = [
+        create_temp_file(&temp_dir, "a1.txt", b"first"),
+        create_temp_file(&temp_dir, "a2.txt", b"first"),
+    ];
+
+    let state_dir = TempDir::new().unwrap();
+    let journal_path = state_dir.path().join("journal.jsonl");
+    let roots = [temp_dir.path().to_path_buf()];
+    let crashed = ScanJournal::create(&journal_path, &roots, HashAlgorithm::Sha256).unwrap();
+    let members: Vec<FileInfo> = files.iter().map(scanned).collect();
+    let group = vec![("journaled-hash".to_string(), members.clone())];
+    crashed
+        .record(5, &journal::fingerprint(&members), &group)
+        .unwrap();
+    drop(crashed);
+
+    // A new member of the size group invalidates the journaled result
+    create_temp_file(&temp_dir, "a3.txt", b"first");
+
+    let scanner = Scanner::with_options(
+        None,
+        ScanOptions {
+            journal: Some(journal_path),
+            resume: true,
+            ..Default::default()
+        },
+    );
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert!(!duplicates.contains_key("journaled-hash"));
+    assert_eq!(duplicates.values().next().unwrap().len(), 3);
+}
+
+#[test]
+fn test_resume_after_dropping_scanner() {
+    let temp_dir = TempDir::new().unwrap();
+    for i in 0..500 {
+        let content = vec![b'x'; i + 1];
+        create_temp_file(&temp_dir, &format!("{}_a.bin", i), &content);
+        create_temp_file(&temp_dir, &format!("{}_b.bin", i), &content);
+    }
+    let roots = vec![ScanRoot::new(temp_dir.path())];
+
+    let state_dir = TempDir::new().unwrap();
+    let journal_path = state_dir.path().join("journal.jsonl");
+    let options = || ScanOptions {
+        journal: Some(journal_path.clone()),
+        resume: true,
+        ..Default::default()
+    };
+
+    // Interrupted after its first group
+    let mut stream =
+        Scanner::with_options(None, options()).find_duplicates_streaming(roots.clone());
+    let (hash, files) = stream.next().unwrap().unwrap();
+    drop(stream);
+    assert!(
+        journal_path.exists(),
+        "An interrupted scan keeps its journal"
+    );
+
+    // Break the copies without touching their modification times: hashing
+    // them again would no longer pair them
+    for (i, file) in files.iter().enumerate() {
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(&file.path).unwrap());
+        let mut content = fs::read(&file.path).unwrap();
+        content[0] = b'0' + i as u8;
+        fs::write(&file.path, content).unwrap();
+        filetime::set_file_mtime(&file.path, mtime).unwrap();
+    }
+
+    let duplicates = Scanner::with_options(None, options())
+        .find_duplicates_in_roots(&roots, |_, _| {})
+        .unwrap();
+    assert_eq!(
+        duplicates[&hash].len(),
+        files.len(),
+        "The group finished before the interruption is taken from the journal"
+    );
+    assert_eq!(duplicates.len(), 500);
+    assert!(!journal_path.exists());
+}
+
+#[test]
+fn test_default_journal_is_kept_per_root_set() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    let path = |roots: &[&std::path::Path]| {
+        let roots: Vec<PathBuf> = roots.iter().map(|root| root.to_path_buf()).collect();
+        ScanJournal::default_path(&roots).unwrap()
+    };
+    let (first, second) = (first.path(), second.path());
+
+    assert_ne!(path(&[first]), path(&[second]));
+    assert_eq!(
+        path(&[first]),
+        path(&[&first.join(".")]),
+        "Roots are compared once resolved"
+    );
+    assert_eq!(path(&[first, second]), path(&[second, first]));
+}
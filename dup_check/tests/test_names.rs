@@ -0,0 +1,98 @@
+use dup_check::{
+    file_info::FileInfo,
+    names,
+    scanner::{GroupBy, ScanOptions, Scanner},
+};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_strip_copy_suffix() {
+    assert_eq!(names::strip_copy_suffix("photo (1)"), Some("photo"));
+    assert_eq!(names::strip_copy_suffix("photo copy"), Some("photo"));
+    assert_eq!(names::strip_copy_suffix("photo copy 2"), Some("photo"));
+    assert_eq!(names::strip_copy_suffix("photo - Copy"), Some("photo"));
+    assert_eq!(names::strip_copy_suffix("photo - Copy (2)"), Some("photo"));
+    assert_eq!(names::strip_copy_suffix("photo"), None);
+    assert_eq!(names::strip_copy_suffix("photo 2"), None);
+    assert_eq!(names::strip_copy_suffix("(1)"), None);
+}
+
+#[test]
+fn test_dedupe_names_keeps_unsuffixed_original() {
+    let temp_dir = TempDir::new().unwrap();
+    let write = |name: &str, content: &[u8]| {
+        let path = temp_dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    };
+    let original = write("photo.jpg", b"picture");
+    let copy = write("photo (1).jpg", b"picture");
+    // Same name pattern and size, different content
+    write("photo (2).jpg", b"PICTURE");
+    // Identical content without a copy-style name
+    write("holiday.jpg", b"picture");
+
+    let options = ScanOptions {
+        dedupe_names: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![original, copy],
+        "Only the identical suffixed copy is grouped, after the original"
+    );
+}
+
+#[test]
+fn test_fold_name() {
+    assert_eq!(names::fold_name("Foo.TXT"), names::fold_name("foo.txt"));
+    assert_eq!(names::fold_name("ÄRGER.md"), names::fold_name("ärger.md"));
+    assert_eq!(names::fold_name("Straße"), names::fold_name("STRASSE"));
+    assert_ne!(names::fold_name("foo.txt"), names::fold_name("fo.txt"));
+}
+
+#[test]
+fn test_same_name_groups_ignore_case() {
+    let files = || {
+        ["a/Résumé.pdf", "b/RÉSUMÉ.PDF", "c/Notes.txt", "d/notes.txt"]
+            .iter()
+            .map(|path| FileInfo::new(PathBuf::from(path), 10))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(names::same_name_groups(files(), false).is_empty());
+
+    let groups = names::same_name_groups(files(), true);
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups["name:notes.txt"].len(), 2);
+    assert_eq!(groups["name:résumé.pdf"].len(), 2);
+}
+
+#[test]
+fn test_quick_scan_ignore_case_names() {
+    let temp_dir = TempDir::new().unwrap();
+    for dir in ["a", "b"] {
+        fs::create_dir(temp_dir.path().join(dir)).unwrap();
+    }
+    fs::write(temp_dir.path().join("a/Foo.txt"), b"one").unwrap();
+    fs::write(temp_dir.path().join("b/foo.txt"), b"two").unwrap();
+
+    let options = ScanOptions {
+        group_by: GroupBy::Quick,
+        ignore_case_names: true,
+        ..Default::default()
+    };
+    let duplicates = Scanner::with_options(None, options)
+        .find_duplicates(temp_dir.path())
+        .unwrap();
+
+    assert_eq!(duplicates["quick:3:foo.txt"].len(), 2);
+}
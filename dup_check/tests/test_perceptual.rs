@@ -0,0 +1,108 @@
+use dup_check::{
+    file_info::{FileInfo, MatchKind},
+    perceptual,
+    scanner::{ScanOptions, Scanner},
+};
+use image::{ImageFormat, RgbImage};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A horizontal gradient, bright on the left when `reversed`
+fn gradient(width: u32, height: u32, reversed: bool) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let mut level = (x * 255 / (width - 1)) as u8;
+        if reversed {
+            level = 255 - level;
+        }
+        // Some vertical structure, so rows differ
+        let shade = level.saturating_sub((y * 40 / height) as u8);
+        image::Rgb([shade, shade, shade])
+    })
+}
+
+fn save(image: &RgbImage, dir: &Path, name: &str, format: ImageFormat) -> PathBuf {
+    let path = dir.join(name);
+    image.save_with_format(&path, format).unwrap();
+    path
+}
+
+fn file_info(path: &Path) -> FileInfo {
+    FileInfo::new(path.to_path_buf(), std::fs::metadata(path).unwrap().len())
+}
+
+#[test]
+fn test_resized_and_reencoded_copies_are_similar() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = save(
+        &gradient(256, 192, false),
+        temp_dir.path(),
+        "photo.png",
+        ImageFormat::Png,
+    );
+    let resized = save(
+        &gradient(64, 48, false),
+        temp_dir.path(),
+        "small.jpg",
+        ImageFormat::Jpeg,
+    );
+    let other = save(
+        &gradient(256, 192, true),
+        temp_dir.path(),
+        "other.png",
+        ImageFormat::Png,
+    );
+
+    let hash = perceptual::dhash(&original).unwrap();
+    assert!(perceptual::distance(hash, perceptual::dhash(&resized).unwrap()) <= 4);
+    assert!(perceptual::distance(hash, perceptual::dhash(&other).unwrap()) > 32);
+
+    let files = [&original, &resized, &other].map(|path| file_info(path));
+    let groups = perceptual::similar_groups(files.to_vec(), perceptual::DEFAULT_THRESHOLD);
+    assert_eq!(groups.len(), 1);
+    let (key, members) = groups.iter().next().unwrap();
+    assert!(key.starts_with("similar:"));
+    let paths: Vec<&PathBuf> = members.iter().map(|file| &file.path).collect();
+    assert_eq!(paths, [&original, &resized], "The largest copy comes first");
+    assert!(members
+        .iter()
+        .all(|file| file.match_kind == MatchKind::SimilarImage));
+}
+
+#[test]
+fn test_scanner_images_mode_skips_other_files() {
+    let temp_dir = TempDir::new().unwrap();
+    save(
+        &gradient(128, 96, false),
+        temp_dir.path(),
+        "a.png",
+        ImageFormat::Png,
+    );
+    save(
+        &gradient(128, 96, false),
+        temp_dir.path(),
+        "b.bmp",
+        ImageFormat::Bmp,
+    );
+    // Identical, but not images
+    std::fs::write(temp_dir.path().join("notes.txt"), b"same").unwrap();
+    std::fs::write(temp_dir.path().join("copy.txt"), b"same").unwrap();
+    // An image extension on something that doesn't decode
+    std::fs::write(temp_dir.path().join("broken.jpg"), b"not a jpeg").unwrap();
+
+    let scanner = Scanner::with_options(
+        None,
+        ScanOptions {
+            similar_images: Some(perceptual::DEFAULT_THRESHOLD),
+            ..Default::default()
+        },
+    );
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let members = duplicates.values().next().unwrap();
+    assert_eq!(members.len(), 2);
+    assert!(
+        !members[0].match_kind.can_delete(false),
+        "Only deleted with --force"
+    );
+}
@@ -0,0 +1,154 @@
+use dup_check::{
+    file_info::FileInfo,
+    report::{self, FolderBucket, SavingsAction, SortKey},
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn group(paths: &[&str], size: u64) -> Vec<FileInfo> {
+    paths
+        .iter()
+        .map(|path| FileInfo::new(PathBuf::from(path), size))
+        .collect()
+}
+
+#[test]
+fn test_deepest_duplicate_ranked_first() {
+    let mut duplicates = HashMap::new();
+    duplicates.insert(
+        "hash1".to_string(),
+        group(&["root/a.txt", "root/x/y/z/a.txt"], 10),
+    );
+    duplicates.insert(
+        "hash2".to_string(),
+        group(&["root/b.txt", "root/x/b.txt"], 5),
+    );
+
+    let nested = report::deepest_duplicates(&duplicates);
+
+    assert_eq!(nested.len(), 4, "Every duplicate file should be listed");
+    assert_eq!(nested[0].file.path, PathBuf::from("root/x/y/z/a.txt"));
+    assert_eq!(nested[0].depth, 5);
+    assert_eq!(
+        nested[0].levels_below_shallowest, 3,
+        "Deep copy sits three levels below its shallowest sibling"
+    );
+    assert_eq!(nested[1].file.path, PathBuf::from("root/x/b.txt"));
+    assert_eq!(
+        nested.last().unwrap().levels_below_shallowest,
+        0,
+        "Shallow copies come last"
+    );
+}
+
+#[test]
+fn test_savings_by_action_accounting() {
+    let temp_dir = TempDir::new().unwrap();
+    let content = vec![b'x'; 4096];
+    let paths: Vec<PathBuf> = ["a.bin", "b.bin", "c.bin"]
+        .iter()
+        .map(|name| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, &content).unwrap();
+            path
+        })
+        .collect();
+
+    let mut duplicates = HashMap::new();
+    duplicates.insert(
+        "hash".to_string(),
+        paths
+            .iter()
+            .map(|path| FileInfo::new(path.clone(), content.len() as u64))
+            .collect::<Vec<_>>(),
+    );
+
+    let savings = report::savings_by_action(&duplicates);
+    let bytes = |action| {
+        savings
+            .iter()
+            .find(|entry| entry.action == action)
+            .unwrap()
+            .reclaimable_bytes
+    };
+
+    assert_eq!(bytes(SavingsAction::Delete), 2 * 4096);
+    assert_eq!(
+        bytes(SavingsAction::HardLink),
+        bytes(SavingsAction::Delete),
+        "Hard links on one filesystem reclaim as much as deleting"
+    );
+
+    let link_size = paths[0].as_os_str().len() as u64;
+    assert_eq!(
+        bytes(SavingsAction::Symlink),
+        bytes(SavingsAction::Delete) - 2 * link_size,
+        "Each symlink still stores its target path"
+    );
+}
+
+#[test]
+fn test_waste_attributed_to_top_level_folders() {
+    let mut duplicates = HashMap::new();
+    duplicates.insert(
+        "downloads".to_string(),
+        group(
+            &[
+                "home/Downloads/a.iso",
+                "home/Downloads/old/a.iso",
+                "home/Downloads/b/a.iso",
+            ],
+            1000,
+        ),
+    );
+    duplicates.insert(
+        "documents".to_string(),
+        group(&["home/Documents/cv.pdf", "home/Documents/cv2.pdf"], 40),
+    );
+    duplicates.insert(
+        "spread".to_string(),
+        group(&["home/Documents/pic.jpg", "home/Downloads/pic.jpg"], 300),
+    );
+    duplicates.insert("loose".to_string(), group(&["home/x.txt", "home/y.txt"], 5));
+
+    let waste = report::waste_by_top_level_folder(&duplicates, std::path::Path::new("home"));
+    let summary: Vec<(FolderBucket, u64, usize)> = waste
+        .into_iter()
+        .map(|entry| (entry.bucket, entry.wasted_bytes, entry.groups))
+        .collect();
+
+    assert_eq!(
+        summary,
+        vec![
+            (FolderBucket::Folder(PathBuf::from("Downloads")), 2000, 1),
+            (FolderBucket::CrossFolder, 300, 1),
+            (FolderBucket::Folder(PathBuf::from("Documents")), 40, 1),
+            (FolderBucket::Root, 5, 1),
+        ]
+    );
+}
+
+#[test]
+fn test_sorted_groups() {
+    let duplicates = HashMap::from([
+        ("big".to_string(), group(&["/c/big1", "/c/big2"], 250)),
+        (
+            "many".to_string(),
+            group(&["/b/m1", "/b/m2", "/b/m3", "/b/m4"], 100),
+        ),
+        ("small".to_string(), group(&["/a/s1", "/a/s2", "/a/s3"], 10)),
+    ]);
+    let order = |key| -> Vec<&str> {
+        report::sorted_groups(&duplicates, key)
+            .into_iter()
+            .map(|(hash, _)| hash.as_str())
+            .collect()
+    };
+
+    assert_eq!(order(SortKey::Wasted), ["many", "big", "small"]);
+    assert_eq!(order(SortKey::Size), ["big", "many", "small"]);
+    assert_eq!(order(SortKey::Count), ["many", "small", "big"]);
+    assert_eq!(order(SortKey::Path), ["small", "many", "big"]);
+}
@@ -0,0 +1,82 @@
+use dup_check::{
+    file_info::{FileInfo, MatchKind},
+    report_db, ui,
+};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn group(paths: &[&str], size: u64, match_kind: MatchKind) -> Vec<FileInfo> {
+    paths
+        .iter()
+        .map(|path| {
+            let mut file = FileInfo::new(PathBuf::from(path), size);
+            file.match_kind = match_kind;
+            file
+        })
+        .collect()
+}
+
+#[test]
+fn test_export_db_contains_groups_and_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("report.db");
+
+    let mut duplicates = HashMap::new();
+    duplicates.insert(
+        "aaa".to_string(),
+        group(&["a/1.bin", "a/2.bin", "a/3.bin"], 100, MatchKind::Exact),
+    );
+    duplicates.insert(
+        "text-ci:bbb".to_string(),
+        group(&["b/x.txt", "b/X.txt"], 10, MatchKind::CaseInsensitiveText),
+    );
+
+    let written = report_db::export(&duplicates, &db_path, &ui::DeleteOptions::default()).unwrap();
+    assert_eq!(written, 5);
+
+    // Exporting again replaces the previous report
+    report_db::export(&duplicates, &db_path, &ui::DeleteOptions::default()).unwrap();
+
+    let conn = Connection::open(&db_path).unwrap();
+    let groups: i64 = conn
+        .query_row("SELECT COUNT(*) FROM groups", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(groups, 2);
+
+    let (kind, reclaimable): (String, u64) = conn
+        .query_row(
+            "SELECT match_kind, reclaimable_bytes FROM groups WHERE hash = 'aaa'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(kind, "exact");
+    assert_eq!(reclaimable, 200);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path, f.is_original, f.action FROM files f
+             JOIN groups g ON g.id = f.group_id
+             ORDER BY g.hash, f.id",
+        )
+        .unwrap();
+    let rows: Vec<(String, bool, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        rows,
+        vec![
+            ("a/1.bin".to_string(), true, "keep".to_string()),
+            ("a/2.bin".to_string(), false, "delete".to_string()),
+            ("a/3.bin".to_string(), false, "delete".to_string()),
+            ("b/x.txt".to_string(), true, "keep".to_string()),
+            ("b/X.txt".to_string(), false, "keep".to_string()),
+        ],
+        "Non-exact groups are never marked for deletion"
+    );
+}
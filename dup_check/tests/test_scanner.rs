@@ -1,7 +1,22 @@
-use dup_check::{scanner::Scanner, utils};
+use dup_check::{
+    cache::Cache,
+    cancel::CancellationToken,
+    error::DupCheckError,
+    file_info::{FileInfo, MatchKind},
+    scanner::{
+        self, ByteRange, EmptyFiles, GroupBy, ScanOptions, ScanRoot, Scanner, SkipReason,
+        SymlinkPolicy,
+    },
+    ui,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
 use tempfile::TempDir;
 
 /// Helper function to create a temporary file with specific content
@@ -75,6 +90,40 @@ fn test_scanner_with_size_filter() {
     assert_eq!(duplicates.len(), 1, "Should only find empty duplicates");
 }
 
+#[test]
+fn test_size_filter_bounds_are_inclusive() {
+    let temp_dir = TempDir::new().unwrap();
+    for size in [1023, 1024, 1025, 2047, 2048, 2049] {
+        for copy in ["a", "b"] {
+            let name = format!("{}_{}.bin", size, copy);
+            create_temp_file(&temp_dir, &name, &vec![b'x'; size]);
+        }
+    }
+    let sizes = |min_size, max_size| {
+        let scanner = Scanner::with_options(
+            None,
+            ScanOptions {
+                min_size,
+                max_size,
+                ..Default::default()
+            },
+        );
+        let mut sizes: Vec<u64> = scanner
+            .find_duplicates(temp_dir.path())
+            .unwrap()
+            .values()
+            .map(|files| files[0].size)
+            .collect();
+        sizes.sort();
+        sizes
+    };
+
+    assert_eq!(sizes(Some(1024), Some(2048)), vec![1024, 1025, 2047, 2048]);
+    assert_eq!(sizes(Some(1025), Some(2047)), vec![1025, 2047]);
+    assert_eq!(sizes(Some(2048), Some(2048)), vec![2048]);
+    assert_eq!(sizes(Some(2049), Some(2048)), Vec::<u64>::new());
+}
+
 #[test]
 fn test_scanner_empty_directory() {
     let temp_dir = TempDir::new().unwrap();
@@ -101,6 +150,9 @@ fn test_scanner_single_files() {
     assert!(duplicates.is_empty(), "No duplicates should be found");
 }
 
+// Files are hidden through their attribute here; dot-files are covered by
+// test_stats_count_skipped_files_per_reason
+#[cfg(all(windows, feature = "windows-hidden"))]
 #[test]
 fn test_scanner_with_hidden_files() {
     let temp_dir = TempDir::new().unwrap();
@@ -115,7 +167,7 @@ fn test_scanner_with_hidden_files() {
     let hidden2 = create_temp_file(&temp_dir, "hidden2.txt", content);
 
     // Set hidden attribute on Windows
-    #[cfg(windows)]
+    #[cfg(all(windows, feature = "windows-hidden"))]
     {
         use windows::Win32::Storage::FileSystem::{SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN};
         for path in &[&hidden1, &hidden2] {
@@ -143,13 +195,58 @@ fn test_scanner_with_hidden_files() {
         assert_eq!(files.len(), 2, "Should only find visible duplicate files");
         for file in files {
             assert!(
-                !utils::is_hidden(&file.path),
+                !dup_check::utils::is_hidden(&file.path),
                 "Hidden files should be excluded"
             );
         }
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn test_scanner_include_hidden() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "visible.txt", b"dotfile content");
+    create_temp_file(&temp_dir, ".hidden", b"dotfile content");
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert!(duplicates.is_empty(), "Hidden files are skipped by default");
+
+    let options = ScanOptions {
+        include_hidden: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert!(files.iter().any(|file| file.path.ends_with(".hidden")));
+}
+
+#[test]
+fn test_stats_count_skipped_files_per_reason() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"kept content");
+    create_temp_file(&temp_dir, "b.txt", b"kept content");
+    create_temp_file(&temp_dir, ".hidden.txt", b"kept content");
+    create_temp_file(&temp_dir, "tiny.txt", b"x");
+    create_temp_file(&temp_dir, "tiny2.txt", b"y");
+
+    let options = ScanOptions {
+        min_size: Some(2),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(
+        stats.skipped,
+        vec![(SkipReason::Hidden, 1), (SkipReason::TooSmall, 2)]
+    );
+}
+
 #[test]
 fn test_scanner_large_files() {
     let temp_dir = TempDir::new().unwrap();
@@ -172,3 +269,1620 @@ fn test_scanner_large_files() {
         assert_eq!(files[0].size, 1024 * 1024, "Files should be 1MB in size");
     }
 }
+
+#[test]
+fn test_scanner_text_case_insensitive() {
+    let temp_dir = TempDir::new().unwrap();
+    let upper = create_temp_file(&temp_dir, "upper.ini", b"[Section]\nKey = Value\n");
+    let lower = create_temp_file(&temp_dir, "lower.ini", b"[section]\nkey = value\n");
+    create_temp_file(&temp_dir, "other.ini", b"[section]\nkey = other\n");
+
+    // Off by default: the files aren't byte-identical
+    let scanner = Scanner::new(false, None, None).unwrap();
+    assert!(scanner.find_duplicates(temp_dir.path()).unwrap().is_empty());
+
+    let options = ScanOptions {
+        text_case_insensitive: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1, "Case variants should be grouped");
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files
+        .iter()
+        .all(|file| file.match_kind == MatchKind::CaseInsensitiveText));
+
+    // Never auto-deleted
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+    assert!(upper.exists() && lower.exists());
+}
+
+#[test]
+fn test_scanner_huge_same_size_group() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // 600 one-byte files spread over three distinct contents
+    for i in 0..600 {
+        let content = [b'a' + (i % 3) as u8];
+        create_temp_file(&temp_dir, &format!("tiny{:03}.dat", i), &content);
+    }
+
+    let options = ScanOptions {
+        large_group_threshold: Some(64),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 3, "Should find one group per content");
+    for files in duplicates.values() {
+        assert_eq!(
+            files.len(),
+            200,
+            "Each batch-hashed group should be complete"
+        );
+        assert!(files.iter().all(|file| file.size == 1));
+    }
+
+    // Same answer as the in-memory path
+    let in_memory = Scanner::new(false, None, None)
+        .unwrap()
+        .find_duplicates(temp_dir.path())
+        .unwrap();
+    let mut spilled: Vec<_> = duplicates.keys().collect();
+    let mut regular: Vec<_> = in_memory.keys().collect();
+    spilled.sort();
+    regular.sort();
+    assert_eq!(spilled, regular);
+
+    // Batch-hashed files keep the metadata they were collected with
+    for (hash, files) in &duplicates {
+        let metadata = |files: &[FileInfo]| {
+            files
+                .iter()
+                .map(|file| {
+                    (
+                        file.path.clone(),
+                        file.modified,
+                        file.allocated,
+                        file.file_id,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(files.iter().all(|file| file.modified.is_some()));
+        assert_eq!(metadata(files), metadata(&in_memory[hash]));
+    }
+}
+
+#[test]
+fn test_scanner_huge_same_size_group_spills_into_cache() {
+    let temp_dir = TempDir::new().unwrap();
+    for i in 0..200 {
+        let content = [b'a' + (i % 2) as u8];
+        create_temp_file(&temp_dir, &format!("tiny{:03}.dat", i), &content);
+    }
+    let state_dir = TempDir::new().unwrap();
+    let db_path = state_dir.path().join("cache.db");
+    let cache = Arc::new(Cache::open(&db_path, &[]).unwrap());
+
+    let options = ScanOptions {
+        large_group_threshold: Some(32),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(Some(cache), options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 2);
+    assert!(duplicates.values().all(|files| files.len() == 100));
+
+    // The spill table is gone once the group is grouped
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let spilled: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'spill%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(spilled, 0);
+}
+
+#[test]
+fn test_scanner_reports_groups_via_callback() {
+    use std::sync::Mutex;
+
+    let temp_dir = create_test_directory();
+    create_temp_file(&temp_dir, "other1.bin", b"another duplicate");
+    create_temp_file(&temp_dir, "other2.bin", b"another duplicate");
+
+    let seen = Mutex::new(Vec::new());
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner
+        .find_duplicates_with(temp_dir.path(), |hash, files| {
+            assert!(files.len() > 1, "Only confirmed groups are reported");
+            seen.lock().unwrap().push(hash.to_string());
+        })
+        .unwrap();
+
+    let mut seen = seen.into_inner().unwrap();
+    let mut expected: Vec<String> = duplicates.keys().cloned().collect();
+    seen.sort();
+    expected.sort();
+    assert_eq!(seen.len(), 3, "Each group should be reported once");
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn test_scanner_compare_range() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = create_temp_file(&temp_dir, "a.bin", b"HEADER01 body of the first file");
+    let b = create_temp_file(&temp_dir, "b.bin", b"HEADER01 a different, longer body");
+    create_temp_file(&temp_dir, "c.bin", b"HEADER02 body of the first file");
+
+    let options = ScanOptions {
+        compare_range: Some(ByteRange { offset: 0, len: 8 }),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1, "Only files sharing the header match");
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files
+        .iter()
+        .all(|file| file.match_kind == MatchKind::ByteRange));
+
+    // Partial matches are only deleted with force
+    ui::delete_duplicates(&duplicates, &ui::DeleteOptions::default()).unwrap();
+    assert!(a.exists() && b.exists());
+
+    ui::delete_duplicates(
+        &duplicates,
+        &ui::DeleteOptions {
+            force: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(a.exists() as u8 + b.exists() as u8, 1, "One copy remains");
+}
+
+#[test]
+fn test_scanner_same_file_under_two_spellings() {
+    let temp_dir = TempDir::new().unwrap();
+    let a = create_temp_file(&temp_dir, "a.txt", b"shared content");
+    let sub = temp_dir.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    let a_other_spelling = sub.join("..").join("a.txt");
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+
+    // The same file twice is not a duplicate of itself
+    let duplicates = scanner
+        .find_duplicates_in(&[&a, &a_other_spelling], |_, _| {})
+        .unwrap();
+    assert!(duplicates.is_empty(), "A file must not pair with itself");
+
+    // Overlapping roots still find a real copy exactly once
+    let b = create_temp_file(&temp_dir, "b.txt", b"shared content");
+    let duplicates = scanner
+        .find_duplicates_in(&[temp_dir.path(), a_other_spelling.as_path()], |_, _| {})
+        .unwrap();
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2, "Each physical file appears once");
+    assert!(files.iter().any(|file| file.path == b));
+}
+
+#[test]
+fn test_scanner_per_root_size_filters() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("Downloads")).unwrap();
+    fs::create_dir(temp_dir.path().join("Documents")).unwrap();
+    let content = b"small file";
+    let download = create_temp_file(&temp_dir, "Downloads/small.txt", content);
+    create_temp_file(&temp_dir, "Documents/small.txt", content);
+    create_temp_file(&temp_dir, "Documents/small copy.txt", content);
+
+    let roots = [
+        ScanRoot {
+            min_size: Some(1024),
+            ..ScanRoot::new(temp_dir.path().join("Downloads"))
+        },
+        ScanRoot {
+            min_size: Some(0),
+            ..ScanRoot::new(temp_dir.path().join("Documents"))
+        },
+    ];
+    let scanner = Scanner::new(false, Some(5), None).unwrap();
+    let duplicates = scanner.find_duplicates_in_roots(&roots, |_, _| {}).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2, "Only the Documents copies qualify");
+    assert!(files.iter().all(|file| file.path != download));
+}
+
+#[test]
+fn test_scanner_skip_locked_files() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same content");
+    let locked = create_temp_file(&temp_dir, "b.txt", b"same content");
+
+    let holder = File::open(&locked).unwrap();
+    holder.lock().unwrap();
+
+    let options = ScanOptions {
+        skip_locked: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert!(duplicates.is_empty(), "The locked copy is not hashed");
+    assert_eq!(scanner.locked_files(), vec![locked.clone()]);
+
+    // Deletion leaves a locked duplicate in place
+    holder.unlock().unwrap();
+    let mut duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let files = duplicates.values_mut().next().unwrap();
+    if files[0].path == locked {
+        files.swap(0, 1);
+    }
+    holder.lock().unwrap();
+
+    let delete_options = ui::DeleteOptions {
+        skip_locked: true,
+        ..Default::default()
+    };
+    ui::delete_duplicates(&duplicates, &delete_options).unwrap();
+    assert!(locked.exists(), "A locked file must not be deleted");
+}
+
+#[test]
+fn test_scanner_find_unique() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "dup1.txt", b"duplicate");
+    create_temp_file(&temp_dir, "dup2.txt", b"duplicate");
+    let unique = [
+        create_temp_file(&temp_dir, "one.txt", b"one of a kind"),
+        // Same size as the duplicates, different content
+        create_temp_file(&temp_dir, "two.txt", b"DUPLICATE"),
+        create_temp_file(&temp_dir, "three.txt", b"three"),
+    ];
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let found: Vec<PathBuf> = scanner
+        .find_unique(temp_dir.path())
+        .unwrap()
+        .into_iter()
+        .map(|file| file.path)
+        .collect();
+
+    let mut expected = unique.to_vec();
+    expected.sort();
+    assert_eq!(
+        found, expected,
+        "Exactly the three unique files are returned"
+    );
+}
+
+#[test]
+fn test_scanner_quick_hash_skips_early_differences() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+
+    let content = vec![b'a'; 10 * 1024];
+    let mut early_difference = content.clone();
+    early_difference[0] = b'b';
+    let mut late_difference = content.clone();
+    *late_difference.last_mut().unwrap() = b'b';
+
+    let a = create_temp_file(&temp_dir, "scan/a.bin", &content);
+    let b = create_temp_file(&temp_dir, "scan/b.bin", &content);
+    create_temp_file(&temp_dir, "scan/early.bin", &early_difference);
+    let late = create_temp_file(&temp_dir, "scan/late.bin", &late_difference);
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates.values().next().unwrap().len(), 2);
+
+    // Only files whose first block collided were hashed in full
+    let mut hashed: Vec<PathBuf> = cache
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| PathBuf::from(entry.path))
+        .collect();
+    hashed.sort();
+    assert_eq!(hashed, vec![a, b, late]);
+}
+
+#[test]
+fn test_scanner_max_result_groups_keeps_biggest() {
+    let temp_dir = TempDir::new().unwrap();
+    // Three groups wasting 100, 300 and 200 bytes
+    for (name, size, copies) in [("small", 100, 2), ("big", 100, 4), ("medium", 200, 2)] {
+        let content = vec![name.as_bytes()[0]; size];
+        for copy in 0..copies {
+            create_temp_file(&temp_dir, &format!("{}_{}.bin", name, copy), &content);
+        }
+    }
+
+    let options = ScanOptions {
+        max_result_groups: Some(2),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 2);
+    assert_eq!(scanner.dropped_groups(), 1, "The cap should be reported");
+    let mut kept: Vec<&str> = duplicates
+        .values()
+        .map(|files| {
+            let name = files[0].path.file_name().unwrap().to_str().unwrap();
+            name.split('_').next().unwrap()
+        })
+        .collect();
+    kept.sort();
+    assert_eq!(
+        kept,
+        vec!["big", "medium"],
+        "Only the biggest groups survive"
+    );
+}
+
+fn exclude(patterns: &[&str]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).unwrap());
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn test_scanner_exclude_by_name() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"shared");
+    create_temp_file(&temp_dir, "node_modules/pkg/a.txt", b"shared");
+    create_temp_file(&temp_dir, "b.tmp", b"scratch");
+    create_temp_file(&temp_dir, "c.tmp", b"scratch");
+
+    let options = ScanOptions {
+        exclude: exclude(&["node_modules", "*.tmp"]),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert!(
+        duplicates.is_empty(),
+        "Excluded directories and files should not be compared"
+    );
+}
+
+#[test]
+fn test_scanner_exclude_by_full_path() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("build")).unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    create_temp_file(&temp_dir, "build/main.o", b"object");
+    create_temp_file(&temp_dir, "src/main.o", b"object");
+    create_temp_file(&temp_dir, "src/copy.o", b"object");
+
+    let options = ScanOptions {
+        exclude: exclude(&["**/build/*.o"]),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2, "Only the copies outside build/ remain");
+    assert!(files
+        .iter()
+        .all(|file| !file.path.starts_with(temp_dir.path().join("build"))));
+}
+
+#[test]
+fn test_scanner_verify_splits_hash_collisions() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+    let files = [
+        create_temp_file(&temp_dir, "scan/a1.txt", b"content a"),
+        create_temp_file(&temp_dir, "scan/a2.txt", b"content a"),
+        create_temp_file(&temp_dir, "scan/b1.txt", b"content b"),
+        create_temp_file(&temp_dir, "scan/b2.txt", b"content b"),
+        create_temp_file(&temp_dir, "scan/c.txt", b"content c"),
+    ];
+
+    // Simulate a collision: the cache claims all files share one hash
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    for path in &files {
+        let metadata = fs::metadata(path).unwrap();
+        cache
+            .store_hash(path, metadata.len(), metadata.modified().ok(), "collision")
+            .unwrap();
+    }
+
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+    assert_eq!(
+        duplicates["collision"].len(),
+        5,
+        "Unverified, hashes decide"
+    );
+
+    let options = ScanOptions {
+        verify: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(Some(cache), options);
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+
+    assert_eq!(duplicates.len(), 2, "Byte-different files are split apart");
+    for files in duplicates.values() {
+        assert_eq!(files.len(), 2);
+        let content = fs::read(&files[0].path).unwrap();
+        assert_eq!(fs::read(&files[1].path).unwrap(), content);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scanner_hard_links_are_not_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = create_temp_file(&temp_dir, "original.txt", b"linked content");
+    fs::hard_link(&original, temp_dir.path().join("link.txt")).unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert!(duplicates.is_empty(), "Hard links share their storage");
+
+    // A real copy is still reported, once, against a single link
+    create_temp_file(&temp_dir, "copy.txt", b"linked content");
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2);
+}
+
+#[test]
+fn test_scanner_find_duplicates_with_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+    create_temp_file(&temp_dir, "scan/a1.txt", b"0123456789");
+    create_temp_file(&temp_dir, "scan/a2.txt", b"0123456789");
+    create_temp_file(&temp_dir, "scan/a3.txt", b"0123456789");
+    create_temp_file(&temp_dir, "scan/unique.txt", b"unique");
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(&scan_dir).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(stats.files_scanned, 4);
+    assert_eq!(stats.bytes_scanned, 36);
+    assert_eq!(stats.duplicate_groups, 1);
+    assert_eq!(stats.duplicate_files, 3);
+    assert_eq!(stats.reclaimable_bytes, 20);
+    assert_eq!((stats.cache_hits, stats.cache_misses), (0, 3));
+    assert_eq!(stats.bytes_hashed, 30, "Only the same-size files are read");
+
+    // Hashes were cached by the first scan; the unique file is never hashed
+    let scanner = Scanner::with_options(Some(cache), ScanOptions::default());
+    let (_, stats) = scanner.find_duplicates_with_stats(&scan_dir).unwrap();
+    assert_eq!((stats.cache_hits, stats.cache_misses), (3, 0));
+    assert_eq!(stats.bytes_hashed, 0);
+}
+
+/// Sparse copies only free the blocks they actually occupy
+#[cfg(unix)]
+#[test]
+fn test_reclaimable_bytes_of_sparse_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let size = 8 * 1024 * 1024;
+    for name in ["sparse1.img", "sparse2.img"] {
+        let mut file = File::create(temp_dir.path().join(name)).unwrap();
+        file.set_len(size).unwrap();
+        file.write_all(b"header").unwrap();
+    }
+
+    let scanner = Scanner::with_options(None, ScanOptions::default());
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(temp_dir.path()).unwrap();
+
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files[1].size, size);
+    assert_eq!(stats.reclaimable_bytes, files[1].allocated);
+    // Filesystems without hole support allocate every block
+    if files[1].allocated < size {
+        assert!(stats.reclaimable_bytes < size / 2);
+    }
+}
+
+#[test]
+fn test_incremental_rescan_lists_only_changed_directories() {
+    use filetime::FileTime;
+
+    let temp_dir = TempDir::new().unwrap();
+    for dir in ["a", "b/nested", "c"] {
+        fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+    }
+    create_temp_file(&temp_dir, "a/one.txt", b"shared content");
+    create_temp_file(&temp_dir, "b/nested/two.txt", b"shared content");
+    create_temp_file(&temp_dir, "c/other.txt", b"other content");
+    // Directories changed moments ago are never snapshotted
+    let past = FileTime::from_unix_time(1_600_000_000, 0);
+    for dir in ["", "a", "b", "b/nested", "c"] {
+        filetime::set_file_mtime(temp_dir.path().join(dir), past).unwrap();
+    }
+
+    let cache = Arc::new(Cache::in_memory().unwrap());
+    let options = || ScanOptions {
+        incremental: true,
+        ..Default::default()
+    };
+    let scan = || {
+        Scanner::with_options(Some(cache.clone()), options())
+            .find_duplicates_with_stats(temp_dir.path())
+            .unwrap()
+    };
+
+    let (duplicates, stats) = scan();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!((stats.dirs_listed, stats.dirs_unchanged), (5, 0));
+
+    let (duplicates, stats) = scan();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(stats.files_scanned, 3);
+    assert_eq!((stats.dirs_listed, stats.dirs_unchanged), (0, 5));
+
+    create_temp_file(&temp_dir, "c/copy.txt", b"other content");
+    let (duplicates, stats) = scan();
+    assert_eq!(duplicates.len(), 2, "The new file is found");
+    assert_eq!((stats.dirs_listed, stats.dirs_unchanged), (1, 4));
+}
+
+/// Rewriting a file leaves its directory's time alone; the snapshot must
+/// not be trusted for it
+#[test]
+fn test_incremental_rescan_notices_files_changed_in_place() {
+    use filetime::FileTime;
+
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same content");
+    let b = create_temp_file(&temp_dir, "b.txt", b"same content");
+    let past = FileTime::from_unix_time(1_600_000_000, 0);
+    filetime::set_file_mtime(temp_dir.path(), past).unwrap();
+
+    let cache = Arc::new(Cache::in_memory().unwrap());
+    let scan = || {
+        let options = ScanOptions {
+            incremental: true,
+            ..Default::default()
+        };
+        Scanner::with_options(Some(cache.clone()), options)
+            .find_duplicates_with_stats(temp_dir.path())
+            .unwrap()
+    };
+    assert_eq!(scan().0.len(), 1);
+
+    fs::write(&b, b"diff content").unwrap();
+    filetime::set_file_mtime(temp_dir.path(), past).unwrap();
+    let (duplicates, stats) = scan();
+    assert_eq!(stats.dirs_unchanged, 1);
+    assert!(duplicates.is_empty(), "The rewritten file is hashed again");
+}
+
+/// A file rewritten to a new size belongs to its new size group, even if it
+/// was the only file of its old size
+#[test]
+fn test_incremental_rescan_regroups_files_grown_in_place() {
+    use filetime::FileTime;
+
+    let temp_dir = TempDir::new().unwrap();
+    let a = create_temp_file(&temp_dir, "a.txt", b"short");
+    create_temp_file(&temp_dir, "b.txt", b"longer content");
+    let past = FileTime::from_unix_time(1_600_000_000, 0);
+    filetime::set_file_mtime(temp_dir.path(), past).unwrap();
+
+    let cache = Arc::new(Cache::in_memory().unwrap());
+    let scan = || {
+        let options = ScanOptions {
+            incremental: true,
+            ..Default::default()
+        };
+        Scanner::with_options(Some(cache.clone()), options)
+            .find_duplicates_with_stats(temp_dir.path())
+            .unwrap()
+    };
+    assert!(scan().0.is_empty());
+
+    fs::write(&a, b"longer content").unwrap();
+    filetime::set_file_mtime(temp_dir.path(), past).unwrap();
+    let (duplicates, stats) = scan();
+    assert_eq!(stats.dirs_unchanged, 1);
+    assert_eq!(duplicates.len(), 1, "The grown file matches b.txt");
+
+    // The snapshot now records the new size
+    let (duplicates, stats) = scan();
+    assert_eq!(stats.dirs_unchanged, 1);
+    assert_eq!(duplicates.len(), 1);
+}
+
+#[test]
+fn test_scanner_stats_count_only_first_blocks_of_ruled_out_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let size = 3 * scanner::QUICK_HASH_LEN as usize;
+    create_temp_file(&temp_dir, "a.bin", &vec![b'a'; size]);
+    create_temp_file(&temp_dir, "b.bin", &vec![b'b'; size]);
+
+    let scanner = Scanner::with_options(None, ScanOptions::default());
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(temp_dir.path()).unwrap();
+
+    assert!(duplicates.is_empty());
+    assert_eq!(stats.bytes_hashed, 2 * scanner::QUICK_HASH_LEN);
+    assert!(stats.elapsed > std::time::Duration::ZERO);
+}
+
+#[test]
+fn test_scanner_missing_root_is_path_not_found() {
+    let temp_dir = TempDir::new().unwrap();
+    let missing = temp_dir.path().join("missing");
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    match scanner.find_duplicates(&missing) {
+        Err(DupCheckError::PathNotFound(path)) => assert_eq!(path, missing),
+        other => panic!("Expected PathNotFound, got {:?}", other.map(|d| d.len())),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scanner_unreadable_directory_is_reported() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let locked = temp_dir.path().join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    let denied = fs::read_dir(&locked).is_err();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let result = scanner.find_duplicates(temp_dir.path());
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert!(result
+        .expect("An unreadable directory isn't fatal")
+        .is_empty());
+    let errors = scanner.scan_errors();
+    // Running as root, permissions aren't enforced
+    if denied {
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, locked);
+    } else {
+        assert!(errors.is_empty());
+    }
+}
+
+#[test]
+fn test_scanner_cancelled_before_walk() {
+    let temp_dir = create_test_directory();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let options = ScanOptions {
+        cancel,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let error = scanner.find_duplicates(temp_dir.path()).unwrap_err();
+    assert!(matches!(error, DupCheckError::Cancelled));
+}
+
+#[test]
+fn test_scanner_cancel_stops_hashing() {
+    let temp_dir = TempDir::new().unwrap();
+    let pairs = 500;
+    for i in 0..pairs {
+        // A distinct size per pair puts every pair in its own size group
+        let content = vec![b'x'; i + 1];
+        create_temp_file(&temp_dir, &format!("{}_a.bin", i), &content);
+        create_temp_file(&temp_dir, &format!("{}_b.bin", i), &content);
+    }
+
+    let state_dir = TempDir::new().unwrap();
+    let journal_path = state_dir.path().join("journal.jsonl");
+    let cancel = CancellationToken::new();
+    let options = ScanOptions {
+        cancel: cancel.clone(),
+        journal: Some(journal_path.clone()),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let reported = AtomicUsize::new(0);
+    let result = scanner.find_duplicates_with(temp_dir.path(), |_, _| {
+        reported.fetch_add(1, Ordering::SeqCst);
+        cancel.cancel();
+    });
+
+    assert!(matches!(result.unwrap_err(), DupCheckError::Cancelled));
+    assert!(
+        reported.load(Ordering::SeqCst) < pairs,
+        "Groups not yet started are skipped once cancelled"
+    );
+    assert!(journal_path.exists(), "The journal is kept for --resume");
+}
+
+#[test]
+fn test_scanner_runs_on_configured_threads() {
+    let temp_dir = create_test_directory();
+    let options = ScanOptions {
+        threads: 1,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let pool_sizes = std::sync::Mutex::new(Vec::new());
+    let duplicates = scanner
+        .find_duplicates_with(temp_dir.path(), |_, _| {
+            pool_sizes
+                .lock()
+                .unwrap()
+                .push(rayon::current_num_threads());
+        })
+        .unwrap();
+
+    assert!(!duplicates.is_empty());
+    assert!(pool_sizes.into_inner().unwrap().iter().all(|&n| n == 1));
+}
+
+#[test]
+fn test_scanner_min_copies() {
+    let temp_dir = TempDir::new().unwrap();
+    for (name, copies) in [("pair", 2), ("triple", 3), ("many", 6)] {
+        for copy in 0..copies {
+            create_temp_file(
+                &temp_dir,
+                &format!("{}_{}.txt", name, copy),
+                name.as_bytes(),
+            );
+        }
+    }
+
+    let options = ScanOptions {
+        min_copies: 5,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 6);
+}
+
+/// A scan root holding `target.txt`, a link to it, a link to a directory
+/// outside the root with a real copy, and a link loop
+#[cfg(unix)]
+fn create_symlink_tree() -> (TempDir, TempDir) {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let target = create_temp_file(&temp_dir, "target.txt", b"linked content");
+    create_temp_file(&outside, "copy.txt", b"linked content");
+    symlink(&target, temp_dir.path().join("link.txt")).unwrap();
+    symlink(outside.path(), temp_dir.path().join("outside")).unwrap();
+    symlink(temp_dir.path(), temp_dir.path().join("loop")).unwrap();
+    (temp_dir, outside)
+}
+
+#[cfg(unix)]
+fn scan_with_symlinks(path: &std::path::Path, symlinks: SymlinkPolicy) -> Vec<PathBuf> {
+    let options = ScanOptions {
+        symlinks,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(path).unwrap();
+    let mut paths: Vec<PathBuf> = duplicates
+        .into_values()
+        .flatten()
+        .map(|file| file.path.strip_prefix(path).unwrap().to_path_buf())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scanner_symlinks_skipped_by_default() {
+    let (temp_dir, _outside) = create_symlink_tree();
+    assert!(scan_with_symlinks(temp_dir.path(), SymlinkPolicy::default()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scanner_symlinks_followed() {
+    let (temp_dir, _outside) = create_symlink_tree();
+    // The link to target.txt is the same file, and the loop is skipped
+    assert_eq!(
+        scan_with_symlinks(temp_dir.path(), SymlinkPolicy::Follow),
+        vec![
+            PathBuf::from("outside/copy.txt"),
+            PathBuf::from("target.txt")
+        ]
+    );
+}
+
+/// A loop through two links is caught although neither link points to
+/// an ancestor of the directory holding it
+#[cfg(unix)]
+#[test]
+fn test_scanner_symlink_loop_through_two_links() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("a")).unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("b")).unwrap();
+    create_temp_file(&temp_dir, "a/one.txt", b"looped content");
+    create_temp_file(&temp_dir, "b/two.txt", b"looped content");
+    symlink("../b", temp_dir.path().join("a/l1")).unwrap();
+    symlink("../a", temp_dir.path().join("b/l2")).unwrap();
+
+    // b is reached both directly and through a/l1, but each file is kept once
+    let paths = scan_with_symlinks(temp_dir.path(), SymlinkPolicy::Follow);
+    let mut names: Vec<_> = paths.iter().map(|path| path.file_name().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, ["one.txt", "two.txt"], "Got {:?}", paths);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scanner_symlinks_treated_as_files() {
+    let (temp_dir, _outside) = create_symlink_tree();
+    assert_eq!(
+        scan_with_symlinks(temp_dir.path(), SymlinkPolicy::TreatAsFile),
+        vec![PathBuf::from("link.txt"), PathBuf::from("target.txt")]
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn test_scanner_junctions_skipped_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "target.txt", b"junction content");
+    create_temp_file(&outside, "copy.txt", b"junction content");
+    let junction = temp_dir.path().join("junction");
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(&junction)
+        .arg(outside.path())
+        .status()
+        .unwrap();
+    assert!(status.success(), "Failed to create junction");
+
+    let scan = |follow_junctions| {
+        let options = ScanOptions {
+            // Not even following links walks into junctions by default
+            symlinks: SymlinkPolicy::Follow,
+            follow_junctions,
+            ..Default::default()
+        };
+        Scanner::with_options(None, options)
+            .find_duplicates(temp_dir.path())
+            .unwrap()
+    };
+
+    assert!(scan(false).is_empty());
+    assert_eq!(scan(true).values().next().unwrap().len(), 2);
+}
+
+#[test]
+fn test_scanner_max_depth() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("nested/deeper")).unwrap();
+    create_temp_file(&temp_dir, "top.txt", b"top level");
+    create_temp_file(&temp_dir, "nested/top.txt", b"top level");
+    create_temp_file(&temp_dir, "nested/a.txt", b"nested");
+    create_temp_file(&temp_dir, "nested/deeper/a.txt", b"nested");
+
+    let scan = |max_depth| {
+        let options = ScanOptions {
+            max_depth,
+            ..Default::default()
+        };
+        Scanner::with_options(None, options)
+            .find_duplicates(temp_dir.path())
+            .unwrap()
+    };
+
+    assert!(scan(Some(1)).is_empty(), "Depth 1 only sees top.txt");
+    assert_eq!(scan(Some(2)).len(), 1);
+    assert_eq!(scan(None).len(), 2);
+}
+
+#[test]
+fn test_scanner_mmap_threshold_compares_instead_of_hashing() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+
+    let content = vec![b'a'; 64 * 1024];
+    let mut late_difference = content.clone();
+    *late_difference.last_mut().unwrap() = b'b';
+    create_temp_file(&temp_dir, "scan/a.bin", &content);
+    create_temp_file(&temp_dir, "scan/b.bin", &content);
+    create_temp_file(&temp_dir, "scan/late.bin", &late_difference);
+    // Below the threshold, hashed as usual
+    create_temp_file(&temp_dir, "scan/small1.txt", b"small");
+    create_temp_file(&temp_dir, "scan/small2.txt", b"small");
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(
+        Some(cache.clone()),
+        ScanOptions {
+            mmap_threshold: Some(32 * 1024),
+            verify: true,
+            ..Default::default()
+        },
+    );
+    let duplicates = scanner.find_duplicates(&scan_dir).unwrap();
+
+    assert_eq!(duplicates.len(), 2);
+    let (key, compared) = duplicates
+        .iter()
+        .find(|(key, _)| key.starts_with(scanner::COMPARED_PREFIX))
+        .expect("large files are grouped by comparison");
+    let mut names: Vec<_> = compared
+        .iter()
+        .map(|f| f.path.file_name().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        ["a.bin", "b.bin"],
+        "{key} holds only identical files"
+    );
+
+    // Compared files are never hashed in full
+    assert!(cache
+        .entries()
+        .unwrap()
+        .iter()
+        .all(|entry| entry.path.ends_with(".txt")));
+}
+
+#[test]
+fn test_scanner_mmap_threshold_keeps_groups_sharing_a_header_apart() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+
+    // Zero-padded like disk images, so both sizes start with the same block
+    let mut small = vec![0u8; 16 * 1024];
+    *small.last_mut().unwrap() = b's';
+    let mut large = vec![0u8; 24 * 1024];
+    *large.last_mut().unwrap() = b'l';
+    create_temp_file(&temp_dir, "scan/small1.img", &small);
+    create_temp_file(&temp_dir, "scan/small2.img", &small);
+    create_temp_file(&temp_dir, "scan/large1.img", &large);
+    create_temp_file(&temp_dir, "scan/large2.img", &large);
+
+    let scan = |mmap_threshold| {
+        Scanner::with_options(
+            None,
+            ScanOptions {
+                mmap_threshold,
+                ..Default::default()
+            },
+        )
+        .find_duplicates(&scan_dir)
+        .unwrap()
+    };
+
+    let hashed = scan(None);
+    let compared = scan(Some(5 * 1024));
+    assert_eq!(hashed.len(), 2);
+    assert_eq!(compared.len(), 2, "one group per size: {compared:?}");
+    assert!(compared
+        .keys()
+        .all(|key| key.starts_with(scanner::COMPARED_PREFIX)));
+}
+
+/// Three copies of one image under different extension spellings, plus a
+/// copy without an extension and a pair of duplicated logs
+fn create_extension_tree() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "photo.jpg", b"jpeg bytes");
+    create_temp_file(&temp_dir, "photo copy.JPG", b"jpeg bytes");
+    create_temp_file(&temp_dir, "photo.png", b"jpeg bytes");
+    create_temp_file(&temp_dir, "photo", b"jpeg bytes");
+    create_temp_file(&temp_dir, "a.log", b"log line");
+    create_temp_file(&temp_dir, "b.LOG", b"log line");
+    temp_dir
+}
+
+fn scanned_names(path: &std::path::Path, options: ScanOptions) -> Vec<String> {
+    let scanner = Scanner::with_options(None, options);
+    let mut names: Vec<String> = scanner
+        .find_duplicates(path)
+        .unwrap()
+        .into_values()
+        .flatten()
+        .map(|file| {
+            file.path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_scanner_include_extensions() {
+    let temp_dir = create_extension_tree();
+    let names = scanned_names(
+        temp_dir.path(),
+        ScanOptions {
+            extensions: Some(vec!["jpg".to_string(), "png".to_string()]),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        names,
+        ["photo copy.JPG", "photo.jpg", "photo.png"],
+        "Only listed extensions, in any case; files without one are skipped"
+    );
+}
+
+#[test]
+fn test_scanner_exclude_extensions() {
+    let temp_dir = create_extension_tree();
+    let names = scanned_names(
+        temp_dir.path(),
+        ScanOptions {
+            exclude_extensions: vec!["log".to_string(), "png".to_string()],
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        names,
+        ["photo", "photo copy.JPG", "photo.jpg"],
+        "Excluded extensions are skipped in any case; files without one are kept"
+    );
+}
+
+#[test]
+fn test_scanner_streaming_yields_every_group() {
+    let temp_dir = create_test_directory();
+    let expected = Scanner::new(false, None, None)
+        .unwrap()
+        .find_duplicates(temp_dir.path())
+        .unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let streamed: std::collections::HashMap<_, _> = scanner
+        .find_duplicates_streaming(vec![ScanRoot::new(temp_dir.path())])
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(streamed.len(), expected.len());
+    for (hash, files) in &expected {
+        assert_eq!(streamed[hash].len(), files.len());
+    }
+}
+
+#[test]
+fn test_scanner_streaming_ends_with_scan_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let mut stream =
+        scanner.find_duplicates_streaming(vec![ScanRoot::new(temp_dir.path().join("missing"))]);
+
+    assert!(matches!(
+        stream.next(),
+        Some(Err(DupCheckError::PathNotFound(_)))
+    ));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn test_scanner_dropping_stream_cancels_scan() {
+    let temp_dir = TempDir::new().unwrap();
+    let pairs = 500;
+    for i in 0..pairs {
+        let content = vec![b'x'; i + 1];
+        create_temp_file(&temp_dir, &format!("{}_a.bin", i), &content);
+        create_temp_file(&temp_dir, &format!("{}_b.bin", i), &content);
+    }
+
+    let cache = Arc::new(Cache::in_memory().unwrap());
+    let cancel = CancellationToken::new();
+    let options = ScanOptions {
+        cancel: cancel.clone(),
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(Some(Arc::clone(&cache)), options);
+    let mut stream = scanner.find_duplicates_streaming(vec![ScanRoot::new(temp_dir.path())]);
+    assert!(stream.next().unwrap().is_ok());
+
+    drop(stream);
+    assert!(
+        cache.entries().unwrap().len() < 2 * pairs,
+        "The rest of the scan is abandoned"
+    );
+    assert!(
+        !cancel.is_cancelled(),
+        "The caller's token is left alone, e.g. for the next scan"
+    );
+}
+
+#[test]
+fn test_scanner_duplicates_between_trees() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("archive")).unwrap();
+    fs::create_dir(temp_dir.path().join("new_photos")).unwrap();
+    // Already archived, once even twice
+    create_temp_file(&temp_dir, "archive/beach.jpg", b"beach");
+    create_temp_file(&temp_dir, "archive/beach copy.jpg", b"beach");
+    create_temp_file(&temp_dir, "new_photos/beach.jpg", b"beach");
+    create_temp_file(&temp_dir, "archive/dog.jpg", b"dog");
+    create_temp_file(&temp_dir, "new_photos/IMG_0001.jpg", b"dog");
+    // Copies within one tree only
+    create_temp_file(&temp_dir, "archive/cat.jpg", b"cat");
+    create_temp_file(&temp_dir, "archive/cat2.jpg", b"cat");
+    create_temp_file(&temp_dir, "new_photos/sunset.jpg", b"sunset");
+    create_temp_file(&temp_dir, "new_photos/sunset2.jpg", b"sunset");
+    // No copies at all
+    create_temp_file(&temp_dir, "new_photos/unique.jpg", b"unique");
+
+    let archive = temp_dir.path().join("archive");
+    let new_photos = temp_dir.path().join("new_photos");
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let groups = scanner
+        .find_duplicates_between(&archive, &new_photos)
+        .unwrap();
+
+    assert_eq!(groups.len(), 2, "Only groups spanning both trees");
+    for files in groups.values() {
+        assert_eq!(files[0].0, scanner::TreeSide::Left, "Left files come first");
+        for (side, file) in files {
+            let root = match side {
+                scanner::TreeSide::Left => &archive,
+                scanner::TreeSide::Right => &new_photos,
+            };
+            assert!(file.path.starts_with(root));
+        }
+    }
+    let beach = groups.values().find(|files| files.len() == 3).unwrap();
+    assert_eq!(
+        beach
+            .iter()
+            .filter(|(side, _)| *side == scanner::TreeSide::Left)
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn test_scanner_cache_stats_under_parallel_hashing() {
+    let temp_dir = TempDir::new().unwrap();
+    let scan_dir = temp_dir.path().join("scan");
+    fs::create_dir(&scan_dir).unwrap();
+    let pairs = 200;
+    for i in 0..pairs {
+        let content = vec![b'x'; i + 1];
+        create_temp_file(&temp_dir, &format!("scan/{}_a.bin", i), &content);
+        create_temp_file(&temp_dir, &format!("scan/{}_b.bin", i), &content);
+    }
+    let total_bytes: u64 = (1..=pairs as u64).map(|size| 2 * size).sum();
+
+    let cache = Arc::new(Cache::open(&temp_dir.path().join("cache.db"), &[]).unwrap());
+    let scanner = Scanner::with_options(Some(cache.clone()), ScanOptions::default());
+    let (_, cold) = scanner.find_duplicates_with_stats(&scan_dir).unwrap();
+    assert_eq!((cold.cache_hits, cold.cache_misses), (0, 2 * pairs as u64));
+    assert_eq!(cold.cache_miss_bytes, total_bytes);
+    assert_eq!(cold.cache_saved_io_percent(), 0.0);
+
+    let (_, warm) = scanner.find_duplicates_with_stats(&scan_dir).unwrap();
+    assert_eq!((warm.cache_hits, warm.cache_misses), (2 * pairs as u64, 0));
+    assert_eq!(warm.cache_hit_bytes, total_bytes);
+    assert_eq!(warm.cache_saved_io_percent(), 100.0);
+}
+
+#[test]
+fn test_scanner_empty_files_ignored() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "empty1.txt", b"");
+    create_temp_file(&temp_dir, "empty2.txt", b"");
+    create_temp_file(&temp_dir, "file1.txt", b"content");
+    create_temp_file(&temp_dir, "file2.txt", b"content");
+
+    let scanner = Scanner::with_options(
+        None,
+        ScanOptions {
+            empty_files: EmptyFiles::Ignore,
+            ..Default::default()
+        },
+    );
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1, "Only the non-empty pair is reported");
+    assert!(duplicates.values().all(|files| files[0].size == 7));
+    assert_eq!(
+        stats.files_scanned, 2,
+        "Empty files are skipped while walking"
+    );
+}
+
+#[test]
+fn test_scanner_empty_files_separate() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "empty1.txt", b"");
+    create_temp_file(&temp_dir, "empty2.txt", b"");
+    create_temp_file(&temp_dir, "file1.txt", b"content");
+    create_temp_file(&temp_dir, "file2.txt", b"content");
+
+    let scanner = Scanner::with_options(
+        None,
+        ScanOptions {
+            empty_files: EmptyFiles::Separate,
+            ..Default::default()
+        },
+    );
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 2);
+    let empty = &duplicates[scanner::EMPTY_KEY];
+    assert_eq!(empty.len(), 2);
+    assert!(empty.iter().all(|file| file.match_kind == MatchKind::Empty));
+    assert!(!MatchKind::Empty.can_delete(false));
+}
+
+#[test]
+fn test_scanner_builder() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "small1.txt", b"tiny");
+    create_temp_file(&temp_dir, "small2.txt", b"tiny");
+    create_temp_file(&temp_dir, "big1.txt", b"larger content");
+    create_temp_file(&temp_dir, "big2.txt", b"larger content");
+    create_temp_file(&temp_dir, "big3.tmp", b"larger content");
+    create_temp_file(&temp_dir, ".hidden.txt", b"larger content");
+
+    let scanner = Scanner::builder()
+        .cache(false)
+        .min_size(5)
+        .threads(1)
+        .exclude(Glob::new("*.tmp").unwrap())
+        .include_hidden(true)
+        .build()
+        .unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1, "The tiny files are filtered by size");
+    let mut names: Vec<String> = duplicates
+        .values()
+        .flatten()
+        .map(|file| {
+            file.path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    names.sort();
+    assert_eq!(names, [".hidden.txt", "big1.txt", "big2.txt"]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_scanner_without_progress_writes_nothing_to_stderr() {
+    // Runs the scan in a child process whose stderr is a terminal, as
+    // progress bars are never drawn elsewhere
+    if let Some(dir) = std::env::var_os("DUPCHECK_QUIET_SCAN_DIR") {
+        let show = std::env::var_os("DUPCHECK_SHOW_PROGRESS").is_some();
+        let scanner = Scanner::builder().show_progress(show).build().unwrap();
+        let duplicates = scanner.find_duplicates(std::path::Path::new(&dir)).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same content");
+    create_temp_file(&temp_dir, "b.txt", b"same content");
+
+    // `script` gives the child a pseudo-terminal and copies what is written
+    // to it, here only stderr, to its own stdout
+    let scan = |show_progress: bool| {
+        let mut command = std::process::Command::new("script");
+        command
+            .args(["--quiet", "--return", "--command"])
+            .arg(format!(
+                "'{}' test_scanner_without_progress_writes_nothing_to_stderr --exact --nocapture >/dev/null",
+                std::env::current_exe().unwrap().display()
+            ))
+            .arg("/dev/null")
+            .env("DUPCHECK_QUIET_SCAN_DIR", temp_dir.path())
+            .env_remove("DUPCHECK_SHOW_PROGRESS");
+        if show_progress {
+            command.env("DUPCHECK_SHOW_PROGRESS", "1");
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        output.stdout
+    };
+
+    // Without this the check below could pass just because nothing is drawn
+    assert!(
+        !scan(true).is_empty(),
+        "Progress never reached the terminal"
+    );
+    let stderr = scan(false);
+    assert!(
+        stderr.is_empty(),
+        "Unexpected stderr: {}",
+        String::from_utf8_lossy(&stderr)
+    );
+}
+
+#[test]
+fn test_scanner_group_by_name() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("docs")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    create_temp_file(&temp_dir, "README.md", b"top level readme");
+    create_temp_file(&temp_dir, "docs/README.md", b"different readme, longer");
+    create_temp_file(&temp_dir, "src/README.md", b"");
+    // Identical content under other names isn't a name match
+    create_temp_file(&temp_dir, "a.txt", b"same");
+    create_temp_file(&temp_dir, "b.txt", b"same");
+
+    let options = ScanOptions {
+        group_by: GroupBy::Name,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = &duplicates["name:README.md"];
+    let paths: Vec<PathBuf> = files
+        .iter()
+        .map(|file| {
+            file.path
+                .strip_prefix(temp_dir.path())
+                .unwrap()
+                .to_path_buf()
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            PathBuf::from("README.md"),
+            PathBuf::from("docs/README.md"),
+            PathBuf::from("src/README.md")
+        ]
+    );
+    assert!(files
+        .iter()
+        .all(|file| file.match_kind == MatchKind::SameName));
+    assert!(
+        !MatchKind::SameName.can_delete(true),
+        "Name matches are never deleted"
+    );
+}
+
+#[test]
+fn test_scanner_quick_groups_by_size_and_name_unverified() {
+    let temp_dir = TempDir::new().unwrap();
+    for dir in ["a", "b", "c"] {
+        fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+    }
+    // Same size and name, different content
+    create_temp_file(&temp_dir, "a/photo.jpg", b"first version");
+    create_temp_file(&temp_dir, "b/photo.jpg", b"other version");
+    // Same name but another size, and same content under another name
+    create_temp_file(&temp_dir, "c/photo.jpg", b"longer version");
+    create_temp_file(&temp_dir, "c/copy.jpg", b"first version");
+
+    let options = ScanOptions {
+        group_by: GroupBy::Quick,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let (duplicates, stats) = scanner.find_duplicates_with_stats(temp_dir.path()).unwrap();
+
+    assert_eq!(duplicates.len(), 1);
+    let files = &duplicates["quick:13:photo.jpg"];
+    assert_eq!(files.len(), 2);
+    assert!(files[0].path.ends_with("a/photo.jpg"));
+    assert!(files[1].path.ends_with("b/photo.jpg"));
+    assert!(files
+        .iter()
+        .all(|file| file.match_kind == MatchKind::Unverified));
+    assert!(!MatchKind::Unverified.can_delete(true));
+    assert_eq!(stats.bytes_hashed, 0, "Nothing is read");
+
+    let mut out = Vec::new();
+    ui::display_duplicates(&duplicates, &ui::DisplayOptions::default(), &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("unverified"));
+}
+
+#[test]
+fn test_scanner_modified_time_window() {
+    use filetime::FileTime;
+    use std::time::{Duration, SystemTime};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+    let now = SystemTime::now();
+    let temp_dir = TempDir::new().unwrap();
+    let set_age = |name: &str, modified: SystemTime| {
+        let path = create_temp_file(&temp_dir, name, b"same content");
+        filetime::set_file_mtime(&path, FileTime::from_system_time(modified)).unwrap();
+    };
+    set_age("recent1.txt", now - DAY);
+    set_age("recent2.txt", now - 2 * DAY);
+    set_age("old1.txt", now - 400 * DAY);
+    set_age("old2.txt", now - 500 * DAY);
+    set_age("future.txt", now + 30 * DAY);
+
+    let scan = |newer_than, older_than| {
+        let options = ScanOptions {
+            newer_than,
+            older_than,
+            ..Default::default()
+        };
+        let duplicates = Scanner::with_options(None, options)
+            .find_duplicates(temp_dir.path())
+            .unwrap();
+        let mut names: Vec<String> = duplicates
+            .values()
+            .flatten()
+            .map(|file| {
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(
+        scan(Some(now - 7 * DAY), None),
+        ["future.txt", "recent1.txt", "recent2.txt"]
+    );
+    assert_eq!(scan(None, Some(now - 365 * DAY)), ["old1.txt", "old2.txt"]);
+    assert!(
+        scan(Some(now - 450 * DAY), Some(now - 365 * DAY)).is_empty(),
+        "Only old1.txt is inside the window"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compare_xattrs_separates_files_with_different_attributes() {
+    let temp_dir = TempDir::new().unwrap();
+    let tagged = create_temp_file(&temp_dir, "tagged.txt", b"same content");
+    create_temp_file(&temp_dir, "plain.txt", b"same content");
+    if xattr::set(&tagged, "user.dupcheck.test", b"tag").is_err() {
+        // The file system doesn't support user attributes
+        return;
+    }
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 1, "Attributes are ignored by default");
+
+    for verify in [false, true] {
+        let options = ScanOptions {
+            compare_xattrs: true,
+            verify,
+            ..Default::default()
+        };
+        let scanner = Scanner::with_options(None, options);
+        let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+        assert!(duplicates.is_empty(), "verify: {}", verify);
+    }
+
+    let copy = create_temp_file(&temp_dir, "copy.txt", b"same content");
+    xattr::set(&copy, "user.dupcheck.test", b"tag").unwrap();
+    let options = ScanOptions {
+        compare_xattrs: true,
+        ..Default::default()
+    };
+    let scanner = Scanner::with_options(None, options);
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    assert_eq!(duplicates.len(), 1);
+    let files = duplicates.values().next().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|file| !file.path.ends_with("plain.txt")));
+}
+
+#[test]
+fn test_parallel_walk_matches_sequential_listing() {
+    let temp_dir = TempDir::new().unwrap();
+    // Four levels of three directories, with copies of a few contents spread
+    // across all of them
+    let mut dirs = vec![PathBuf::new()];
+    for _ in 0..4 {
+        dirs = dirs
+            .iter()
+            .flat_map(|dir| (0..3).map(move |i| dir.join(format!("d{}", i))))
+            .collect();
+        for dir in &dirs {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+        }
+    }
+    for (i, dir) in dirs.iter().enumerate() {
+        let content = format!("content {}", i % 7);
+        fs::write(temp_dir.path().join(dir).join("file.txt"), &content).unwrap();
+        fs::write(
+            temp_dir.path().join(dir).join(format!("unique{}", i)),
+            dir.to_str().unwrap(),
+        )
+        .unwrap();
+    }
+
+    // Expected groups from a plain sequential walk
+    let mut by_content: std::collections::BTreeMap<Vec<u8>, Vec<PathBuf>> = Default::default();
+    for entry in walkdir::WalkDir::new(temp_dir.path()) {
+        let entry = entry.unwrap();
+        if entry.file_type().is_file() {
+            let content = fs::read(entry.path()).unwrap();
+            by_content
+                .entry(content)
+                .or_default()
+                .push(entry.into_path());
+        }
+    }
+    let mut expected: Vec<Vec<PathBuf>> = by_content
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            paths.sort();
+            paths
+        })
+        .collect();
+    expected.sort();
+
+    for threads in [1, 0] {
+        let options = ScanOptions {
+            threads,
+            ..Default::default()
+        };
+        let scanner = Scanner::with_options(None, options);
+        let mut groups: Vec<Vec<PathBuf>> = scanner
+            .find_duplicates(temp_dir.path())
+            .unwrap()
+            .into_values()
+            .map(|files| files.into_iter().map(|file| file.path).collect())
+            .collect();
+        groups.sort();
+        assert_eq!(groups, expected, "threads: {}", threads);
+    }
+    assert_eq!(expected.len(), 7);
+}
@@ -0,0 +1,72 @@
+use dup_check::{
+    ui,
+    undo::{self, UndoLog},
+    Scanner,
+};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn create_temp_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+    let file_path = dir.path().join(name);
+    let mut file = File::create(&file_path).unwrap();
+    file.write_all(content).unwrap();
+    file_path
+}
+
+#[test]
+fn test_logs_never_clobber_each_other() {
+    let undo_dir = TempDir::new().unwrap();
+    let first = UndoLog::new(false, &[PathBuf::from("/data/a.txt")]);
+    let second = UndoLog::new(false, &[PathBuf::from("/data/b.txt")]);
+
+    let first_path = first.write(undo_dir.path()).unwrap();
+    let second_path = second.write(undo_dir.path()).unwrap();
+
+    assert_ne!(first_path, second_path);
+    assert_eq!(UndoLog::load(&first_path).unwrap(), first);
+    assert_eq!(
+        undo::latest(undo_dir.path()).unwrap(),
+        Some(second_path),
+        "The log written last is the most recent"
+    );
+}
+
+#[test]
+fn test_nothing_to_undo() {
+    let undo_dir = TempDir::new().unwrap();
+    assert_eq!(
+        undo::latest(&undo_dir.path().join("missing")).unwrap(),
+        None
+    );
+    assert!(undo::undo_latest(undo_dir.path()).is_err());
+}
+
+#[test]
+fn test_permanent_deletion_cannot_be_undone() {
+    let temp_dir = TempDir::new().unwrap();
+    create_temp_file(&temp_dir, "a.txt", b"same");
+    create_temp_file(&temp_dir, "b.txt", b"same");
+    let undo_dir = TempDir::new().unwrap();
+
+    let scanner = Scanner::new(false, None, None).unwrap();
+    let duplicates = scanner.find_duplicates(temp_dir.path()).unwrap();
+    let options = ui::DeleteOptions {
+        undo_dir: Some(undo_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    ui::delete_duplicates(&duplicates, &options).unwrap();
+
+    let log_path = undo::latest(undo_dir.path()).unwrap().unwrap();
+    let log = UndoLog::load(&log_path).unwrap();
+    assert!(!log.trash);
+    assert_eq!(log.files.len(), 1);
+
+    let error = undo::undo_latest(undo_dir.path()).unwrap_err();
+    assert!(error.to_string().contains("permanent"));
+    assert!(
+        log_path.exists(),
+        "The log still describes the last deletion"
+    );
+}
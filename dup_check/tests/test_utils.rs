@@ -1,4 +1,4 @@
-use dup_check::utils;
+use dup_check::utils::{self, HashAlgorithm};
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
@@ -72,6 +72,18 @@ fn test_is_hidden_file() {
     }
 }
 
+#[test]
+#[cfg(not(feature = "windows-hidden"))]
+fn test_is_hidden_without_windows_api() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Without the Win32 attribute check every platform uses the dot-file rule
+    let regular_file = create_temp_file(&temp_dir, "regular.txt", b"");
+    let dot_file = create_temp_file(&temp_dir, ".dotfile", b"");
+    assert!(!utils::is_hidden(&regular_file));
+    assert!(utils::is_hidden(&dot_file));
+}
+
 #[test]
 fn test_calculate_hash_large_file() {
     let temp_dir = TempDir::new().unwrap();
@@ -112,3 +124,121 @@ fn test_calculate_hash_nonexistent_file() {
         err_msg
     );
 }
+
+#[test]
+fn test_calculate_partial_hash_range() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = create_temp_file(&temp_dir, "file1.bin", b"xxxxSAMEyyyy");
+    let file2 = create_temp_file(&temp_dir, "file2.bin", b"zzzzSAMEwwwwwwww");
+
+    let hash1 = utils::calculate_partial_hash(&file1, 4, 4).unwrap();
+    let hash2 = utils::calculate_partial_hash(&file2, 4, 4).unwrap();
+    assert_eq!(hash1, hash2, "Matching windows should hash equally");
+
+    let whole1 = utils::calculate_partial_hash(&file1, 0, 4).unwrap();
+    let whole2 = utils::calculate_partial_hash(&file2, 0, 4).unwrap();
+    assert_ne!(whole1, whole2);
+}
+
+#[test]
+fn test_calculate_hash_across_buffer_sizes() {
+    let temp_dir = TempDir::new().unwrap();
+    for len in [
+        0usize,
+        1,
+        200,
+        4096,
+        65_537,
+        1024 * 1024,
+        3 * 1024 * 1024 + 7,
+    ] {
+        let content: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let path = create_temp_file(&temp_dir, &format!("file_{}.bin", len), &content);
+        assert_eq!(
+            utils::calculate_hash(&path).unwrap(),
+            utils::hash_bytes(&content),
+            "Hash mismatch for a {} byte file",
+            len
+        );
+    }
+
+    assert_eq!(
+        utils::buffer_size_for(200),
+        256,
+        "Tiny files get tiny buffers"
+    );
+    assert_eq!(utils::buffer_size_for(0), 1);
+    assert_eq!(utils::buffer_size_for(10 * 1024 * 1024), 1024 * 1024);
+    assert_eq!(utils::buffer_size_for(u64::MAX), 1024 * 1024);
+}
+
+#[test]
+fn test_calculate_hash_with_algorithms() {
+    let temp_dir = TempDir::new().unwrap();
+    let empty = create_temp_file(&temp_dir, "empty.txt", b"");
+
+    assert_eq!(
+        utils::calculate_hash_with(&empty, HashAlgorithm::Sha256).unwrap(),
+        utils::calculate_hash(&empty).unwrap(),
+        "calculate_hash stays SHA-256"
+    );
+    assert_eq!(
+        utils::calculate_hash_with(&empty, HashAlgorithm::Blake3).unwrap(),
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+    assert_eq!(
+        utils::calculate_hash_with(&empty, HashAlgorithm::XxHash).unwrap(),
+        "99aa06d3014798d86001c324468d497f"
+    );
+}
+
+#[test]
+fn test_format_time() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    assert_eq!(utils::format_time(UNIX_EPOCH), "1970-01-01 00:00:00");
+    assert_eq!(
+        utils::format_time(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+        "2000-02-29 12:34:56"
+    );
+    assert_eq!(
+        utils::format_time(UNIX_EPOCH + Duration::from_secs(1_735_689_599)),
+        "2024-12-31 23:59:59"
+    );
+}
+
+#[test]
+fn test_parse_duration() {
+    use std::time::Duration;
+    let day = 24 * 60 * 60;
+    for (input, seconds) in [
+        ("45s", 45),
+        ("10m", 600),
+        ("12h", 12 * 60 * 60),
+        ("30d", 30 * day),
+        ("2w", 14 * day),
+        ("1y", 365 * day),
+        (" 3D ", 3 * day),
+        ("0s", 0),
+    ] {
+        assert_eq!(
+            utils::parse_duration(input).unwrap(),
+            Duration::from_secs(seconds),
+            "{}",
+            input
+        );
+    }
+
+    for invalid in ["", "30", "d", "30x", "1.5h", "-1d", "d30"] {
+        assert!(utils::parse_duration(invalid).is_err(), "{}", invalid);
+    }
+    let error = utils::parse_duration("3q").unwrap_err().to_string();
+    assert!(error.contains("Invalid duration unit"), "{}", error);
+
+    assert!(utils::parse_duration(&format!("{}s", u64::MAX)).is_ok());
+    let error = utils::parse_duration(&format!("{}m", u64::MAX))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("too large"), "{}", error);
+    assert!(utils::parse_duration("99999999999999999999d").is_err());
+}